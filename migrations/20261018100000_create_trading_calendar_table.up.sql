@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS trading_calendar(
+  trade_date date PRIMARY KEY, -- 日期
+  is_trading_day boolean NOT NULL, -- 是否為交易日
+  description text, -- 休市原因或備註（例如 TWSE 休市日期表中的名稱）
+  source text NOT NULL DEFAULT 'twse', -- 資料來源：twse（自動同步）或 manual（管理者手動維護，同步時不覆蓋）
+  created_at timestamptz DEFAULT NOW(),
+  updated_at timestamptz DEFAULT NOW()
+);
+
+-- 缺漏報表只看交易日
+CREATE INDEX IF NOT EXISTS idx_trading_calendar_trading_days ON trading_calendar(trade_date) WHERE is_trading_day;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS stock_quality_issues(
+  id serial PRIMARY KEY,
+  trade_date date NOT NULL, -- 交易日
+  stock_code text NOT NULL, -- 證券代號
+  rule text NOT NULL, -- 規則名稱：ohlc、volume_amount、price_change、price_limit
+  severity text NOT NULL, -- error 或 warning
+  message text NOT NULL, -- 問題說明
+  details jsonb, -- 檢查時使用的數值
+  rejected boolean NOT NULL DEFAULT FALSE, -- 是否因此未寫入 stock_day_all
+  created_at timestamptz DEFAULT NOW(),
+  UNIQUE (trade_date, stock_code, rule) -- 重新匯入時覆蓋同一個問題
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_quality_issues_stock_code ON stock_quality_issues(stock_code, trade_date);
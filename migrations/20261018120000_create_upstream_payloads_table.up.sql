@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS upstream_payloads(
+  id bigserial PRIMARY KEY,
+  source text NOT NULL, -- 資料來源，例如 stock_day_all
+  url text NOT NULL, -- 實際請求的網址（含查詢參數）
+  status_code integer NOT NULL, -- HTTP 狀態碼
+  request_date date, -- 要求的日期，抓最新資料時為 NULL
+  trade_date date, -- 從回應解析出的交易日，解析失敗時為 NULL
+  body_gzip bytea NOT NULL, -- gzip 壓縮後的原始回應
+  body_size integer NOT NULL, -- 壓縮前的大小（bytes）
+  sha256 text NOT NULL, -- 原始回應的 SHA-256
+  fetched_at timestamptz NOT NULL DEFAULT NOW(), -- 第一次抓到的時間
+  last_fetched_at timestamptz NOT NULL DEFAULT NOW(), -- 最後一次抓到相同內容的時間
+  UNIQUE (source, sha256) -- 相同內容只存一份
+);
+
+CREATE INDEX IF NOT EXISTS idx_upstream_payloads_source_date ON upstream_payloads(source, (COALESCE(trade_date, request_date)));
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS index_day(
+  id serial PRIMARY KEY,
+  trade_date date NOT NULL, -- 交易日
+  index_name text NOT NULL, -- 指數名稱，例如 發行量加權股價指數、半導體類指數
+  close_value numeric(12, 2) NOT NULL, -- 收盤指數
+  change_points numeric(12, 2), -- 漲跌點數（下跌為負）
+  change_pct numeric(8, 2), -- 漲跌百分比（下跌為負）
+  UNIQUE (trade_date, index_name) -- 每天每個指數只能有一筆紀錄
+);
+
+CREATE INDEX IF NOT EXISTS idx_index_day_index_name ON index_day(index_name, trade_date);
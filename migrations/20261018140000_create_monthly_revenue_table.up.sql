@@ -0,0 +1,22 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS monthly_revenue(
+  id serial PRIMARY KEY,
+  stock_code text NOT NULL, -- 公司代號
+  company_name text NOT NULL, -- 公司名稱
+  industry text, -- 產業別
+  revenue_month date NOT NULL, -- 資料年月（以該月第一天表示）
+  revenue bigint, -- 當月營收（千元）
+  last_month_revenue bigint, -- 上月營收（千元）
+  last_year_revenue bigint, -- 去年當月營收（千元）
+  mom_pct numeric(12, 2), -- 上月比較增減（%）
+  yoy_pct numeric(12, 2), -- 去年同月增減（%）
+  cumulative_revenue bigint, -- 當月累計營收（千元）
+  last_year_cumulative_revenue bigint, -- 去年累計營收（千元）
+  cumulative_yoy_pct numeric(12, 2), -- 累計營收前期比較增減（%）
+  note text, -- 備註
+  published_date date, -- 出表日期
+  updated_at timestamptz DEFAULT NOW(),
+  UNIQUE (stock_code, revenue_month) -- 每家公司每月一筆，更正時覆蓋
+);
+
+CREATE INDEX IF NOT EXISTS idx_monthly_revenue_month ON monthly_revenue(revenue_month);
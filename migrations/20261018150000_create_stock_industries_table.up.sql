@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS stock_industries(
+  stock_code text PRIMARY KEY, -- 證券代號
+  industry text NOT NULL, -- 產業別
+  source text NOT NULL DEFAULT 'monthly_revenue', -- monthly_revenue: 由月營收產業別帶入, manual: 手動維護
+  updated_at timestamptz DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_industries_industry ON stock_industries(industry);
+
+-- 以既有月營收資料中最新的產業別初始化
+INSERT INTO stock_industries (stock_code, industry)
+SELECT DISTINCT ON (stock_code) stock_code, industry
+FROM monthly_revenue
+WHERE industry IS NOT NULL
+ORDER BY stock_code, revenue_month DESC
+ON CONFLICT (stock_code) DO NOTHING;
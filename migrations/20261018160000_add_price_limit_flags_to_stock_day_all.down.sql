@@ -0,0 +1,10 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS idx_stock_day_all_limit_flags;
+
+ALTER TABLE stock_day_all
+  DROP COLUMN IF EXISTS limit_up,
+  DROP COLUMN IF EXISTS limit_down,
+  DROP COLUMN IF EXISTS touched_limit_up,
+  DROP COLUMN IF EXISTS touched_limit_down,
+  DROP COLUMN IF EXISTS halted;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+ALTER TABLE stock_day_all
+  ADD COLUMN IF NOT EXISTS limit_up boolean NOT NULL DEFAULT false, -- 收盤漲停
+  ADD COLUMN IF NOT EXISTS limit_down boolean NOT NULL DEFAULT false, -- 收盤跌停
+  ADD COLUMN IF NOT EXISTS touched_limit_up boolean NOT NULL DEFAULT false, -- 盤中觸及漲停但收盤未鎖住
+  ADD COLUMN IF NOT EXISTS touched_limit_down boolean NOT NULL DEFAULT false, -- 盤中觸及跌停但收盤未鎖住
+  ADD COLUMN IF NOT EXISTS halted boolean NOT NULL DEFAULT false; -- 當日沒有成交價（暫停交易或全日無成交）
+
+-- 既有資料沒有價格的列視為停止交易；漲跌停標記可用 recompute-limit-flags 指令補算
+UPDATE stock_day_all SET halted = true WHERE close_price IS NULL;
+
+-- 只索引有標記的列，查詢「某日漲停股」時使用
+CREATE INDEX IF NOT EXISTS idx_stock_day_all_limit_flags ON stock_day_all(trade_date)
+WHERE limit_up OR limit_down OR touched_limit_up OR touched_limit_down OR halted;
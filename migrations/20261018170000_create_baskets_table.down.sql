@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS basket_components;
+DROP TABLE IF EXISTS baskets;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS baskets(
+  id serial PRIMARY KEY,
+  name text NOT NULL, -- 名稱
+  weighting text NOT NULL DEFAULT 'equal', -- equal: 等權重, fixed: 固定權重, trade_amount: 依近期成交金額
+  rebalance text NOT NULL DEFAULT 'monthly', -- 再平衡週期: monthly, quarterly, yearly, never
+  base_value double precision NOT NULL DEFAULT 100, -- 起始指數值
+  created_at timestamptz DEFAULT NOW(),
+  updated_at timestamptz NOT NULL DEFAULT NOW(), -- 定義變更時更新，同時作為圖表快取版本
+  CHECK (weighting IN ('equal', 'fixed', 'trade_amount')),
+  CHECK (rebalance IN ('monthly', 'quarterly', 'yearly', 'never')),
+  CHECK (base_value > 0)
+);
+
+CREATE TABLE IF NOT EXISTS basket_components(
+  basket_id integer NOT NULL REFERENCES baskets(id) ON DELETE CASCADE,
+  stock_code text NOT NULL, -- 證券代號
+  weight double precision, -- 固定權重（只有 fixed 使用，會自動正規化）
+  PRIMARY KEY (basket_id, stock_code)
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP MATERIALIZED VIEW IF EXISTS industry_daily_summary;
+DROP MATERIALIZED VIEW IF EXISTS market_daily_summary;
@@ -0,0 +1,42 @@
+-- Add up migration script here
+
+-- 每日全市場統計（漲跌家數、成交量值、漲跌停家數）
+CREATE MATERIALIZED VIEW IF NOT EXISTS market_daily_summary AS
+SELECT trade_date,
+       COUNT(*) AS stock_count, -- 當日資料筆數
+       COUNT(*) FILTER (WHERE price_change > 0) AS advancers, -- 上漲家數
+       COUNT(*) FILTER (WHERE price_change < 0) AS decliners, -- 下跌家數
+       COUNT(*) FILTER (WHERE price_change = 0) AS unchanged, -- 平盤家數
+       COUNT(*) FILTER (WHERE limit_up) AS limit_up_count, -- 漲停家數
+       COUNT(*) FILTER (WHERE limit_down) AS limit_down_count, -- 跌停家數
+       COUNT(*) FILTER (WHERE halted) AS halted_count, -- 無成交價家數
+       COALESCE(SUM(trade_volume), 0)::bigint AS total_volume, -- 成交股數合計
+       COALESCE(SUM(trade_amount), 0)::bigint AS total_amount, -- 成交金額合計
+       COALESCE(SUM(transaction_count), 0)::bigint AS total_transactions -- 成交筆數合計
+FROM stock_day_all
+GROUP BY trade_date;
+
+-- REFRESH ... CONCURRENTLY 需要唯一索引
+CREATE UNIQUE INDEX IF NOT EXISTS idx_market_daily_summary_date ON market_daily_summary(trade_date);
+
+-- 每日各產業統計，沒有產業分類的股票歸入「未分類」
+CREATE MATERIALIZED VIEW IF NOT EXISTS industry_daily_summary AS
+SELECT d.trade_date,
+       COALESCE(i.industry, '未分類') AS industry,
+       COUNT(*) AS stock_count,
+       COUNT(*) FILTER (WHERE d.price_change > 0) AS advancers,
+       COUNT(*) FILTER (WHERE d.price_change < 0) AS decliners,
+       COALESCE(SUM(d.trade_amount), 0)::bigint AS trade_amount,
+       -- 以成交金額加權的平均漲跌幅（%）
+       ROUND((
+         SUM(d.price_change / (d.close_price - d.price_change) * 100 * d.trade_amount)
+           FILTER (WHERE d.close_price - d.price_change > 0 AND d.trade_amount > 0)
+         / NULLIF(SUM(d.trade_amount)
+           FILTER (WHERE d.close_price - d.price_change > 0 AND d.trade_amount > 0), 0)
+       )::numeric, 2)::float8 AS change_pct
+FROM stock_day_all d
+LEFT JOIN stock_industries i ON i.stock_code = d.stock_code
+GROUP BY d.trade_date, COALESCE(i.industry, '未分類');
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_industry_daily_summary_date_industry
+ON industry_daily_summary(trade_date, industry);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+  id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  family_id uuid NOT NULL, -- 同一次登入輪替出來的 token 共用，偵測到重複使用時整個家族一起撤銷
+  parent_id uuid REFERENCES refresh_tokens(id) ON DELETE SET NULL, -- 輪替前的 token
+  token_hash text NOT NULL UNIQUE, -- token 的 SHA-256，不保存原文
+  user_agent text,
+  created_at timestamptz NOT NULL DEFAULT NOW(),
+  expires_at timestamptz NOT NULL,
+  used_at timestamptz, -- 已被用來換發新 token 的時間
+  revoked_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user_id ON refresh_tokens(user_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE user_identities DROP COLUMN IF EXISTS token_key_id;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE user_identities
+  ADD COLUMN IF NOT EXISTS token_key_id text; -- 加密 access_token / refresh_token 使用的金鑰 kid，NULL 表示明文
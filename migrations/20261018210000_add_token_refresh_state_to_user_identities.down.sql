@@ -0,0 +1,10 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS idx_user_identities_refresh_due;
+
+ALTER TABLE user_identities
+  DROP COLUMN IF EXISTS needs_reconsent,
+  DROP COLUMN IF EXISTS last_refresh_error,
+  DROP COLUMN IF EXISTS refresh_failures,
+  DROP COLUMN IF EXISTS last_refresh_attempt_at,
+  DROP COLUMN IF EXISTS last_refreshed_at;
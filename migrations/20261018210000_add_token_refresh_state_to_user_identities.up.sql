@@ -0,0 +1,11 @@
+-- Add up migration script here
+ALTER TABLE user_identities
+  ADD COLUMN IF NOT EXISTS last_refreshed_at timestamptz, -- 最後一次成功換發 access token 的時間
+  ADD COLUMN IF NOT EXISTS last_refresh_attempt_at timestamptz, -- 最後一次嘗試換發的時間，失敗時用於退避
+  ADD COLUMN IF NOT EXISTS refresh_failures int NOT NULL DEFAULT 0, -- 連續換發失敗次數
+  ADD COLUMN IF NOT EXISTS last_refresh_error text,
+  ADD COLUMN IF NOT EXISTS needs_reconsent boolean NOT NULL DEFAULT FALSE; -- 提供者已撤銷授權，需使用者重新登入授權
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_refresh_due
+  ON user_identities(expires_at)
+  WHERE refresh_token IS NOT NULL AND NOT needs_reconsent;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS idx_baskets_user_id;
+
+ALTER TABLE baskets DROP COLUMN IF EXISTS user_id;
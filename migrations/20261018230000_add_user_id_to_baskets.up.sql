@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE baskets
+  ADD COLUMN IF NOT EXISTS user_id uuid REFERENCES users(id) ON DELETE CASCADE; -- 建立者，只有建立者可以讀取與修改定義
+
+-- 加入前建立的組合 user_id 為 NULL，任何 API 都讀不到（包含 basket:{id} 的序列）；
+-- 要保留的組合請在部署後指定擁有者，例如：
+--   UPDATE baskets SET user_id = '<users.id>' WHERE user_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_baskets_user_id ON baskets(user_id);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users
+  ADD COLUMN IF NOT EXISTS is_admin boolean NOT NULL DEFAULT false; -- 可使用 /admin 管理路由，需直接以 SQL 指派
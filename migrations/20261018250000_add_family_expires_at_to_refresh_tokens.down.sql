@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE refresh_tokens
+  DROP COLUMN IF EXISTS family_expires_at;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- 家族的絕對期限：輪替出的 token 不會超過這個時間，到期後必須重新登入
+ALTER TABLE refresh_tokens
+  ADD COLUMN IF NOT EXISTS family_expires_at timestamptz;
+
+-- 既有的家族無法得知原本的期限，以家族中最晚的到期時間為準，不再延長
+UPDATE refresh_tokens t
+SET family_expires_at = f.expires_at
+FROM (
+  SELECT family_id, MAX(expires_at) AS expires_at
+  FROM refresh_tokens
+  GROUP BY family_id
+) f
+WHERE t.family_id = f.family_id
+  AND t.family_expires_at IS NULL;
+
+ALTER TABLE refresh_tokens
+  ALTER COLUMN family_expires_at SET NOT NULL;
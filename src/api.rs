@@ -1,2 +1,4 @@
+pub mod auth;
 pub mod handlers;
+pub mod query;
 pub mod response;
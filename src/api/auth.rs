@@ -0,0 +1,229 @@
+// src/api/auth.rs
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{Extensions, HeaderMap, Method, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    error::AppError,
+    services::{
+        api_key, jwt, session,
+        users::{self, User},
+    },
+    state::AppState,
+};
+
+/// 呼叫者的驗證方式
+#[derive(Debug, Clone)]
+pub enum AuthMethod {
+    /// 瀏覽器的 session cookie，帶有 session 識別碼
+    Session(String),
+    /// `Authorization: Bearer` 的 JWT access token
+    Bearer,
+    /// `X-Api-Key` 或 `Authorization: Bearer ak_...` 的 API key
+    ApiKey,
+}
+
+/// 路由所屬的資源，API key 需要 `{資源}:read`（GET、HEAD）或 `{資源}:write`（其他方法）權限
+///
+/// 以 `route_layer(Extension(ScopeResource(..)))` 套在 `require_auth` 之後（外層先執行）；
+/// 沒有標記資源的路由一律不接受 API key。
+#[derive(Debug, Clone, Copy)]
+pub struct ScopeResource(pub &'static str);
+
+/// 以 POST 傳送查詢條件、不會修改資料的路由，API key 只需要 `{資源}:read` 權限
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnly;
+
+fn required_scope(method: &Method, extensions: &Extensions) -> Option<String> {
+    let ScopeResource(resource) = extensions.get::<ScopeResource>()?;
+    let read_only = extensions.get::<ReadOnly>().is_some();
+    let action = if read_only || method == Method::GET || method == Method::HEAD {
+        "read"
+    } else {
+        "write"
+    };
+    Some(format!("{}:{}", resource, action))
+}
+
+/// 已登入的使用者，handler 參數中加上它即要求登入（未登入回傳 401，帳號停用回傳 403）
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user: User,
+    pub method: AuthMethod,
+}
+
+impl CurrentUser {
+    /// 目前 session 的識別碼（以 bearer token 呼叫時為 `None`）
+    pub fn session_id(&self) -> Option<&str> {
+        match &self.method {
+            AuthMethod::Session(id) => Some(id),
+            AuthMethod::Bearer | AuthMethod::ApiKey => None,
+        }
+    }
+}
+
+/// 可選的登入使用者：沒有帶任何憑證時為 `None`，憑證無效或帳號停用時仍會拒絕
+#[derive(Debug, Clone)]
+pub struct OptionalUser(pub Option<CurrentUser>);
+
+/// 依序以 API key、bearer token、session cookie 解析呼叫者
+///
+/// 帶了 API key 或 bearer token 卻無效時直接拒絕，不會退回 cookie；cookie 過期則視為未登入。
+/// API key 只能用在標記了資源的路由，且必須具備 `scope` 權限。
+async fn resolve(
+    state: &AppState,
+    headers: &HeaderMap,
+    scope: Option<&str>,
+) -> Result<Option<CurrentUser>, AppError> {
+    let auth = &state.config.auth;
+    let (user_id, method) = if let Some(key) = api_key::from_headers(headers) {
+        let grant = api_key::authenticate(&state.db, key)
+            .await?
+            .ok_or_else(|| AppError::unauthorized("API key 無效、已過期或已撤銷"))?;
+        let Some(scope) = scope else {
+            return Err(AppError::forbidden("此功能不接受 API key"));
+        };
+        if !grant.scopes.iter().any(|granted| granted == scope) {
+            tracing::info!("API key {} 缺少 {} 權限", grant.id, scope);
+            return Err(AppError::forbidden(format!("API key 缺少 {} 權限", scope)));
+        }
+        (grant.user_id, AuthMethod::ApiKey)
+    } else if let Some(token) = jwt::bearer_token(headers) {
+        let claims = jwt::verify_access_token(&auth.jwt, token)?;
+        (claims.sub, AuthMethod::Bearer)
+    } else if let Some(token) = session::token_from_headers(headers, auth)
+        && let Some(active) = session::authenticate(&state.redis, auth, &token).await?
+    {
+        (active.session.user_id, AuthMethod::Session(active.id))
+    } else {
+        return Ok(None);
+    };
+
+    let Some(user) = users::find(&state.db, user_id).await? else {
+        return Ok(None);
+    };
+    if !user.is_active {
+        return Err(AppError::forbidden("帳號已停用"));
+    }
+
+    Ok(Some(CurrentUser { user, method }))
+}
+
+impl FromRequestParts<Arc<AppState>> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // 經過 require_auth 的路由已經解析過，直接沿用
+        if let Some(user) = parts.extensions.get::<CurrentUser>() {
+            return Ok(user.clone());
+        }
+        let scope = required_scope(&parts.method, &parts.extensions);
+        resolve(state, &parts.headers, scope.as_deref())
+            .await?
+            .ok_or_else(|| AppError::unauthorized("尚未登入"))
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for OptionalUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<CurrentUser>() {
+            return Ok(Self(Some(user.clone())));
+        }
+        let scope = required_scope(&parts.method, &parts.extensions);
+        Ok(Self(
+            resolve(state, &parts.headers, scope.as_deref()).await?,
+        ))
+    }
+}
+
+/// 要求登入的路由層，用 `route_layer` 套在整組路由上
+///
+/// 解析出的使用者會放進 request extensions，後續的 `CurrentUser` 不會重複查詢。
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let scope = required_scope(req.method(), req.extensions());
+    let user = resolve(&state, req.headers(), scope.as_deref())
+        .await?
+        .ok_or_else(|| AppError::unauthorized("尚未登入"))?;
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+/// 要求管理員的路由層，套在 `require_auth` 內層（先 `route_layer(require_admin)` 再 `route_layer(require_auth)`）
+///
+/// 以 API key 呼叫時看的是金鑰擁有者是否為管理員，金鑰本身仍需具備路由的權限。
+pub async fn require_admin(req: Request, next: Next) -> Result<Response, AppError> {
+    let Some(current) = req.extensions().get::<CurrentUser>() else {
+        return Err(AppError::unauthorized("尚未登入"));
+    };
+    if !current.user.is_admin {
+        tracing::info!(
+            "非管理員 user {} 嘗試使用 {}",
+            current.user.id,
+            req.uri().path()
+        );
+        return Err(AppError::forbidden("需要管理員權限"));
+    }
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extensions(resource: Option<&'static str>, read_only: bool) -> Extensions {
+        let mut extensions = Extensions::new();
+        if let Some(resource) = resource {
+            extensions.insert(ScopeResource(resource));
+        }
+        if read_only {
+            extensions.insert(ReadOnly);
+        }
+        extensions
+    }
+
+    #[test]
+    fn scope_follows_method_and_read_only_marker() {
+        let stocks = extensions(Some("stocks"), false);
+        for method in [Method::GET, Method::HEAD] {
+            assert_eq!(
+                required_scope(&method, &stocks).as_deref(),
+                Some("stocks:read")
+            );
+        }
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert_eq!(
+                required_scope(&method, &stocks).as_deref(),
+                Some("stocks:write")
+            );
+        }
+
+        // POST 查詢路由標記為唯讀
+        let read_only = extensions(Some("stocks"), true);
+        assert_eq!(
+            required_scope(&Method::POST, &read_only).as_deref(),
+            Some("stocks:read")
+        );
+
+        // 沒有標記資源的路由不接受 API key
+        assert_eq!(required_scope(&Method::GET, &extensions(None, false)), None);
+        assert_eq!(required_scope(&Method::POST, &extensions(None, true)), None);
+    }
+}
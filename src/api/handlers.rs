@@ -1,6 +1,43 @@
+mod analytics;
+mod api_key;
+mod auth;
+mod basket;
+mod chart;
 pub mod health;
+mod identity;
+mod index;
+mod industry;
+mod market;
+mod monthly_revenue;
+mod session;
+mod stock;
+mod token;
+mod trading_calendar;
 mod upload;
 
 // 重新導出常用處理函數，方便引入
-pub use health::{get_stock_day_all, handler_404, health_fail, health_ok};
+pub use analytics::{compare_stocks, portfolio_risk, stock_dca, stock_risk};
+pub use api_key::{create_api_key, list_api_keys, revoke_api_key};
+pub use auth::{
+    current_user, discord_callback, discord_link, discord_login, google_callback, google_link,
+    google_login, logout, oidc_callback, oidc_link, oidc_login,
+};
+pub use basket::{create_basket, delete_basket, get_basket, list_baskets, update_basket};
+pub use chart::{index_chart_png, index_chart_svg, stock_chart_png, stock_chart_svg};
+pub use health::{handler_404, health_fail, health_ok};
+pub use identity::{decline_merge, list_identities, merge_identity, unlink_identity};
+pub use index::{index_daily, ingest_index_day, list_indices};
+pub use industry::{industry_heatmap, upsert_stock_industry};
+pub use market::{market_summary, refresh_market_summary};
+pub use monthly_revenue::{ingest_monthly_revenue, screen_monthly_revenue, stock_monthly_revenue};
+pub use session::{list_sessions, revoke_all_sessions, revoke_session};
+pub use stock::{
+    get_stock_day_all, ingest_stock_day_all, list_price_limit_stocks, list_quality_issues,
+    recompute_price_limit_flags, reprocess_stock_day_all, stock_daily,
+};
+pub use token::{issue_token, refresh_token, revoke_token};
+pub use trading_calendar::{
+    delete_trading_day, list_trading_calendar, sync_trading_calendar, trading_calendar_gaps,
+    upsert_trading_day,
+};
 pub use upload::upload_image;
@@ -0,0 +1,255 @@
+// src/api/handlers/analytics.rs
+
+use crate::{
+    api::{auth::OptionalUser, query::DateRange, response::success},
+    error::AppError,
+    services::{
+        comparison,
+        dca::{self, DcaParams},
+        index_day::TAIEX,
+        risk::{self, RiskMetrics},
+        series::{self, Series, SeriesSource},
+    },
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// 組合最多可包含的標的數
+const MAX_PORTFOLIO_HOLDINGS: usize = 50;
+
+/// 比較頁最多可同時比較的標的數
+const MAX_COMPARE_CODES: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    /// 以逗號分隔的證券代號，例如 `2330,2317,0050`
+    pub codes: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DcaQuery {
+    /// 每期投入金額（元）
+    pub amount: f64,
+    /// 每月扣款日，預設 6 日
+    pub day: Option<u32>,
+    /// 配息是否再投入，預設否
+    #[serde(default)]
+    pub reinvest: bool,
+    /// 手續費率，預設 0.1425%
+    pub fee_rate: Option<f64>,
+    /// 每筆最低手續費，預設 1 元（定期定額常見的優惠）
+    pub min_fee: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RiskQuery {
+    /// 計算使用的交易日數，預設 250（約一年）
+    pub window: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RiskResponse {
+    pub code: String,
+    pub name: String,
+    pub window: i64,
+    pub metrics: RiskMetrics,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PortfolioHolding {
+    pub code: String,
+    pub shares: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PortfolioRiskRequest {
+    pub holdings: Vec<PortfolioHolding>,
+    pub window: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioRiskResponse {
+    pub window: i64,
+    /// 最後一個共同交易日的組合市值
+    pub latest_value: f64,
+    pub metrics: RiskMetrics,
+}
+
+fn parse_window(window: Option<i64>) -> Result<i64, AppError> {
+    match window.unwrap_or(250) {
+        window @ 20..=2500 => Ok(window),
+        _ => Err(AppError::bad_request("window 必須介於 20 到 2500 之間")),
+    }
+}
+
+/// 讀取與價值序列同期間的 TAIEX 收盤，尚未匯入指數時回傳 `None`
+async fn load_benchmark(
+    db: &PgPool,
+    values: &[(NaiveDate, f64)],
+) -> Result<Option<Vec<(NaiveDate, f64)>>, AppError> {
+    let (Some((from, _)), Some((to, _))) = (values.first(), values.last()) else {
+        return Ok(None);
+    };
+
+    match SeriesSource::Index(TAIEX.to_string())
+        .load(db, Some(*from), Some(*to), i64::MAX)
+        .await
+    {
+        Ok(benchmark) => Ok(Some(benchmark.closes())),
+        Err(e) if e.status_code == StatusCode::NOT_FOUND => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 個股風險指標：年化波動率、相對 TAIEX 的 beta 與相關係數、最大回撤、下檔標準差
+pub async fn stock_risk(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(query): Query<RiskQuery>,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    let window = parse_window(query.window)?;
+
+    // window 個日報酬需要 window + 1 個收盤價
+    let series = SeriesSource::from_stock_code(code, current.map(|current| current.user.id))?
+        .load(&state.db, None, None, window + 1)
+        .await?;
+    let values = series.closes();
+    let benchmark = load_benchmark(&state.db, &values).await?;
+    let metrics = risk::compute(&values, benchmark.as_deref())?;
+
+    Ok(success(RiskResponse {
+        code: series.code,
+        name: series.name,
+        window,
+        metrics,
+    }))
+}
+
+/// 組合風險指標：以持股數乘上收盤價得到每日市值，再套用與個股相同的計算
+pub async fn portfolio_risk(
+    State(state): State<Arc<AppState>>,
+    OptionalUser(current): OptionalUser,
+    Json(body): Json<PortfolioRiskRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let viewer = current.map(|current| current.user.id);
+    let window = parse_window(body.window)?;
+    if body.holdings.is_empty() || body.holdings.len() > MAX_PORTFOLIO_HOLDINGS {
+        return Err(AppError::bad_request(format!(
+            "holdings 必須有 1 到 {} 檔",
+            MAX_PORTFOLIO_HOLDINGS
+        )));
+    }
+    if body.holdings.iter().any(|h| h.shares <= 0.0) {
+        return Err(AppError::bad_request("shares 必須大於 0"));
+    }
+
+    let mut all_series: Vec<Series> = Vec::with_capacity(body.holdings.len());
+    for holding in &body.holdings {
+        let series = SeriesSource::from_stock_code(holding.code.clone(), viewer)?
+            .load(&state.db, None, None, window + 1)
+            .await?;
+        all_series.push(series);
+    }
+
+    let (dates, closes) = series::align_closes(&all_series);
+    let values: Vec<(NaiveDate, f64)> = dates
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let value = body
+                .holdings
+                .iter()
+                .zip(&closes)
+                .map(|(holding, closes)| holding.shares * closes[i])
+                .sum();
+            (*date, value)
+        })
+        .collect();
+
+    let benchmark = load_benchmark(&state.db, &values).await?;
+    let metrics = risk::compute(&values, benchmark.as_deref())?;
+
+    Ok(success(PortfolioRiskResponse {
+        window,
+        latest_value: values.last().map(|(_, v)| *v).unwrap_or_default(),
+        metrics,
+    }))
+}
+
+/// 多檔標的比較：日報酬相關係數矩陣與重新定基為 100 的累積績效
+///
+/// 各檔缺資料的日期會被略過，只使用所有標的都有交易的日期。
+pub async fn compare_stocks(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<DateRange>,
+    Query(query): Query<CompareQuery>,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let viewer = current.map(|current| current.user.id);
+
+    let mut codes: Vec<&str> = Vec::new();
+    for code in query
+        .codes
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+    {
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    if codes.len() < 2 || codes.len() > MAX_COMPARE_CODES {
+        return Err(AppError::bad_request(format!(
+            "codes 必須有 2 到 {} 檔",
+            MAX_COMPARE_CODES
+        )));
+    }
+
+    let mut all_series: Vec<Series> = Vec::with_capacity(codes.len());
+    for code in codes {
+        let series = SeriesSource::from_stock_code(code, viewer)?
+            .load(&state.db, Some(range.from), Some(range.to), i64::MAX)
+            .await?;
+        all_series.push(series);
+    }
+
+    Ok(success(comparison::compare(&all_series)?))
+}
+
+/// 以歷史收盤價模擬定期定額投資
+pub async fn stock_dca(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(range): Query<DateRange>,
+    Query(query): Query<DcaQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let fee_rate = query.fee_rate.unwrap_or(0.001425);
+    let min_fee = query.min_fee.unwrap_or(1.0);
+    if !(0.0..0.1).contains(&fee_rate) || min_fee < 0.0 {
+        return Err(AppError::bad_request("手續費設定不合理"));
+    }
+
+    let params = DcaParams {
+        from: range.from,
+        to: range.to,
+        amount: query.amount,
+        day_of_month: query.day.unwrap_or(6),
+        reinvest_dividends: query.reinvest,
+        fee_rate,
+        min_fee,
+    };
+    let result = dca::simulate(&state.db, &code, &params).await?;
+    Ok(success(result))
+}
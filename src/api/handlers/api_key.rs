@@ -0,0 +1,69 @@
+// src/api/handlers/api_key.rs
+
+use crate::{
+    api::{auth::CurrentUser, response::success},
+    error::AppError,
+    services::api_key,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyBody {
+    pub name: String,
+    /// 例如 `stocks:read`、`uploads:write`
+    pub scopes: Vec<String>,
+    /// 有效天數（1 到 3650），省略時不過期
+    pub expires_in_days: Option<u32>,
+}
+
+/// 列出目前使用者的 API key
+pub async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let keys = api_key::list(&state.db, current.user.id).await?;
+    Ok(success(keys))
+}
+
+/// 建立 API key，金鑰只會在這次回應中出現
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Json(body): Json<CreateApiKeyBody>,
+) -> Result<impl IntoResponse, AppError> {
+    let created = api_key::create(
+        &state.db,
+        current.user.id,
+        &body.name,
+        &body.scopes,
+        body.expires_in_days,
+    )
+    .await?;
+    tracing::info!(
+        "🗝️ 建立 API key: user {}, {} ({})",
+        current.user.id,
+        created.api_key.prefix,
+        created.api_key.scopes.join(",")
+    );
+    Ok(success(created))
+}
+
+/// 撤銷 API key
+pub async fn revoke_api_key(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    if !api_key::revoke(&state.db, current.user.id, id).await? {
+        return Err(AppError::not_found("找不到此 API key"));
+    }
+    Ok(success("已撤銷"))
+}
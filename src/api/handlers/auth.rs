@@ -0,0 +1,241 @@
+// src/api/handlers/auth.rs
+
+use crate::{
+    api::{
+        auth::{CurrentUser, OptionalUser},
+        response::success,
+    },
+    error::AppError,
+    services::{
+        oauth::{self, CallbackParams, LoginOutcome, Provider},
+        session::{self, ClientInfo},
+        users::User,
+    },
+    state::AppState,
+};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderName, header},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+use std::{net::SocketAddr, sync::Arc};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    /// 登入完成後導回的站內路徑
+    pub redirect: Option<String>,
+}
+
+/// 導向提供者的授權頁面並設定 state cookie；`link_user_id` 有值時為綁定流程
+async fn login(
+    state: &AppState,
+    provider: Provider,
+    query: LoginQuery,
+    link_user_id: Option<Uuid>,
+) -> Result<Response, AppError> {
+    let request =
+        oauth::authorization_url(state, &provider, query.redirect.as_deref(), link_user_id).await?;
+    Ok((
+        AppendHeaders([(header::SET_COOKIE, request.state_cookie)]),
+        Redirect::to(&request.url),
+    )
+        .into_response())
+}
+
+/// 建立 session 並設定 cookie
+pub(super) async fn start_session(
+    state: &AppState,
+    user: &User,
+    client: ClientInfo,
+) -> Result<AppendHeaders<[(HeaderName, String); 1]>, AppError> {
+    if !user.is_active {
+        return Err(AppError::forbidden("帳號已停用"));
+    }
+    let auth = &state.config.auth;
+    let token = session::create(&state.redis, auth, user.id, client).await?;
+    Ok(AppendHeaders([(
+        header::SET_COOKIE,
+        session::cookie(auth, &token),
+    )]))
+}
+
+/// 完成登入或綁定，不論成功與否都清除 state cookie
+async fn callback(
+    state: &AppState,
+    provider: Provider,
+    params: CallbackParams,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    current: Option<CurrentUser>,
+) -> Response {
+    let client = ClientInfo::from_request(headers, Some(peer), &state.config.auth.trusted_proxies);
+    let state_cookie = oauth::state_from_cookie(headers);
+    let result = finish_login(
+        state,
+        provider,
+        params,
+        state_cookie.as_deref(),
+        client,
+        current,
+    )
+    .await;
+    (
+        AppendHeaders([(
+            header::SET_COOKIE,
+            oauth::clear_state_cookie(&state.config.auth),
+        )]),
+        result,
+    )
+        .into_response()
+}
+
+/// email 與既有帳號相同時以 cookie 帶著 `merge_token` 導回（網址只加上 `merge_required=true`），
+/// 由使用者決定是否合併
+async fn finish_login(
+    state: &AppState,
+    provider: Provider,
+    params: CallbackParams,
+    state_cookie: Option<&str>,
+    client: ClientInfo,
+    current: Option<CurrentUser>,
+) -> Result<Response, AppError> {
+    let current_user_id = current.map(|current| current.user.id);
+    let response =
+        match oauth::complete_login(state, &provider, params, state_cookie, current_user_id).await?
+        {
+            LoginOutcome::SignedIn { user, redirect_to } => (
+                start_session(state, &user, client).await?,
+                Redirect::to(&redirect_to),
+            )
+                .into_response(),
+            LoginOutcome::Linked { redirect_to } => Redirect::to(&redirect_to).into_response(),
+            LoginOutcome::MergeRequired {
+                merge_token,
+                redirect_to,
+            } => {
+                let separator = if redirect_to.contains('?') { '&' } else { '?' };
+                (
+                    AppendHeaders([(
+                        header::SET_COOKIE,
+                        oauth::merge_cookie(&state.config.auth, &merge_token),
+                    )]),
+                    Redirect::to(&format!("{}{}merge_required=true", redirect_to, separator)),
+                )
+                    .into_response()
+            }
+        };
+    Ok(response)
+}
+
+/// Google 登入（授權碼流程 + PKCE）
+pub async fn google_login(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    login(&state, Provider::Google, query, None).await
+}
+
+/// Google 登入回呼
+pub async fn google_callback(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CallbackParams>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    OptionalUser(current): OptionalUser,
+) -> impl IntoResponse {
+    callback(&state, Provider::Google, params, &headers, peer, current).await
+}
+
+/// Discord 登入
+pub async fn discord_login(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    login(&state, Provider::Discord, query, None).await
+}
+
+/// Discord 登入回呼
+pub async fn discord_callback(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CallbackParams>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    OptionalUser(current): OptionalUser,
+) -> impl IntoResponse {
+    callback(&state, Provider::Discord, params, &headers, peer, current).await
+}
+
+/// 以設定的 OpenID Connect 提供者登入
+pub async fn oidc_login(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let provider = Provider::oidc(&state, &name)?;
+    login(&state, provider, query, None).await
+}
+
+/// OpenID Connect 登入回呼
+pub async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<CallbackParams>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    let provider = Provider::oidc(&state, &name)?;
+    Ok(callback(&state, provider, params, &headers, peer, current).await)
+}
+
+/// 把 Google 帳號綁定到目前的使用者
+pub async fn google_link(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    login(&state, Provider::Google, query, Some(current.user.id)).await
+}
+
+/// 把 Discord 帳號綁定到目前的使用者
+pub async fn discord_link(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    login(&state, Provider::Discord, query, Some(current.user.id)).await
+}
+
+/// 把 OpenID Connect 提供者的帳號綁定到目前的使用者
+pub async fn oidc_link(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(name): Path<String>,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let provider = Provider::oidc(&state, &name)?;
+    login(&state, provider, query, Some(current.user.id)).await
+}
+
+/// 目前登入的使用者，未登入時回傳 null
+pub async fn current_user(OptionalUser(current): OptionalUser) -> impl IntoResponse {
+    success(current.map(|current| current.user))
+}
+
+/// 登出：刪除 session 並清除 cookie
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let auth = &state.config.auth;
+    if let Some(token) = session::token_from_headers(&headers, auth) {
+        session::destroy(&state.redis, &token).await?;
+    }
+
+    Ok((
+        AppendHeaders([(header::SET_COOKIE, session::clear_cookie(auth))]),
+        success("已登出"),
+    ))
+}
@@ -0,0 +1,64 @@
+// src/api/handlers/basket.rs
+
+use crate::{
+    api::{auth::CurrentUser, response::success},
+    error::AppError,
+    services::basket::{self, BasketInput},
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+/// 列出目前使用者的自訂組合
+pub async fn list_baskets(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let baskets = basket::list(&state.db, current.user.id).await?;
+    Ok(success(baskets))
+}
+
+/// 新增自訂組合；之後可用 `basket:{id}` 當作證券代號查詢日資料、圖表與風險指標
+pub async fn create_basket(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Json(body): Json<BasketInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let basket = basket::create(&state.db, current.user.id, &body).await?;
+    Ok(success(basket))
+}
+
+/// 讀取自訂組合
+pub async fn get_basket(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let basket = basket::get(&state.db, current.user.id, id).await?;
+    Ok(success(basket))
+}
+
+/// 修改自訂組合（成分股整批取代）
+pub async fn update_basket(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<i32>,
+    Json(body): Json<BasketInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let basket = basket::update(&state.db, current.user.id, id, &body).await?;
+    Ok(success(basket))
+}
+
+/// 刪除自訂組合
+pub async fn delete_basket(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    basket::delete(&state.db, current.user.id, id).await?;
+    Ok(success("成功"))
+}
@@ -0,0 +1,212 @@
+// src/api/handlers/chart.rs
+
+use crate::{
+    api::{auth::OptionalUser, query::SeriesRange},
+    error::AppError,
+    services::{
+        cache,
+        chart::{self, ChartOptions, Theme},
+        series::SeriesSource,
+    },
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// 圖表快取時間；快取鍵含最後匯入日與寫入版本，資料寫入後自然失效
+const CHART_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+/// 最多可疊加的均線數量
+const MAX_MOVING_AVERAGES: usize = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct ChartQuery {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// `light` 或 `dark`
+    pub theme: Option<String>,
+    /// 均線天數，以逗號分隔，例如 `5,20,60`
+    pub ma: Option<String>,
+}
+
+impl ChartQuery {
+    fn to_options(&self) -> Result<ChartOptions, AppError> {
+        let theme = match self.theme.as_deref() {
+            None => Theme::Light,
+            Some(s) => Theme::parse(s)
+                .ok_or_else(|| AppError::bad_request(format!("不支援的主題: {}", s)))?,
+        };
+
+        let moving_averages = match self.ma.as_deref() {
+            None | Some("") => Vec::new(),
+            Some(s) => s
+                .split(',')
+                .map(|n| match n.trim().parse::<usize>() {
+                    Ok(n) if (1..=250).contains(&n) => Ok(n),
+                    _ => Err(AppError::bad_request(format!("無效的均線天數: {}", n))),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        if moving_averages.len() > MAX_MOVING_AVERAGES {
+            return Err(AppError::bad_request(format!(
+                "最多只能疊加 {} 條均線",
+                MAX_MOVING_AVERAGES
+            )));
+        }
+
+        Ok(ChartOptions {
+            width: self.width.unwrap_or(800).clamp(200, 2000),
+            height: self.height.unwrap_or(480).clamp(150, 1500),
+            theme,
+            moving_averages,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ChartFormat {
+    Svg,
+    Png,
+}
+
+impl ChartFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Png => "png",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Svg => "image/svg+xml",
+            Self::Png => "image/png",
+        }
+    }
+}
+
+/// 個股 K 線圖（SVG）
+pub async fn stock_chart_svg(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(range): Query<SeriesRange>,
+    Query(query): Query<ChartQuery>,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    render_chart(
+        &state,
+        SeriesSource::from_stock_code(code, current.map(|current| current.user.id))?,
+        &range,
+        &query,
+        ChartFormat::Svg,
+    )
+    .await
+}
+
+/// 個股 K 線圖（PNG）
+pub async fn stock_chart_png(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(range): Query<SeriesRange>,
+    Query(query): Query<ChartQuery>,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    render_chart(
+        &state,
+        SeriesSource::from_stock_code(code, current.map(|current| current.user.id))?,
+        &range,
+        &query,
+        ChartFormat::Png,
+    )
+    .await
+}
+
+/// 指數走勢圖（SVG）
+pub async fn index_chart_svg(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(range): Query<SeriesRange>,
+    Query(query): Query<ChartQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    render_chart(
+        &state,
+        SeriesSource::Index(name),
+        &range,
+        &query,
+        ChartFormat::Svg,
+    )
+    .await
+}
+
+/// 指數走勢圖（PNG）
+pub async fn index_chart_png(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(range): Query<SeriesRange>,
+    Query(query): Query<ChartQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    render_chart(
+        &state,
+        SeriesSource::Index(name),
+        &range,
+        &query,
+        ChartFormat::Png,
+    )
+    .await
+}
+
+async fn render_chart(
+    state: &AppState,
+    source: SeriesSource,
+    range: &SeriesRange,
+    query: &ChartQuery,
+    format: ChartFormat,
+) -> Result<impl IntoResponse + use<>, AppError> {
+    range.validate()?;
+    let options = query.to_options()?;
+
+    let version = source.cache_version(&state.db, &state.redis).await?;
+    let cache_key = format!(
+        "chart:{}:{}:{}:{}x{}:{}:{:?}:{:?}:{:?}:{}",
+        source.key(),
+        version,
+        format.extension(),
+        options.width,
+        options.height,
+        options.theme.as_str(),
+        options.moving_averages,
+        range.from,
+        range.to,
+        range.limit()
+    );
+
+    let body = match cache::get_bytes(&state.redis, &cache_key).await {
+        Some(body) => body,
+        None => {
+            // 最長的均線需要區間之前 window - 1 個交易日的收盤價
+            let warmup = options
+                .moving_averages
+                .iter()
+                .max()
+                .map_or(0, |window| window - 1);
+            let (series, warmup) = source
+                .load_with_warmup(&state.db, range.from, range.to, range.limit(), warmup)
+                .await?;
+            let svg = chart::render_svg(&series, &warmup, &options);
+            let body = match format {
+                ChartFormat::Svg => svg.into_bytes(),
+                ChartFormat::Png => tokio::task::spawn_blocking(move || chart::svg_to_png(&svg))
+                    .await
+                    .map_err(|e| AppError::internal_error(format!("圖表轉檔失敗: {}", e)))??,
+            };
+            cache::set_bytes(&state.redis, &cache_key, &body, CHART_CACHE_TTL_SECS).await;
+            body
+        }
+    };
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body))
+}
@@ -0,0 +1,79 @@
+// src/api/handlers/identity.rs
+
+use super::auth::start_session;
+use crate::{
+    api::{auth::CurrentUser, response::success},
+    error::AppError,
+    services::{oauth, session::ClientInfo, users},
+    state::AppState,
+};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, header},
+    response::{AppendHeaders, IntoResponse},
+};
+use std::{net::SocketAddr, sync::Arc};
+use uuid::Uuid;
+
+/// 登入回呼設定的 merge cookie
+fn merge_token(headers: &HeaderMap) -> Result<String, AppError> {
+    oauth::merge_token_from_cookie(headers)
+        .ok_or_else(|| AppError::bad_request("沒有等待合併的登入，請重新登入"))
+}
+
+/// 列出目前使用者綁定的登入方式
+pub async fn list_identities(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let identities = users::list_identities(&state.db, current.user.id).await?;
+    Ok(success(identities))
+}
+
+/// 解除綁定一個登入方式（至少要保留一個）
+pub async fn unlink_identity(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    users::unlink_identity(&state.db, current.user.id, id).await?;
+    Ok(success("已解除綁定"))
+}
+
+/// 合併：以 email 相同的既有帳號登入後，把新的登入方式綁定到此帳號
+///
+/// merge_token 由登入回呼以 cookie 帶來；失敗時保留 cookie，換成正確的帳號後可以重試。
+pub async fn merge_identity(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    oauth::complete_merge(&state, &merge_token(&headers)?, current.user.id).await?;
+    let identities = users::list_identities(&state.db, current.user.id).await?;
+    Ok((
+        AppendHeaders([(
+            header::SET_COOKIE,
+            oauth::clear_merge_cookie(&state.config.auth),
+        )]),
+        success(identities),
+    ))
+}
+
+/// 不合併：以新的登入方式另外建立帳號並登入
+pub async fn decline_merge(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let user = oauth::decline_merge(&state, &merge_token(&headers)?).await?;
+    let client = ClientInfo::from_request(&headers, Some(peer), &state.config.auth.trusted_proxies);
+    let cookie = start_session(&state, &user, client).await?;
+    Ok((
+        AppendHeaders([(
+            header::SET_COOKIE,
+            oauth::clear_merge_cookie(&state.config.auth),
+        )]),
+        cookie,
+        success(user),
+    ))
+}
@@ -0,0 +1,53 @@
+// src/api/handlers/index.rs
+
+use crate::{
+    api::{query::SeriesRange, response::success},
+    error::AppError,
+    services::{index_day, series::SeriesSource},
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct IngestIndexQuery {
+    /// 是否先刪除該日既有資料再寫入
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// 列出所有已匯入的指數
+pub async fn list_indices(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let indices = index_day::list_indices(&state.db).await?;
+    Ok(success(indices))
+}
+
+/// 指數日資料，格式與個股日資料相同
+pub async fn index_daily(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(range): Query<SeriesRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let series = SeriesSource::Index(name)
+        .load(&state.db, range.from, range.to, range.limit())
+        .await?;
+    Ok(success(series))
+}
+
+/// 匯入指定交易日的 TAIEX 與各類指數（`?replace=true` 會取代該日既有資料）
+pub async fn ingest_index_day(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<NaiveDate>,
+    Query(query): Query<IngestIndexQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = index_day::ingest(&state, date, query.replace).await?;
+    Ok(success(summary))
+}
@@ -0,0 +1,41 @@
+// src/api/handlers/industry.rs
+
+use crate::{api::response::success, error::AppError, services::industry, state::AppState};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct HeatmapQuery {
+    /// 交易日，預設為最新交易日
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertIndustryRequest {
+    pub industry: String,
+}
+
+/// 產業 → 個股的熱力圖資料（成交金額決定大小、漲跌幅決定顏色）
+pub async fn industry_heatmap(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HeatmapQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let heatmap = industry::heatmap(&state.db, query.date).await?;
+    Ok(success(heatmap))
+}
+
+/// 手動設定個股的產業分類
+pub async fn upsert_stock_industry(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Json(body): Json<UpsertIndustryRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let row = industry::upsert_manual(&state.db, &code, &body.industry).await?;
+    Ok(success(row))
+}
@@ -0,0 +1,31 @@
+// src/api/handlers/market.rs
+
+use crate::{
+    api::{query::SeriesRange, response::success},
+    error::AppError,
+    services::market_summary,
+    state::AppState,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+/// 每日全市場統計：漲跌家數、漲跌停家數、成交量值
+pub async fn market_summary(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<SeriesRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let rows = market_summary::list_market(&state.db, range.from, range.to, range.limit()).await?;
+    Ok(success(rows))
+}
+
+/// 立即重新整理每日統計（通常在匯入後自動執行）
+pub async fn refresh_market_summary(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    market_summary::refresh(&state.db).await?;
+    Ok(success("成功"))
+}
@@ -0,0 +1,72 @@
+// src/api/handlers/monthly_revenue.rs
+
+use crate::{
+    api::response::success,
+    error::AppError,
+    services::monthly_revenue::{self, RevenueScreen},
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct StockRevenueQuery {
+    /// 取最近幾個月，預設 24
+    pub months: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevenueScreenQuery {
+    /// `YYYY-MM`，預設為最新月份
+    pub month: Option<String>,
+    /// 年增率下限（%）
+    pub min_yoy: Option<f64>,
+    /// 月增率下限（%）
+    pub min_mom: Option<f64>,
+    pub industry: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// 立即從設定的來源匯入月營收
+pub async fn ingest_monthly_revenue(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = monthly_revenue::ingest(&state).await?;
+    Ok(success(summary))
+}
+
+/// 個股月營收與同月份的收盤價
+pub async fn stock_monthly_revenue(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(query): Query<StockRevenueQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let months = query.months.unwrap_or(24).clamp(1, 240);
+    let history = monthly_revenue::stock_history(&state.db, &code, months).await?;
+    Ok(success(history))
+}
+
+/// 依年增率、月增率篩選某月份的月營收
+pub async fn screen_monthly_revenue(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RevenueScreenQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let filter = RevenueScreen {
+        revenue_month: query
+            .month
+            .as_deref()
+            .map(monthly_revenue::parse_month_param)
+            .transpose()?,
+        min_yoy: query.min_yoy,
+        min_mom: query.min_mom,
+        industry: query.industry,
+    };
+    let limit = query.limit.unwrap_or(100).clamp(1, 2000);
+
+    let rows = monthly_revenue::screen(&state.db, &filter, limit).await?;
+    Ok(success(rows))
+}
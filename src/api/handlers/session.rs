@@ -0,0 +1,78 @@
+// src/api/handlers/session.rs
+
+use crate::{
+    api::{auth::CurrentUser, response::success},
+    error::AppError,
+    services::session,
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{AppendHeaders, IntoResponse},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeAllQuery {
+    /// 為 true 時保留目前的 session（登出其他裝置）
+    #[serde(default)]
+    pub except_current: bool,
+}
+
+/// 列出目前使用者所有登入中的裝置
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = session::list(&state.redis, current.user.id, current.session_id()).await?;
+    Ok(success(sessions))
+}
+
+/// 撤銷一個 session；撤銷的是目前的 session 時一併清除 cookie
+pub async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if !session::revoke(&state.redis, current.user.id, &id).await? {
+        return Err(AppError::not_found("找不到此 session"));
+    }
+
+    let cookies: Vec<_> = (current.session_id() == Some(id.as_str()))
+        .then(|| {
+            (
+                header::SET_COOKIE,
+                session::clear_cookie(&state.config.auth),
+            )
+        })
+        .into_iter()
+        .collect();
+    Ok((AppendHeaders(cookies), success("已撤銷")))
+}
+
+/// 撤銷目前使用者的所有 session
+pub async fn revoke_all_sessions(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Query(query): Query<RevokeAllQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let keep = current.session_id().filter(|_| query.except_current);
+    let revoked = session::revoke_all(&state.redis, current.user.id, keep).await?;
+
+    let cookies: Vec<_> = keep
+        .is_none()
+        .then(|| {
+            (
+                header::SET_COOKIE,
+                session::clear_cookie(&state.config.auth),
+            )
+        })
+        .into_iter()
+        .collect();
+    Ok((
+        AppendHeaders(cookies),
+        success(serde_json::json!({ "revoked": revoked })),
+    ))
+}
@@ -0,0 +1,134 @@
+// src/api/handlers/stock.rs
+
+use crate::{
+    api::{
+        auth::OptionalUser,
+        query::{DateRange, SeriesRange},
+        response::success,
+    },
+    error::AppError,
+    services::{
+        data_quality::{self, IssueFilter},
+        price_limit::{self, PriceLimitFlag},
+        series::SeriesSource,
+        stock_day_all,
+    },
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct IngestQuery {
+    /// 是否先刪除該日既有資料再寫入
+    #[serde(default)]
+    pub replace: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QualityIssueQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub code: Option<String>,
+    pub rule: Option<String>,
+    pub severity: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceLimitQuery {
+    /// 交易日，預設為最新交易日
+    pub date: Option<NaiveDate>,
+    /// limit_up / limit_down / touched_limit_up / touched_limit_down / halted
+    pub flag: PriceLimitFlag,
+}
+
+/// 取公開資訊觀測站 當日日成交資訊 資料並且整理進資料庫
+pub async fn get_stock_day_all(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    stock_day_all::ingest(&state, None, false).await?;
+    Ok(success("成功"))
+}
+
+/// 重新匯入指定交易日的日成交資訊（`?replace=true` 會取代該日既有資料）
+pub async fn ingest_stock_day_all(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<NaiveDate>,
+    Query(query): Query<IngestQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = stock_day_all::ingest(&state, Some(date), query.replace).await?;
+    Ok(success(summary))
+}
+
+/// 個股日資料
+pub async fn stock_daily(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Query(range): Query<SeriesRange>,
+    OptionalUser(current): OptionalUser,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let series = SeriesSource::from_stock_code(code, current.map(|current| current.user.id))?
+        .load(&state.db, range.from, range.to, range.limit())
+        .await?;
+    Ok(success(series))
+}
+
+/// 查詢匯入時發現的資料品質問題
+pub async fn list_quality_issues(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<QualityIssueQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let filter = IssueFilter {
+        from: query.from,
+        to: query.to,
+        stock_code: query.code,
+        rule: query.rule,
+        severity: query.severity,
+    };
+    let limit = query.limit.unwrap_or(500).clamp(1, 5000);
+
+    let issues = data_quality::list_issues(&state.db, &filter, limit).await?;
+    Ok(success(issues))
+}
+
+/// 以目前的解析邏輯重新處理區間內封存的 TWSE 原始回應
+pub async fn reprocess_stock_day_all(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<DateRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let results = stock_day_all::reprocess(&state, range.from, range.to).await?;
+    Ok(success(results))
+}
+
+/// 查詢某個交易日漲停、跌停、盤中觸及漲跌停或停止交易的股票
+pub async fn list_price_limit_stocks(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PriceLimitQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let stocks = price_limit::list_flagged(&state.db, query.date, query.flag).await?;
+    Ok(success(stocks))
+}
+
+/// 以資料庫中既有的日資料重新計算區間內的漲跌停標記
+pub async fn recompute_price_limit_flags(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<DateRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let summary = price_limit::recompute(
+        &state.db,
+        range.from,
+        range.to,
+        state.config.data_quality.price_limit_pct,
+    )
+    .await?;
+    Ok(success(summary))
+}
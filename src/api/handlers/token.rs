@@ -0,0 +1,73 @@
+// src/api/handlers/token.rs
+
+use crate::{
+    api::{auth::CurrentUser, response::success},
+    error::AppError,
+    services::refresh_token as refresh_tokens,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenBody {
+    pub refresh_token: String,
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// 以登入中的 session 換取 access token 與 refresh token（行動端在內嵌瀏覽器登入後呼叫）
+pub async fn issue_token(
+    State(state): State<Arc<AppState>>,
+    current: CurrentUser,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if current.session_id().is_none() {
+        return Err(AppError::bad_request(
+            "請以 session 登入後換取 token，已有 token 時請使用 refresh",
+        ));
+    }
+    let pair = refresh_tokens::issue(
+        &state.db,
+        &state.config.auth.jwt,
+        current.user.id,
+        user_agent(&headers),
+    )
+    .await?;
+    Ok(success(pair))
+}
+
+/// 以 refresh token 換發新的 token 組（refresh token 每次使用都會輪替）
+pub async fn refresh_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<RefreshTokenBody>,
+) -> Result<impl IntoResponse, AppError> {
+    let pair = refresh_tokens::rotate(
+        &state.db,
+        &state.config.auth.jwt,
+        &body.refresh_token,
+        user_agent(&headers),
+    )
+    .await?;
+    Ok(success(pair))
+}
+
+/// 撤銷 refresh token（同一次登入輪替出的 token 一併失效）
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<RefreshTokenBody>,
+) -> Result<impl IntoResponse, AppError> {
+    refresh_tokens::revoke(&state.db, &body.refresh_token).await?;
+    Ok(success("已撤銷"))
+}
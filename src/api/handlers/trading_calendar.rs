@@ -0,0 +1,84 @@
+// src/api/handlers/trading_calendar.rs
+
+use crate::{
+    api::{query::DateRange, response::success},
+    error::AppError,
+    services::trading_calendar,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertTradingDayRequest {
+    pub is_trading_day: bool,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GapReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// 有開市但 stock_day_all 沒有資料的日期
+    pub missing_dates: Vec<NaiveDate>,
+}
+
+/// 查詢交易日曆
+pub async fn list_trading_calendar(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<DateRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let days = trading_calendar::list(&state.db, range.from, range.to).await?;
+    Ok(success(days))
+}
+
+/// 從 TWSE 休市日期表同步整年的交易日曆
+pub async fn sync_trading_calendar(
+    State(state): State<Arc<AppState>>,
+    Path(year): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = trading_calendar::sync_year(&state.db, &state.http_client, year).await?;
+    Ok(success(summary))
+}
+
+/// 手動設定某一天是否開市（會標記為 manual，之後同步不會覆蓋），週六補行交易也用這裡設定
+pub async fn upsert_trading_day(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<NaiveDate>,
+    Json(body): Json<UpsertTradingDayRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let day =
+        trading_calendar::upsert_manual(&state.db, date, body.is_trading_day, body.description)
+            .await?;
+    Ok(success(day))
+}
+
+/// 刪除某一天的設定
+pub async fn delete_trading_day(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<impl IntoResponse, AppError> {
+    trading_calendar::delete(&state.db, date).await?;
+    Ok(success("成功"))
+}
+
+/// 缺漏報表：列出有開市但沒有 stock_day_all 資料的交易日，方便重新抓取
+pub async fn trading_calendar_gaps(
+    State(state): State<Arc<AppState>>,
+    Query(range): Query<DateRange>,
+) -> Result<impl IntoResponse, AppError> {
+    range.validate()?;
+    let missing_dates = trading_calendar::find_gaps(&state.db, range.from, range.to).await?;
+    Ok(success(GapReport {
+        from: range.from,
+        to: range.to,
+        missing_dates,
+    }))
+}
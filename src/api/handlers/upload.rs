@@ -161,7 +161,7 @@ pub async fn upload_image(
     // --- 5. 生成唯一檔名並執行上傳 ---
     let extension = original_filename
         .as_ref()
-        .and_then(|name| name.split('.').last())
+        .and_then(|name| name.split('.').next_back())
         .unwrap_or("jpg");
 
     let unique_filename = format!("axum-app-uploads/{}.{}", Uuid::new_v4(), extension);
@@ -0,0 +1,60 @@
+// src/api/query.rs
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+/// 日期區間查詢參數（`?from=2025-01-01&to=2025-01-31`，包含頭尾）
+#[derive(Debug, Deserialize)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// 檢查區間是否合法
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.from > self.to {
+            return Err(AppError::bad_request("from 不可晚於 to"));
+        }
+        Ok(())
+    }
+}
+
+/// 時間序列查詢參數：可指定 `from`/`to`，或用 `days` 取最近 N 個交易日
+#[derive(Debug, Deserialize)]
+pub struct SeriesRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub days: Option<i64>,
+}
+
+impl SeriesRange {
+    /// 沒有指定任何條件時預設取最近的交易日數
+    const DEFAULT_DAYS: i64 = 120;
+    /// 單次最多回傳的交易日數
+    const MAX_DAYS: i64 = 5000;
+
+    /// 檢查區間是否合法
+    pub fn validate(&self) -> Result<(), AppError> {
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from > to
+        {
+            return Err(AppError::bad_request("from 不可晚於 to"));
+        }
+        if matches!(self.days, Some(days) if days <= 0) {
+            return Err(AppError::bad_request("days 必須大於 0"));
+        }
+        Ok(())
+    }
+
+    /// 最多取幾個交易日（取區間內最新的部分）
+    pub fn limit(&self) -> i64 {
+        match (self.days, self.from) {
+            (Some(days), _) => days.min(Self::MAX_DAYS),
+            (None, Some(_)) => Self::MAX_DAYS,
+            (None, None) => Self::DEFAULT_DAYS,
+        }
+    }
+}
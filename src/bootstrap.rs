@@ -67,6 +67,7 @@ pub async fn setup_app_state(config: &AppConfig) -> Result<Arc<AppState>> {
         db,
         http_client,
         redis,
+        config: config.clone(),
     }))
 }
 
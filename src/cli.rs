@@ -0,0 +1,134 @@
+// src/cli.rs
+
+use crate::{
+    services::{index_day, monthly_revenue, price_limit, stock_day_all, token_crypto},
+    state::AppState,
+};
+use chrono::NaiveDate;
+use color_eyre::eyre::{Context, Result, bail};
+
+const USAGE: &str = "用法:
+  axum-app                                          啟動 HTTP 伺服器
+  axum-app ingest-stock-day-all <YYYY-MM-DD> [--replace]
+                                                    重新匯入指定交易日的日成交資訊
+  axum-app reprocess-stock-day-all <FROM> <TO>      以目前的解析邏輯重新處理封存的原始回應
+  axum-app ingest-index-day <YYYY-MM-DD> [--replace]
+                                                    匯入指定交易日的 TAIEX 與各類指數
+  axum-app recompute-limit-flags <FROM> <TO>        以既有日資料重新計算漲跌停與停止交易標記
+  axum-app ingest-monthly-revenue                   從設定的來源（MONTHLY_REVENUE_SOURCE）匯入月營收
+  axum-app reencrypt-tokens                         以 TOKEN_ENCRYPTION_ACTIVE_KID 重新加密所有第三方 token";
+
+/// 執行命令列指令（沒有帶任何參數時 main 會啟動伺服器）
+pub async fn run(state: &AppState, args: &[String]) -> Result<()> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => bail!(USAGE),
+    };
+
+    match command {
+        "ingest-stock-day-all" => {
+            let date = parse_date_arg(rest.first())?;
+            let replace = has_flag(rest, "--replace");
+
+            let summary = stock_day_all::ingest(state, Some(date), replace).await?;
+            println!(
+                "{}: 解析 {} 筆, 寫入 {} 筆, 刪除 {} 筆",
+                summary.trade_date,
+                summary.parsed_rows,
+                summary.inserted_rows,
+                summary.deleted_rows
+            );
+        }
+        "reprocess-stock-day-all" => {
+            let from = parse_date_arg(rest.first())?;
+            let to = parse_date_arg(rest.get(1))?;
+            if from > to {
+                bail!("FROM 不可晚於 TO");
+            }
+
+            for result in stock_day_all::reprocess(state, from, to).await? {
+                match (result.summary, result.error) {
+                    (Some(summary), _) => println!(
+                        "{} (封存 #{}): 解析 {} 筆, 寫入 {} 筆, 刪除 {} 筆",
+                        result.trade_date,
+                        result.payload_id,
+                        summary.parsed_rows,
+                        summary.inserted_rows,
+                        summary.deleted_rows
+                    ),
+                    (None, error) => println!(
+                        "{} (封存 #{}): 失敗 {}",
+                        result.trade_date,
+                        result.payload_id,
+                        error.unwrap_or_default()
+                    ),
+                }
+            }
+        }
+        "ingest-index-day" => {
+            let date = parse_date_arg(rest.first())?;
+            let replace = has_flag(rest, "--replace");
+
+            let summary = index_day::ingest(state, date, replace).await?;
+            println!(
+                "{}: 解析 {} 筆, 寫入 {} 筆, 刪除 {} 筆",
+                summary.trade_date,
+                summary.parsed_rows,
+                summary.inserted_rows,
+                summary.deleted_rows
+            );
+        }
+        "recompute-limit-flags" => {
+            let from = parse_date_arg(rest.first())?;
+            let to = parse_date_arg(rest.get(1))?;
+            if from > to {
+                bail!("FROM 不可晚於 TO");
+            }
+
+            let summary = price_limit::recompute(
+                &state.db,
+                from,
+                to,
+                state.config.data_quality.price_limit_pct,
+            )
+            .await?;
+            println!(
+                "{} ~ {}: {} 個交易日, 更新 {} 筆",
+                summary.from, summary.to, summary.trade_days, summary.updated_rows
+            );
+        }
+        "ingest-monthly-revenue" => {
+            let summary = monthly_revenue::ingest(state).await?;
+            println!(
+                "{} {:?}: 解析 {} 筆, 略過 {} 筆, 寫入 {} 筆",
+                summary.source,
+                summary.revenue_months,
+                summary.parsed_rows,
+                summary.skipped_rows,
+                summary.upserted_rows
+            );
+        }
+        "reencrypt-tokens" => {
+            let summary =
+                token_crypto::reencrypt_all(&state.db, &state.config.auth.token_encryption).await?;
+            println!(
+                "金鑰 {}: 重新加密 {} 筆",
+                summary.key_id, summary.updated_rows
+            );
+        }
+        _ => bail!(USAGE),
+    }
+
+    Ok(())
+}
+
+fn parse_date_arg(arg: Option<&String>) -> Result<NaiveDate> {
+    let Some(arg) = arg else {
+        bail!(USAGE);
+    };
+    NaiveDate::parse_from_str(arg, "%Y-%m-%d").wrap_err_with(|| format!("無效的日期: {}", arg))
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
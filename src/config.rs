@@ -1,6 +1,7 @@
 // src/config.rs
 
-use std::time::Duration;
+use ipnet::IpNet;
+use std::{net::IpAddr, str::FromStr, time::Duration};
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -10,6 +11,400 @@ pub struct AppConfig {
     pub database_url: String,
     pub db_max_connections: u32,
     pub valkey_url: String, // 新增
+    pub data_quality: DataQualityConfig,
+    pub monthly_revenue: MonthlyRevenueConfig,
+    /// 日成交資料一次寫入超過這個筆數時改用 COPY 大量匯入
+    pub bulk_insert_threshold: usize,
+    pub auth: AuthConfig,
+}
+
+/// 登入與 session 設定
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// 未設定 `GOOGLE_CLIENT_ID` 時為 `None`，登入路由回傳 404
+    pub google: Option<OAuthClientConfig>,
+    /// 未設定 `DISCORD_CLIENT_ID` 時為 `None`
+    pub discord: Option<OAuthClientConfig>,
+    /// 以 discovery 設定的 OpenID Connect 提供者
+    pub oidc: Vec<OidcProviderConfig>,
+    /// 登入完成後，沒有指定 `redirect` 時導向的位置
+    pub post_login_redirect: String,
+    pub session_cookie: String,
+    /// 閒置超過這段時間的 session 失效，每次使用都會重新計算（sliding expiry）
+    pub session_idle_timeout: Duration,
+    /// session 自建立起的最長存活時間，到期後必須重新登入
+    pub session_max_age: Duration,
+    /// 是否在 cookie 加上 `Secure`（正式環境走 HTTPS 時應開啟）
+    pub cookie_secure: bool,
+    /// 可信任的反向代理（IP 或 CIDR），只有連線來自這些位址時才採用 `X-Forwarded-For`
+    pub trusted_proxies: Vec<IpNet>,
+    pub jwt: JwtConfig,
+    pub token_encryption: TokenEncryptionConfig,
+    pub token_refresh: ProviderTokenRefreshConfig,
+}
+
+/// 背景換發第三方 access token 的設定
+#[derive(Debug, Clone)]
+pub struct ProviderTokenRefreshConfig {
+    /// 檢查間隔，0 表示不啟用
+    pub interval: Duration,
+    /// access token 在到期前這段時間內就換發
+    pub ahead: Duration,
+}
+
+impl Default for ProviderTokenRefreshConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(
+                env_or::<u64>("PROVIDER_TOKEN_REFRESH_INTERVAL_MINUTES", 5) * 60,
+            ),
+            ahead: Duration::from_secs(
+                env_or::<u64>("PROVIDER_TOKEN_REFRESH_AHEAD_MINUTES", 10) * 60,
+            ),
+        }
+    }
+}
+
+/// 第三方提供者 token 的加密金鑰（AES-256-GCM）
+#[derive(Debug, Clone)]
+pub struct TokenEncryptionConfig {
+    /// 加密金鑰（kid, 32 bytes）
+    pub keys: Vec<(String, Vec<u8>)>,
+    /// 加密新資料使用的 kid；沒有設定任何金鑰時為 `None`，token 以明文保存
+    pub active_kid: Option<String>,
+}
+
+impl TokenEncryptionConfig {
+    /// 依 kid 取得金鑰
+    pub fn key(&self, kid: &str) -> Option<&[u8]> {
+        self.keys
+            .iter()
+            .find(|(id, _)| id == kid)
+            .map(|(_, key)| key.as_slice())
+    }
+}
+
+impl Default for TokenEncryptionConfig {
+    fn default() -> Self {
+        use base64::{Engine, engine::general_purpose::STANDARD};
+
+        // 格式：kid:base64(32 bytes),kid:base64(32 bytes)
+        let keys: Vec<(String, Vec<u8>)> = std::env::var("TOKEN_ENCRYPTION_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let key = entry
+                    .trim()
+                    .split_once(':')
+                    .filter(|(kid, _)| !kid.is_empty())
+                    .and_then(|(kid, key)| Some((kid.to_string(), STANDARD.decode(key).ok()?)))
+                    .filter(|(_, key)| key.len() == 32);
+                key.unwrap_or_else(|| {
+                    panic!("TOKEN_ENCRYPTION_KEYS 格式應為 kid:base64，且金鑰解碼後為 32 bytes")
+                })
+            })
+            .collect();
+        let active_kid = std::env::var("TOKEN_ENCRYPTION_ACTIVE_KID")
+            .ok()
+            .or_else(|| keys.first().map(|(kid, _)| kid.clone()));
+        if let Some(kid) = &active_kid
+            && !keys.iter().any(|(id, _)| id == kid)
+        {
+            panic!(
+                "TOKEN_ENCRYPTION_ACTIVE_KID {} 不在 TOKEN_ENCRYPTION_KEYS 中",
+                kid
+            );
+        }
+
+        Self { keys, active_kid }
+    }
+}
+
+/// 行動端使用的 JWT access token 與 refresh token 設定
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    /// 簽章密鑰（kid, secret），沒有設定時不提供 token 端點
+    pub keys: Vec<(String, String)>,
+    /// 簽發新 token 使用的 kid；其他 kid 只用於驗證輪替前簽出的 token
+    pub active_kid: String,
+    pub issuer: String,
+    pub access_ttl: Duration,
+    /// 單一 refresh token 的有效期限，每次輪替重新計算
+    pub refresh_ttl: Duration,
+    /// 同一次登入（refresh token 家族）的最長存活時間，輪替不會延長，到期後必須重新登入
+    pub refresh_max_age: Duration,
+}
+
+impl JwtConfig {
+    /// 依 kid 取得密鑰
+    pub fn key(&self, kid: &str) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(id, _)| id == kid)
+            .map(|(_, secret)| secret.as_str())
+    }
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        // 格式：kid:secret,kid:secret
+        let keys: Vec<(String, String)> = std::env::var("JWT_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| match entry.trim().split_once(':') {
+                Some((kid, secret)) if !kid.is_empty() && secret.len() >= 32 => {
+                    (kid.to_string(), secret.to_string())
+                }
+                _ => panic!("JWT_KEYS 格式應為 kid:secret，且密鑰至少 32 個字元"),
+            })
+            .collect();
+        let active_kid = std::env::var("JWT_ACTIVE_KID")
+            .ok()
+            .or_else(|| keys.first().map(|(kid, _)| kid.clone()))
+            .unwrap_or_default();
+        if !keys.is_empty() && !keys.iter().any(|(kid, _)| *kid == active_kid) {
+            panic!("JWT_ACTIVE_KID {} 不在 JWT_KEYS 中", active_kid);
+        }
+
+        Self {
+            keys,
+            active_kid,
+            issuer: env_or("JWT_ISSUER", "axum-app".to_string()),
+            access_ttl: Duration::from_secs(env_or::<u64>("JWT_ACCESS_TTL_MINUTES", 15) * 60),
+            refresh_ttl: Duration::from_secs(
+                env_or::<u64>("JWT_REFRESH_TTL_DAYS", 30) * 24 * 60 * 60,
+            ),
+            refresh_max_age: Duration::from_secs(
+                env_or::<u64>("JWT_REFRESH_MAX_AGE_DAYS", 90) * 24 * 60 * 60,
+            ),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        let config = Self {
+            google: OAuthClientConfig::from_env(
+                "GOOGLE",
+                "https://accounts.google.com/o/oauth2/v2/auth",
+                "https://oauth2.googleapis.com/token",
+                "https://openidconnect.googleapis.com/v1/userinfo",
+                "openid email profile",
+            ),
+            discord: OAuthClientConfig::from_env(
+                "DISCORD",
+                "https://discord.com/oauth2/authorize",
+                "https://discord.com/api/oauth2/token",
+                "https://discord.com/api/users/@me",
+                "identify email",
+            ),
+            oidc: OidcProviderConfig::all_from_env(),
+            post_login_redirect: env_or("AUTH_POST_LOGIN_REDIRECT", "/".to_string()),
+            session_cookie: env_or("SESSION_COOKIE_NAME", "sid".to_string()),
+            session_idle_timeout: Duration::from_secs(
+                env_or::<u64>("SESSION_IDLE_TIMEOUT_HOURS", 24 * 7) * 60 * 60,
+            ),
+            session_max_age: Duration::from_secs(
+                env_or::<u64>("SESSION_MAX_AGE_DAYS", 30) * 24 * 60 * 60,
+            ),
+            cookie_secure: env_or("SESSION_COOKIE_SECURE", false),
+            trusted_proxies: trusted_proxies_from_env(),
+            jwt: JwtConfig::default(),
+            token_encryption: TokenEncryptionConfig::default(),
+            token_refresh: ProviderTokenRefreshConfig::default(),
+        };
+        config.check_token_encryption();
+        config
+    }
+}
+
+impl AuthConfig {
+    /// 有啟用第三方登入卻沒有設定 token 加密金鑰時拒絕啟動，
+    /// 除非以 `TOKEN_ENCRYPTION_ALLOW_PLAINTEXT=true` 明確允許明文保存
+    fn check_token_encryption(&self) {
+        let has_provider = self.google.is_some() || self.discord.is_some() || !self.oidc.is_empty();
+        if !has_provider || self.token_encryption.active_kid.is_some() {
+            return;
+        }
+        if !env_or("TOKEN_ENCRYPTION_ALLOW_PLAINTEXT", false) {
+            panic!(
+                "已啟用第三方登入但未設定 TOKEN_ENCRYPTION_KEYS；若確定要以明文保存 token，請設定 TOKEN_ENCRYPTION_ALLOW_PLAINTEXT=true"
+            );
+        }
+        tracing::warn!("⚠️ 未設定 TOKEN_ENCRYPTION_KEYS，第三方 access/refresh token 將以明文保存");
+    }
+}
+
+/// 讀取 `TRUSTED_PROXIES`，以逗號分隔的 IP 或 CIDR；單一 IP 視為 /32（IPv6 為 /128）
+fn trusted_proxies_from_env() -> Vec<IpNet> {
+    std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .unwrap_or_else(|_| panic!("TRUSTED_PROXIES 格式錯誤: {}", entry))
+        })
+        .collect()
+}
+
+/// OAuth 2.0 用戶端設定
+///
+/// 授權、token 與使用者資料端點都可以用環境變數覆寫，測試時指向本機的模擬 IdP。
+#[derive(Debug, Clone)]
+pub struct OAuthClientConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub auth_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub scopes: String,
+}
+
+impl OAuthClientConfig {
+    /// 讀取 `{PREFIX}_CLIENT_ID` 等環境變數；沒有設定 client id 時視為未啟用
+    fn from_env(
+        prefix: &str,
+        auth_url: &str,
+        token_url: &str,
+        userinfo_url: &str,
+        scopes: &str,
+    ) -> Option<Self> {
+        let var = |name: &str| std::env::var(format!("{}_{}", prefix, name)).ok();
+        let client_id = var("CLIENT_ID").filter(|id| !id.is_empty())?;
+
+        Some(Self {
+            client_id,
+            client_secret: var("CLIENT_SECRET")
+                .unwrap_or_else(|| panic!("Not Found {}_CLIENT_SECRET", prefix)),
+            redirect_uri: var("REDIRECT_URI")
+                .unwrap_or_else(|| panic!("Not Found {}_REDIRECT_URI", prefix)),
+            auth_url: var("AUTH_URL").unwrap_or_else(|| auth_url.to_string()),
+            token_url: var("TOKEN_URL").unwrap_or_else(|| token_url.to_string()),
+            userinfo_url: var("USERINFO_URL").unwrap_or_else(|| userinfo_url.to_string()),
+            scopes: var("SCOPES").unwrap_or_else(|| scopes.to_string()),
+        })
+    }
+}
+
+/// 月營收匯入設定
+#[derive(Debug, Clone)]
+pub struct MonthlyRevenueConfig {
+    /// 資料來源：HTTP(S) 網址，或本機 JSON 檔路徑（可加 `file://` 前綴，測試時使用）
+    pub source: String,
+    /// 背景定期匯入的間隔，0 表示不啟用
+    pub interval: Duration,
+}
+
+impl Default for MonthlyRevenueConfig {
+    fn default() -> Self {
+        Self {
+            source: std::env::var("MONTHLY_REVENUE_SOURCE").unwrap_or_else(|_| {
+                "https://openapi.twse.com.tw/v1/opendata/t187ap05_L".to_string()
+            }),
+            interval: Duration::from_secs(
+                env_or::<u64>("MONTHLY_REVENUE_INTERVAL_HOURS", 24) * 60 * 60,
+            ),
+        }
+    }
+}
+
+/// 日成交資料匯入時的資料品質檢查設定
+#[derive(Debug, Clone)]
+pub struct DataQualityConfig {
+    /// 啟用的規則名稱（`ohlc`、`volume_amount`、`price_change`、`price_limit`）
+    pub rules: Vec<String>,
+    /// 是否不寫入有 error 等級問題的資料列（預設仍寫入，只記錄問題）
+    pub reject_errors: bool,
+    /// 每日漲跌幅限制（百分比）
+    pub price_limit_pct: f64,
+    /// 價格比對的容許誤差
+    pub price_tolerance: f64,
+}
+
+impl DataQualityConfig {
+    pub fn is_enabled(&self, rule: &str) -> bool {
+        self.rules.iter().any(|r| r == rule)
+    }
+}
+
+impl Default for DataQualityConfig {
+    fn default() -> Self {
+        Self {
+            rules: std::env::var("DATA_QUALITY_RULES")
+                .unwrap_or_else(|_| "ohlc,volume_amount,price_change,price_limit".to_string())
+                .split(',')
+                .map(|rule| rule.trim().to_string())
+                .filter(|rule| !rule.is_empty())
+                .collect(),
+            reject_errors: env_or("DATA_QUALITY_REJECT_ERRORS", false),
+            price_limit_pct: env_or("DATA_QUALITY_PRICE_LIMIT_PCT", 10.0),
+            price_tolerance: env_or("DATA_QUALITY_PRICE_TOLERANCE", 0.005),
+        }
+    }
+}
+
+/// 讀取選填的環境變數，沒設定時使用預設值
+fn env_or<T>(key: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: std::fmt::Debug,
+{
+    match std::env::var(key) {
+        Ok(value) => value
+            .parse::<T>()
+            .unwrap_or_else(|e| panic!("{} value is invalid: {:?}", key, e)),
+        Err(_) => default,
+    }
+}
+
+/// OpenID Connect 提供者設定，端點由 `{issuer}/.well-known/openid-configuration` 取得
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    /// 路由 `/auth/oidc/{name}/login` 中的名稱，同時寫入 user_identities.provider
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub scopes: String,
+}
+
+impl OidcProviderConfig {
+    /// 讀取 `OIDC_PROVIDERS` 列出的提供者，每個提供者以 `OIDC_{NAME}_ISSUER` 等變數設定
+    fn all_from_env() -> Vec<Self> {
+        std::env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                if name == "google" || name == "discord" {
+                    panic!("OIDC_PROVIDERS 不可使用內建提供者名稱 {}", name);
+                }
+                let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+                let var = |key: &str| {
+                    let key = format!("{}_{}", prefix, key);
+                    std::env::var(&key).unwrap_or_else(|_| panic!("Not Found {}", key))
+                };
+                Self {
+                    issuer: var("ISSUER"),
+                    client_id: var("CLIENT_ID"),
+                    client_secret: var("CLIENT_SECRET"),
+                    redirect_uri: var("REDIRECT_URI"),
+                    scopes: std::env::var(format!("{}_SCOPES", prefix))
+                        .unwrap_or_else(|_| "openid email profile".to_string()),
+                    name,
+                }
+            })
+            .collect()
+    }
 }
 
 impl Default for AppConfig {
@@ -32,6 +427,10 @@ impl Default for AppConfig {
                 .parse::<u32>()
                 .expect("DB_MAX_CONNECTIONS value must be a valid u32 number"),
             valkey_url: std::env::var("VALKEY_URL").expect("Not Found VALKEY_URL"), // 新增
+            data_quality: DataQualityConfig::default(),
+            monthly_revenue: MonthlyRevenueConfig::default(),
+            bulk_insert_threshold: env_or("BULK_INSERT_THRESHOLD", 5000),
+            auth: AuthConfig::default(),
         }
     }
 }
@@ -48,7 +48,11 @@ impl AppError {
         Self::new(StatusCode::NOT_FOUND, message)
     }
 
-    pub fn _unauthorized(message: impl Into<String>) -> Self {
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
         Self::new(StatusCode::UNAUTHORIZED, message)
     }
 
@@ -124,6 +128,13 @@ impl From<reqwest::Error> for AppError {
     }
 }
 
+impl From<redis::RedisError> for AppError {
+    fn from(err: redis::RedisError) -> Self {
+        tracing::error!("Valkey error: {:?}", err);
+        Self::with_source(StatusCode::INTERNAL_SERVER_ERROR, "快取服務錯誤", err)
+    }
+}
+
 impl From<chrono::ParseError> for AppError {
     fn from(err: chrono::ParseError) -> Self {
         Self::with_source(StatusCode::BAD_REQUEST, "日期格式錯誤", err)
@@ -1,10 +1,12 @@
 mod api;
 mod bootstrap;
+mod cli;
 mod config;
 mod error;
 mod logging;
 mod router;
 mod server;
+mod services;
 mod state;
 mod utils;
 
@@ -21,6 +23,16 @@ async fn main() -> Result<()> {
     let config = load_config();
 
     let app_state = setup_app_state(&config).await?;
+
+    // 帶參數時以命令列工具模式執行，執行完即結束
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&app_state, &args).await;
+    }
+
+    services::monthly_revenue::spawn_scheduler(app_state.clone());
+    services::provider_token_refresh::spawn_scheduler(app_state.clone());
+
     let app = create_router(app_state);
 
     let addr = format!("{}:{}", config.host, config.port);
@@ -1,12 +1,31 @@
 use crate::{
-    api::handlers::{get_stock_day_all, handler_404, health_fail, health_ok, upload_image},
+    api::{
+        auth::{ReadOnly, ScopeResource, require_admin, require_auth},
+        handlers::{
+            compare_stocks, create_api_key, create_basket, current_user, decline_merge,
+            delete_basket, delete_trading_day, discord_callback, discord_link, discord_login,
+            get_basket, get_stock_day_all, google_callback, google_link, google_login, handler_404,
+            health_fail, health_ok, index_chart_png, index_chart_svg, index_daily,
+            industry_heatmap, ingest_index_day, ingest_monthly_revenue, ingest_stock_day_all,
+            issue_token, list_api_keys, list_baskets, list_identities, list_indices,
+            list_price_limit_stocks, list_quality_issues, list_sessions, list_trading_calendar,
+            logout, market_summary, merge_identity, oidc_callback, oidc_link, oidc_login,
+            portfolio_risk, recompute_price_limit_flags, refresh_market_summary, refresh_token,
+            reprocess_stock_day_all, revoke_all_sessions, revoke_api_key, revoke_session,
+            revoke_token, screen_monthly_revenue, stock_chart_png, stock_chart_svg, stock_daily,
+            stock_dca, stock_monthly_revenue, stock_risk, sync_trading_calendar,
+            trading_calendar_gaps, unlink_identity, update_basket, upload_image,
+            upsert_stock_industry, upsert_trading_day,
+        },
+    },
     config::load_config,
     state::AppState,
 };
 use axum::{
-    Router,
+    Extension, Router,
     http::StatusCode,
-    routing::{get, post},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
 };
 use std::sync::Arc;
 use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
@@ -17,8 +36,41 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/ok", get(health_ok))
         .route("/fail", get(health_fail))
-        .route("/get_stock_day_all", get(get_stock_day_all))
-        .route("/upload_image", post(upload_image))
+        .route(
+            "/get_stock_day_all",
+            get(get_stock_day_all)
+                .route_layer(from_fn(require_admin))
+                .route_layer(from_fn_with_state(state.clone(), require_auth))
+                .route_layer(Extension(ScopeResource("stocks"))),
+        )
+        .route(
+            "/upload_image",
+            post(upload_image)
+                .route_layer(from_fn_with_state(state.clone(), require_auth))
+                .route_layer(Extension(ScopeResource("uploads"))),
+        )
+        .route("/trading_calendar", get(list_trading_calendar))
+        .route("/stock_quality_issues", get(list_quality_issues))
+        .route("/stocks/{code}/dca", get(stock_dca))
+        .merge(series_routes())
+        .nest("/baskets", basket_routes(state.clone()))
+        .route("/stocks/limits", get(list_price_limit_stocks))
+        .route("/stocks/{code}/monthly_revenue", get(stock_monthly_revenue))
+        .route("/monthly_revenue", get(screen_monthly_revenue))
+        .route("/heatmap/industries", get(industry_heatmap))
+        .route("/market/summary", get(market_summary))
+        .route("/indices", get(list_indices))
+        .route("/indices/{name}/daily", get(index_daily))
+        .route("/indices/{name}/chart.svg", get(index_chart_svg))
+        .route("/indices/{name}/chart.png", get(index_chart_png))
+        .nest("/auth", auth_routes(state.clone()))
+        .nest(
+            "/admin",
+            admin_routes()
+                .route_layer(from_fn(require_admin))
+                .route_layer(from_fn_with_state(state.clone(), require_auth))
+                .route_layer(Extension(ScopeResource("stocks"))),
+        )
         .fallback(handler_404)
         .layer((
             TraceLayer::new_for_http(),
@@ -26,3 +78,82 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         ))
         .with_state(state)
 }
+
+/// 登入相關路由；帳號管理的部分需要先登入
+fn auth_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let account = Router::new()
+        .route("/sessions", get(list_sessions).delete(revoke_all_sessions))
+        .route("/sessions/{id}", delete(revoke_session))
+        .route("/token", post(issue_token))
+        .route("/api_keys", get(list_api_keys).post(create_api_key))
+        .route("/api_keys/{id}", delete(revoke_api_key))
+        .route("/identities", get(list_identities))
+        .route("/identities/{id}", delete(unlink_identity))
+        .route("/merge", post(merge_identity))
+        .route("/google/link", get(google_link))
+        .route("/discord/link", get(discord_link))
+        .route("/oidc/{provider}/link", get(oidc_link))
+        .route_layer(from_fn_with_state(state, require_auth));
+
+    Router::new()
+        .route("/google/login", get(google_login))
+        .route("/google/callback", get(google_callback))
+        .route("/discord/login", get(discord_login))
+        .route("/discord/callback", get(discord_callback))
+        .route("/oidc/{provider}/login", get(oidc_login))
+        .route("/oidc/{provider}/callback", get(oidc_callback))
+        .route("/me", get(current_user))
+        .route("/logout", post(logout))
+        .route("/token/refresh", post(refresh_token))
+        .route("/token/revoke", post(revoke_token))
+        .route("/merge/decline", post(decline_merge))
+        .merge(account)
+}
+
+/// 接受 `basket:{id}` 的序列路由：登入時可以讀取自己的組合，API key 需要 `stocks:read` 權限
+fn series_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/stocks/{code}/daily", get(stock_daily))
+        .route("/stocks/{code}/chart.svg", get(stock_chart_svg))
+        .route("/stocks/{code}/chart.png", get(stock_chart_png))
+        .route("/stocks/{code}/risk", get(stock_risk))
+        .route(
+            "/portfolio/risk",
+            post(portfolio_risk).route_layer(Extension(ReadOnly)),
+        )
+        .route("/stocks/compare", get(compare_stocks))
+        .route_layer(Extension(ScopeResource("stocks")))
+}
+
+/// 自訂組合，只能管理自己建立的組合；API key 需要 `stocks:read` 或 `stocks:write` 權限
+fn basket_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_baskets).post(create_basket))
+        .route(
+            "/{id}",
+            get(get_basket).put(update_basket).delete(delete_basket),
+        )
+        .route_layer(from_fn_with_state(state, require_auth))
+        .route_layer(Extension(ScopeResource("stocks")))
+}
+
+/// 管理用路由，限管理員使用；API key 需要 `stocks:read` 或 `stocks:write` 權限
+fn admin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/trading_calendar/sync/{year}", post(sync_trading_calendar))
+        .route(
+            "/trading_calendar/{date}",
+            put(upsert_trading_day).delete(delete_trading_day),
+        )
+        .route("/trading_calendar/gaps", get(trading_calendar_gaps))
+        .route("/stock_day_all/{date}", post(ingest_stock_day_all))
+        .route("/stock_day_all/reprocess", post(reprocess_stock_day_all))
+        .route(
+            "/stock_day_all/limit_flags",
+            post(recompute_price_limit_flags),
+        )
+        .route("/index_day/{date}", post(ingest_index_day))
+        .route("/monthly_revenue/ingest", post(ingest_monthly_revenue))
+        .route("/stock_industries/{code}", put(upsert_stock_industry))
+        .route("/market_summary/refresh", post(refresh_market_summary))
+}
@@ -1,15 +1,20 @@
 use crate::utils::shutdown::shutdown_signal;
 use axum::Router;
 use color_eyre::eyre::Result;
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 
 pub async fn run_server(addr: &str, app: Router) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
     tracing::info!("Server listening on {}", addr);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // 提供連線來源位址給 session 記錄裝置資訊
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,26 @@
+pub mod api_key;
+pub mod basket;
+pub mod cache;
+pub mod chart;
+pub mod comparison;
+pub mod data_quality;
+pub mod dca;
+pub mod index_day;
+pub mod indicators;
+pub mod industry;
+pub mod jwt;
+pub mod market_summary;
+pub mod monthly_revenue;
+pub mod oauth;
+pub mod oidc;
+pub mod payload_archive;
+pub mod price_limit;
+pub mod provider_token_refresh;
+pub mod refresh_token;
+pub mod risk;
+pub mod series;
+pub mod session;
+pub mod stock_day_all;
+pub mod token_crypto;
+pub mod trading_calendar;
+pub mod users;
@@ -0,0 +1,353 @@
+// src/services/api_key.rs
+
+use axum::http::{HeaderMap, StatusCode};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{error::AppError, services::jwt, utils::token::random_token};
+
+/// 所有 API key 的開頭，用來和 JWT access token 區分
+const KEY_PREFIX: &str = "ak_";
+/// 保存並顯示的金鑰開頭長度（含 `ak_`）
+const DISPLAY_PREFIX_LEN: usize = 11;
+/// last_used_at 更新的最短間隔，避免每個請求都寫入
+const TOUCH_INTERVAL_SECS: i64 = 60;
+/// 每個使用者可持有的有效金鑰數
+const MAX_KEYS_PER_USER: i64 = 20;
+/// 有效天數上限
+const MAX_EXPIRES_IN_DAYS: u32 = 3650;
+
+/// 可授予 API key 的權限
+///
+/// 路由以 `{資源}:read`（GET）或 `{資源}:write`（其他方法）檢查，見 `api::auth::ScopeResource`。
+pub const SCOPES: &[&str] = &["stocks:read", "stocks:write", "uploads:write"];
+
+/// API key（不含金鑰本身）
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// 剛建立的 API key，`key` 只會在建立時回傳這一次
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// 通過驗證的 API key
+#[derive(Debug)]
+pub struct KeyGrant {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredKey {
+    id: Uuid,
+    user_id: Uuid,
+    scopes: Vec<String>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// 從 `X-Api-Key` 或 `Authorization: Bearer ak_...` 取出 API key
+pub fn from_headers(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .or_else(|| jwt::bearer_token(headers).filter(|token| token.starts_with(KEY_PREFIX)))
+}
+
+/// 檢查過的建立參數
+struct NewKey<'a> {
+    name: &'a str,
+    /// 排序並去除重複
+    scopes: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// 檢查建立 API key 的輸入
+fn validate<'a>(
+    name: &'a str,
+    scopes: &[String],
+    expires_in_days: Option<u32>,
+    now: DateTime<Utc>,
+) -> Result<NewKey<'a>, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::bad_request("名稱不可為空且不可超過 100 字"));
+    }
+    if scopes.is_empty() {
+        return Err(AppError::bad_request("至少需要一個權限"));
+    }
+    if let Some(scope) = scopes
+        .iter()
+        .find(|scope| !SCOPES.contains(&scope.as_str()))
+    {
+        return Err(AppError::bad_request(format!(
+            "未知的權限 {}，可用：{}",
+            scope,
+            SCOPES.join(", ")
+        )));
+    }
+    let expires_at = expires_in_days
+        .map(|days| {
+            (1..=MAX_EXPIRES_IN_DAYS)
+                .contains(&days)
+                .then(|| now.checked_add_signed(Duration::days(days.into())))
+                .flatten()
+                .ok_or_else(|| {
+                    AppError::bad_request(format!(
+                        "有效天數需介於 1 到 {} 之間",
+                        MAX_EXPIRES_IN_DAYS
+                    ))
+                })
+        })
+        .transpose()?;
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+
+    Ok(NewKey {
+        name,
+        scopes,
+        expires_at,
+    })
+}
+
+/// 建立 API key
+pub async fn create(
+    db: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<u32>,
+) -> Result<CreatedApiKey, AppError> {
+    let NewKey {
+        name,
+        scopes,
+        expires_at,
+    } = validate(name, scopes, expires_in_days, Utc::now())?;
+
+    let mut tx = db.begin().await?;
+    // 鎖住使用者，避免同時建立而超過上限
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    let active = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*) FROM api_keys
+        WHERE user_id = $1 AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if active >= MAX_KEYS_PER_USER {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("每個帳號最多 {} 把有效的 API key", MAX_KEYS_PER_USER),
+        ));
+    }
+
+    let key = format!("{}{}", KEY_PREFIX, random_token(32));
+    let api_key = sqlx::query_as::<_, ApiKey>(
+        r#"
+        INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, prefix, scopes, created_at, expires_at, last_used_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(&key[..DISPLAY_PREFIX_LEN])
+    .bind(hash(&key))
+    .bind(&scopes)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(CreatedApiKey { api_key, key })
+}
+
+/// 使用者尚未撤銷的 API key（包含已過期的，方便辨識後撤銷）
+pub async fn list(db: &PgPool, user_id: Uuid) -> Result<Vec<ApiKey>, AppError> {
+    let keys = sqlx::query_as::<_, ApiKey>(
+        r#"
+        SELECT id, name, prefix, scopes, created_at, expires_at, last_used_at
+        FROM api_keys
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(keys)
+}
+
+/// 撤銷 API key，找不到（或不屬於此使用者）時回傳 `false`
+pub async fn revoke(db: &PgPool, user_id: Uuid, id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 驗證 API key，無效、過期或已撤銷時回傳 `None`
+pub async fn authenticate(db: &PgPool, key: &str) -> Result<Option<KeyGrant>, AppError> {
+    if !key.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+    let Some(stored) = sqlx::query_as::<_, StoredKey>(
+        r#"
+        SELECT id, user_id, scopes, last_used_at FROM api_keys
+        WHERE key_hash = $1 AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+    )
+    .bind(hash(key))
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let stale = stored
+        .last_used_at
+        .is_none_or(|at| Utc::now() - at >= Duration::seconds(TOUCH_INTERVAL_SECS));
+    if stale {
+        sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+            .bind(stored.id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(Some(KeyGrant {
+        id: stored.id,
+        user_id: stored.user_id,
+        scopes: stored.scopes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(scopes: &[&str]) -> Vec<String> {
+        scopes.iter().map(|scope| scope.to_string()).collect()
+    }
+
+    #[test]
+    fn key_comes_from_x_api_key_or_prefixed_bearer() {
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+
+        assert_eq!(
+            from_headers(&headers(&[("x-api-key", " ak_abc ")])),
+            Some("ak_abc")
+        );
+        assert_eq!(
+            from_headers(&headers(&[("authorization", "Bearer ak_abc")])),
+            Some("ak_abc")
+        );
+        // 一般的 JWT access token 不是 API key
+        assert_eq!(
+            from_headers(&headers(&[("authorization", "Bearer eyJhbGciOi")])),
+            None
+        );
+        // 兩個都有時以 X-Api-Key 為準；空白的 X-Api-Key 視為沒有帶
+        assert_eq!(
+            from_headers(&headers(&[
+                ("x-api-key", "ak_header"),
+                ("authorization", "Bearer ak_bearer")
+            ])),
+            Some("ak_header")
+        );
+        assert_eq!(
+            from_headers(&headers(&[
+                ("x-api-key", "  "),
+                ("authorization", "Bearer ak_bearer")
+            ])),
+            Some("ak_bearer")
+        );
+        assert_eq!(from_headers(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn scopes_must_be_known_and_non_empty() {
+        let now = Utc::now();
+        for invalid in [
+            scopes(&[]),
+            scopes(&["stocks:admin"]),
+            scopes(&["stocks:read", ""]),
+            scopes(&["STOCKS:READ"]),
+        ] {
+            let err = validate("key", &invalid, None, now).err().unwrap();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST, "{:?}", invalid);
+        }
+
+        let key = validate(
+            " key ",
+            &scopes(&["uploads:write", "stocks:read", "uploads:write"]),
+            None,
+            now,
+        )
+        .unwrap();
+        assert_eq!(key.name, "key");
+        assert_eq!(key.scopes, scopes(&["stocks:read", "uploads:write"]));
+
+        let valid = scopes(&["stocks:read"]);
+        assert!(validate("  ", &valid, None, now).is_err());
+        assert!(validate(&"名".repeat(101), &valid, None, now).is_err());
+    }
+
+    #[test]
+    fn expires_in_days_is_bounded() {
+        let now = Utc::now();
+        let valid = scopes(&["stocks:read"]);
+        for days in [0, MAX_EXPIRES_IN_DAYS + 1, 4_000_000_000, u32::MAX] {
+            let err = validate("key", &valid, Some(days), now).err().unwrap();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST, "{}", days);
+        }
+
+        let key = validate("key", &valid, Some(MAX_EXPIRES_IN_DAYS), now).unwrap();
+        assert_eq!(
+            key.expires_at,
+            Some(now + Duration::days(MAX_EXPIRES_IN_DAYS.into()))
+        );
+        let key = validate("key", &valid, None, now).unwrap();
+        assert_eq!(key.expires_at, None);
+    }
+}
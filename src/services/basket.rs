@@ -0,0 +1,539 @@
+// src/services/basket.rs
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    services::series::{Candle, Series},
+};
+
+/// 一個組合最多可包含的成分股數
+pub const MAX_COMPONENTS: usize = 50;
+
+/// 依成交金額加權時，回看的交易日數
+const TRADE_AMOUNT_LOOKBACK_DAYS: usize = 20;
+
+/// 權重方式
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    /// 等權重
+    Equal,
+    /// 使用者指定的固定權重
+    Fixed,
+    /// 依再平衡日前 20 個交易日的成交金額加權（市值加權的近似）
+    TradeAmount,
+}
+
+impl Weighting {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Equal => "equal",
+            Self::Fixed => "fixed",
+            Self::TradeAmount => "trade_amount",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, AppError> {
+        match s {
+            "equal" => Ok(Self::Equal),
+            "fixed" => Ok(Self::Fixed),
+            "trade_amount" => Ok(Self::TradeAmount),
+            _ => Err(AppError::internal_error(format!("未知的權重方式 {}", s))),
+        }
+    }
+}
+
+/// 再平衡週期，於每個週期的第一個交易日依權重重新配置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rebalance {
+    Monthly,
+    Quarterly,
+    Yearly,
+    /// 只在起始日配置一次
+    Never,
+}
+
+impl Rebalance {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Monthly => "monthly",
+            Self::Quarterly => "quarterly",
+            Self::Yearly => "yearly",
+            Self::Never => "never",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, AppError> {
+        match s {
+            "monthly" => Ok(Self::Monthly),
+            "quarterly" => Ok(Self::Quarterly),
+            "yearly" => Ok(Self::Yearly),
+            "never" => Ok(Self::Never),
+            _ => Err(AppError::internal_error(format!("未知的再平衡週期 {}", s))),
+        }
+    }
+
+    /// 日期所屬的週期，週期改變時進行再平衡
+    fn period(&self, date: NaiveDate) -> (i32, u32) {
+        match self {
+            Self::Monthly => (date.year(), date.month()),
+            Self::Quarterly => (date.year(), (date.month() - 1) / 3),
+            Self::Yearly => (date.year(), 0),
+            Self::Never => (0, 0),
+        }
+    }
+}
+
+/// 成分股
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BasketComponent {
+    pub stock_code: String,
+    /// 固定權重，只有 `fixed` 使用
+    pub weight: Option<f64>,
+}
+
+/// 自訂組合
+#[derive(Debug, Serialize)]
+pub struct Basket {
+    pub id: i32,
+    pub name: String,
+    pub weighting: Weighting,
+    pub rebalance: Rebalance,
+    pub base_value: f64,
+    pub updated_at: DateTime<Utc>,
+    pub components: Vec<BasketComponent>,
+}
+
+/// 新增或修改組合的內容
+#[derive(Debug, Deserialize)]
+pub struct BasketInput {
+    pub name: String,
+    pub weighting: Weighting,
+    #[serde(default = "default_rebalance")]
+    pub rebalance: Rebalance,
+    pub base_value: Option<f64>,
+    pub components: Vec<BasketComponent>,
+}
+
+fn default_rebalance() -> Rebalance {
+    Rebalance::Monthly
+}
+
+#[derive(sqlx::FromRow)]
+struct BasketRow {
+    id: i32,
+    name: String,
+    weighting: String,
+    rebalance: String,
+    base_value: f64,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ComponentRow {
+    basket_id: i32,
+    stock_code: String,
+    weight: Option<f64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ComponentPrice {
+    stock_code: String,
+    trade_date: NaiveDate,
+    close: f64,
+    trade_amount: Option<i64>,
+}
+
+impl BasketInput {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::bad_request("name 不可為空"));
+        }
+        if self.components.is_empty() || self.components.len() > MAX_COMPONENTS {
+            return Err(AppError::bad_request(format!(
+                "components 必須有 1 到 {} 檔",
+                MAX_COMPONENTS
+            )));
+        }
+        let mut seen: Vec<&str> = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            let code = component.stock_code.trim();
+            if code.is_empty() || code.contains(':') {
+                return Err(AppError::bad_request(format!(
+                    "無效的證券代號 {}",
+                    component.stock_code
+                )));
+            }
+            if seen.contains(&code) {
+                return Err(AppError::bad_request(format!("證券代號 {} 重複", code)));
+            }
+            seen.push(code);
+        }
+        if self.weighting == Weighting::Fixed
+            && self
+                .components
+                .iter()
+                .any(|c| !c.weight.is_some_and(|w| w > 0.0 && w.is_finite()))
+        {
+            return Err(AppError::bad_request(
+                "固定權重的每個成分股都必須有大於 0 的 weight",
+            ));
+        }
+        if self.base_value.is_some_and(|v| v <= 0.0 || !v.is_finite()) {
+            return Err(AppError::bad_request("base_value 必須大於 0"));
+        }
+        Ok(())
+    }
+}
+
+fn to_basket(row: BasketRow, components: Vec<BasketComponent>) -> Result<Basket, AppError> {
+    Ok(Basket {
+        id: row.id,
+        name: row.name,
+        weighting: Weighting::parse(&row.weighting)?,
+        rebalance: Rebalance::parse(&row.rebalance)?,
+        base_value: row.base_value,
+        updated_at: row.updated_at,
+        components,
+    })
+}
+
+/// 列出使用者的所有組合
+pub async fn list(db: &PgPool, user_id: Uuid) -> Result<Vec<Basket>, AppError> {
+    let rows = sqlx::query_as::<_, BasketRow>(
+        r#"
+        SELECT id, name, weighting, rebalance, base_value, updated_at FROM baskets
+        WHERE user_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let components = sqlx::query_as::<_, ComponentRow>(
+        r#"
+        SELECT basket_id, stock_code, weight FROM basket_components
+        WHERE basket_id = ANY($1)
+        ORDER BY basket_id, stock_code
+        "#,
+    )
+    .bind(&ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut by_basket: HashMap<i32, Vec<BasketComponent>> = HashMap::new();
+    for c in components {
+        by_basket
+            .entry(c.basket_id)
+            .or_default()
+            .push(BasketComponent {
+                stock_code: c.stock_code,
+                weight: c.weight,
+            });
+    }
+
+    rows.into_iter()
+        .map(|row| {
+            let components = by_basket.remove(&row.id).unwrap_or_default();
+            to_basket(row, components)
+        })
+        .collect()
+}
+
+/// 讀取使用者的單一組合；不是該使用者的組合同樣回傳 404
+pub async fn get(db: &PgPool, user_id: Uuid, id: i32) -> Result<Basket, AppError> {
+    let row = sqlx::query_as::<_, BasketRow>(
+        r#"
+        SELECT id, name, weighting, rebalance, base_value, updated_at FROM baskets
+        WHERE id = $1 AND user_id = $2
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("找不到組合 {}", id)))?;
+
+    let components = sqlx::query_as::<_, BasketComponent>(
+        "SELECT stock_code, weight FROM basket_components WHERE basket_id = $1 ORDER BY stock_code",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    to_basket(row, components)
+}
+
+/// 新增組合
+pub async fn create(db: &PgPool, user_id: Uuid, input: &BasketInput) -> Result<Basket, AppError> {
+    input.validate()?;
+
+    let mut tx = db.begin().await?;
+    let id = sqlx::query_scalar::<_, i32>(
+        r#"
+        INSERT INTO baskets (user_id, name, weighting, rebalance, base_value)
+        VALUES ($1, $2, $3, $4, COALESCE($5, 100))
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(input.name.trim())
+    .bind(input.weighting.as_str())
+    .bind(input.rebalance.as_str())
+    .bind(input.base_value)
+    .fetch_one(&mut *tx)
+    .await?;
+    insert_components(&mut tx, id, &input.components).await?;
+    tx.commit().await?;
+
+    get(db, user_id, id).await
+}
+
+/// 修改組合，成分股整批取代
+pub async fn update(
+    db: &PgPool,
+    user_id: Uuid,
+    id: i32,
+    input: &BasketInput,
+) -> Result<Basket, AppError> {
+    input.validate()?;
+
+    let mut tx = db.begin().await?;
+    let updated = sqlx::query(
+        r#"
+        UPDATE baskets
+        SET name = $3, weighting = $4, rebalance = $5,
+            base_value = COALESCE($6, base_value), updated_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(input.name.trim())
+    .bind(input.weighting.as_str())
+    .bind(input.rebalance.as_str())
+    .bind(input.base_value)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::not_found(format!("找不到組合 {}", id)));
+    }
+
+    sqlx::query("DELETE FROM basket_components WHERE basket_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    insert_components(&mut tx, id, &input.components).await?;
+    tx.commit().await?;
+
+    get(db, user_id, id).await
+}
+
+/// 刪除組合
+pub async fn delete(db: &PgPool, user_id: Uuid, id: i32) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM baskets WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found(format!("找不到組合 {}", id)));
+    }
+
+    Ok(())
+}
+
+async fn insert_components(
+    conn: &mut sqlx::PgConnection,
+    basket_id: i32,
+    components: &[BasketComponent],
+) -> Result<(), AppError> {
+    let codes: Vec<&str> = components.iter().map(|c| c.stock_code.trim()).collect();
+    let weights: Vec<Option<f64>> = components.iter().map(|c| c.weight).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO basket_components (basket_id, stock_code, weight)
+        SELECT $1, * FROM UNNEST($2::text[], $3::double precision[])
+        "#,
+    )
+    .bind(basket_id)
+    .bind(&codes)
+    .bind(&weights)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// 組合定義的版本，定義變更後圖表快取會失效；不是該使用者的組合回傳 `None`
+pub async fn version(
+    db: &PgPool,
+    user_id: Uuid,
+    id: i32,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let updated_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        "SELECT updated_at FROM baskets WHERE id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(updated_at)
+}
+
+/// 計算組合的每日合成指數，取區間內最新的 `limit` 個交易日
+///
+/// 指數從任一成分股最早有資料的交易日開始，以 `base_value` 起算；每個再平衡日依權重
+/// 重新分配持有單位，期間內單位固定，價格隨成分股收盤變動。成分股當日沒有成交時沿用
+/// 前一個收盤價；在某次再平衡時還沒有資料的成分股，要到下一次再平衡才會納入。
+/// 只有建立者可以讀取，其他使用者回傳 404。
+pub async fn load_series(
+    db: &PgPool,
+    user_id: Uuid,
+    id: i32,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+) -> Result<Series, AppError> {
+    let basket = get(db, user_id, id).await?;
+    let codes: Vec<&str> = basket
+        .components
+        .iter()
+        .map(|c| c.stock_code.as_str())
+        .collect();
+
+    let prices = sqlx::query_as::<_, ComponentPrice>(
+        r#"
+        SELECT stock_code, trade_date, close_price::float8 AS close, trade_amount
+        FROM stock_day_all
+        WHERE stock_code = ANY($1)
+          AND close_price IS NOT NULL
+          AND close_price > 0
+          AND ($2::date IS NULL OR trade_date <= $2)
+        ORDER BY trade_date
+        "#,
+    )
+    .bind(&codes)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    let values = composite(&basket, &prices);
+    let mut candles: Vec<Candle> = values
+        .into_iter()
+        .filter(|(date, _)| from.is_none_or(|from| *date >= from))
+        .map(|(trade_date, value)| Candle {
+            trade_date,
+            open: value,
+            high: value,
+            low: value,
+            close: value,
+            volume: None,
+        })
+        .collect();
+    let skip = candles.len().saturating_sub(limit.max(0) as usize);
+    candles.drain(..skip);
+
+    Ok(Series {
+        code: format!("basket:{}", basket.id),
+        name: basket.name,
+        candles,
+    })
+}
+
+/// 依權重方式與再平衡週期計算合成指數
+fn composite(basket: &Basket, prices: &[ComponentPrice]) -> Vec<(NaiveDate, f64)> {
+    let index_of: HashMap<&str, usize> = basket
+        .components
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.stock_code.as_str(), i))
+        .collect();
+    let n = basket.components.len();
+
+    let mut by_date: BTreeMap<NaiveDate, Vec<&ComponentPrice>> = BTreeMap::new();
+    for price in prices {
+        by_date.entry(price.trade_date).or_default().push(price);
+    }
+
+    let mut last_close: Vec<Option<f64>> = vec![None; n];
+    let mut recent_amounts: Vec<VecDeque<i64>> = vec![VecDeque::new(); n];
+    let mut units: Vec<f64> = vec![0.0; n];
+    let mut current_period: Option<(i32, u32)> = None;
+    let mut values = Vec::with_capacity(by_date.len());
+
+    for (date, day_prices) in by_date {
+        for price in day_prices {
+            let i = index_of[price.stock_code.as_str()];
+            last_close[i] = Some(price.close);
+            let amounts = &mut recent_amounts[i];
+            amounts.push_back(price.trade_amount.unwrap_or(0));
+            if amounts.len() > TRADE_AMOUNT_LOOKBACK_DAYS {
+                amounts.pop_front();
+            }
+        }
+
+        let value_now: f64 = units
+            .iter()
+            .zip(&last_close)
+            .map(|(u, c)| u * c.unwrap_or(0.0))
+            .sum();
+
+        let period = basket.rebalance.period(date);
+        if current_period != Some(period) {
+            let value = if current_period.is_none() {
+                basket.base_value
+            } else {
+                value_now
+            };
+            current_period = Some(period);
+
+            let raw: Vec<f64> = (0..n)
+                .map(|i| match (last_close[i], basket.weighting) {
+                    (None, _) => 0.0,
+                    (Some(_), Weighting::Equal) => 1.0,
+                    (Some(_), Weighting::Fixed) => basket.components[i].weight.unwrap_or(0.0),
+                    (Some(_), Weighting::TradeAmount) => {
+                        recent_amounts[i].iter().sum::<i64>() as f64
+                    }
+                })
+                .collect();
+            let total: f64 = raw.iter().sum();
+            // 成交金額全為 0 時退回等權重
+            let weights: Vec<f64> = if total > 0.0 {
+                raw.iter().map(|w| w / total).collect()
+            } else {
+                let available = last_close.iter().filter(|c| c.is_some()).count() as f64;
+                last_close
+                    .iter()
+                    .map(|c| if c.is_some() { 1.0 / available } else { 0.0 })
+                    .collect()
+            };
+
+            for ((unit, close), weight) in units.iter_mut().zip(&last_close).zip(&weights) {
+                *unit = close.map_or(0.0, |close| value * weight / close);
+            }
+        }
+
+        let value: f64 = units
+            .iter()
+            .zip(&last_close)
+            .map(|(u, c)| u * c.unwrap_or(0.0))
+            .sum();
+        values.push((date, (value * 10_000.0).round() / 10_000.0));
+    }
+
+    values
+}
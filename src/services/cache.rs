@@ -0,0 +1,47 @@
+// src/services/cache.rs
+
+use redis::{AsyncCommands, aio::ConnectionManager};
+
+/// 從 Valkey 讀取快取；連線失敗時只記錄警告並視為未命中
+pub async fn get_bytes(redis: &ConnectionManager, key: &str) -> Option<Vec<u8>> {
+    let mut conn = redis.clone();
+    match conn.get::<_, Option<Vec<u8>>>(key).await {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::warn!("讀取快取 {} 失敗: {}", key, e);
+            None
+        }
+    }
+}
+
+/// 寫入 Valkey 快取；失敗時只記錄警告，不影響回應
+pub async fn set_bytes(redis: &ConnectionManager, key: &str, value: &[u8], ttl_secs: u64) {
+    let mut conn = redis.clone();
+    if let Err(e) = conn.set_ex::<_, _, ()>(key, value, ttl_secs).await {
+        tracing::warn!("寫入快取 {} 失敗: {}", key, e);
+    }
+}
+
+fn version_key(table: &str) -> String {
+    format!("cache_version:{}", table)
+}
+
+/// 資料表的寫入版本；每次寫入都會遞增，補匯或重新處理過去的日期也會讓相關快取失效
+pub async fn version(redis: &ConnectionManager, table: &str) -> u64 {
+    let mut conn = redis.clone();
+    match conn.get::<_, Option<u64>>(version_key(table)).await {
+        Ok(version) => version.unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!("讀取 {} 快取版本失敗: {}", table, e);
+            0
+        }
+    }
+}
+
+/// 寫入資料表後遞增版本；失敗時只記錄警告，快取最晚在 TTL 到期後更新
+pub async fn bump_version(redis: &ConnectionManager, table: &str) {
+    let mut conn = redis.clone();
+    if let Err(e) = conn.incr::<_, _, u64>(version_key(table), 1).await {
+        tracing::warn!("遞增 {} 快取版本失敗: {}", table, e);
+    }
+}
@@ -0,0 +1,341 @@
+// src/services/chart.rs
+
+use std::{
+    fmt::Write,
+    sync::{Arc, OnceLock},
+};
+
+use resvg::{tiny_skia, usvg};
+
+use crate::{
+    error::AppError,
+    services::{indicators::moving_average, series::Series},
+};
+
+/// 移動平均線顏色，依序套用
+const MA_COLORS: [&str; 5] = ["#f5a623", "#4a90e2", "#9b59b6", "#16a085", "#e67e22"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+
+    fn palette(&self) -> Palette {
+        match self {
+            Self::Light => Palette {
+                background: "#ffffff",
+                text: "#333333",
+                grid: "#e5e5e5",
+            },
+            Self::Dark => Palette {
+                background: "#1e1e1e",
+                text: "#dddddd",
+                grid: "#3a3a3a",
+            },
+        }
+    }
+}
+
+struct Palette {
+    background: &'static str,
+    text: &'static str,
+    grid: &'static str,
+}
+
+// 台股慣例：紅漲綠跌
+const UP_COLOR: &str = "#e53935";
+const DOWN_COLOR: &str = "#26a69a";
+
+/// 圖表參數
+#[derive(Debug, Clone)]
+pub struct ChartOptions {
+    pub width: u32,
+    pub height: u32,
+    pub theme: Theme,
+    /// 要疊加的移動平均天數，例如 `[5, 20, 60]`
+    pub moving_averages: Vec<usize>,
+}
+
+/// 計算疊加在 K 線上的均線，`warmup` 為第一根 K 線之前的收盤價，讓開頭就有均線
+fn overlay_average(warmup: &[f64], closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let values: Vec<f64> = warmup.iter().chain(closes).copied().collect();
+    moving_average(&values, window).split_off(warmup.len())
+}
+
+/// 繪製 K 線圖（上方 K 線與均線、下方成交量）並輸出 SVG
+///
+/// `warmup` 為區間之前的收盤價，只用來計算均線，不會畫出來。
+pub fn render_svg(series: &Series, warmup: &[f64], options: &ChartOptions) -> String {
+    let palette = options.theme.palette();
+    let width = options.width as f64;
+    let height = options.height as f64;
+
+    let margin_left = 10.0;
+    let margin_right = 60.0;
+    let margin_top = 28.0;
+    let margin_bottom = 22.0;
+    let plot_width = width - margin_left - margin_right;
+    let plot_height = height - margin_top - margin_bottom;
+    let price_height = plot_height * 0.75;
+    let volume_top = margin_top + price_height + 6.0;
+    let volume_height = plot_height - price_height - 6.0;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="11">"#,
+        w = options.width,
+        h = options.height
+    );
+    let _ = write!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        palette.background
+    );
+    let _ = write!(
+        svg,
+        r#"<text x="{}" y="18" fill="{}" font-size="14">{} {}</text>"#,
+        margin_left,
+        palette.text,
+        escape_xml(&series.code),
+        escape_xml(&series.name)
+    );
+
+    let candles = &series.candles;
+    if candles.is_empty() {
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" fill="{}" text-anchor="middle">No data</text></svg>"#,
+            width / 2.0,
+            height / 2.0,
+            palette.text
+        );
+        return svg;
+    }
+
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let averages: Vec<(usize, Vec<Option<f64>>)> = options
+        .moving_averages
+        .iter()
+        .map(|window| (*window, overlay_average(warmup, &closes, *window)))
+        .collect();
+
+    // 價格範圍要包含均線，避免線畫出框外
+    let mut min_price = candles.iter().map(|c| c.low).fold(f64::INFINITY, f64::min);
+    let mut max_price = candles
+        .iter()
+        .map(|c| c.high)
+        .fold(f64::NEG_INFINITY, f64::max);
+    for value in averages
+        .iter()
+        .flat_map(|(_, values)| values.iter().flatten())
+    {
+        min_price = min_price.min(*value);
+        max_price = max_price.max(*value);
+    }
+    if (max_price - min_price).abs() < f64::EPSILON {
+        max_price += 1.0;
+        min_price -= 1.0;
+    }
+    let padding = (max_price - min_price) * 0.05;
+    min_price -= padding;
+    max_price += padding;
+
+    let max_volume = candles
+        .iter()
+        .filter_map(|c| c.volume)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+
+    let slot = plot_width / candles.len() as f64;
+    let body_width = (slot * 0.7).max(1.0);
+    let x_center = |i: usize| margin_left + slot * (i as f64 + 0.5);
+    let y_price =
+        |price: f64| margin_top + (max_price - price) / (max_price - min_price) * price_height;
+
+    // 價格格線與刻度
+    for step in 0..=4 {
+        let price = min_price + (max_price - min_price) * step as f64 / 4.0;
+        let y = y_price(price);
+        let _ = write!(
+            svg,
+            r#"<line x1="{x1:.1}" y1="{y:.1}" x2="{x2:.1}" y2="{y:.1}" stroke="{grid}" stroke-width="1"/><text x="{tx:.1}" y="{ty:.1}" fill="{text}">{label}</text>"#,
+            x1 = margin_left,
+            x2 = margin_left + plot_width,
+            grid = palette.grid,
+            tx = margin_left + plot_width + 4.0,
+            ty = y + 4.0,
+            text = palette.text,
+            label = format_price(price),
+        );
+    }
+
+    // 日期刻度，大約六個
+    let label_every = (candles.len() / 6).max(1);
+    for (i, candle) in candles.iter().enumerate().step_by(label_every) {
+        let _ = write!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" fill="{}" text-anchor="middle">{}</text>"#,
+            x_center(i),
+            height - 6.0,
+            palette.text,
+            candle.trade_date.format("%m/%d")
+        );
+    }
+
+    for (i, candle) in candles.iter().enumerate() {
+        let x = x_center(i);
+        let color = if candle.close >= candle.open {
+            UP_COLOR
+        } else {
+            DOWN_COLOR
+        };
+
+        // 影線
+        let _ = write!(
+            svg,
+            r#"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{:.1}" stroke="{color}" stroke-width="1"/>"#,
+            y_price(candle.high),
+            y_price(candle.low),
+        );
+
+        // 實體
+        let top = y_price(candle.open.max(candle.close));
+        let bottom = y_price(candle.open.min(candle.close));
+        let _ = write!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{color}"/>"#,
+            x - body_width / 2.0,
+            top,
+            body_width,
+            (bottom - top).max(1.0),
+        );
+
+        // 成交量
+        if let Some(volume) = candle.volume {
+            let bar_height = volume as f64 / max_volume * volume_height;
+            let _ = write!(
+                svg,
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{color}" fill-opacity="0.6"/>"#,
+                x - body_width / 2.0,
+                volume_top + volume_height - bar_height,
+                body_width,
+                bar_height,
+            );
+        }
+    }
+
+    // 均線與圖例
+    for (n, (window, values)) in averages.iter().enumerate() {
+        let color = MA_COLORS[n % MA_COLORS.len()];
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| format!("{:.1},{:.1}", x_center(i), y_price(v))))
+            .collect();
+        if points.len() > 1 {
+            let _ = write!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="1.2"/>"#,
+                points.join(" ")
+            );
+        }
+        let _ = write!(
+            svg,
+            r#"<text x="{:.1}" y="18" fill="{color}">MA{window}</text>"#,
+            width - margin_right - 60.0 * (averages.len() - n) as f64 + 10.0,
+        );
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// 將 SVG 轉成 PNG
+pub fn svg_to_png(svg: &str) -> Result<Vec<u8>, AppError> {
+    let options = usvg::Options {
+        fontdb: font_database(),
+        ..Default::default()
+    };
+
+    let tree = usvg::Tree::from_str(svg, &options)
+        .map_err(|e| AppError::internal_error(format!("SVG 解析失敗: {}", e)))?;
+
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| AppError::internal_error("無法建立圖片緩衝區"))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    pixmap
+        .encode_png()
+        .map_err(|e| AppError::internal_error(format!("PNG 編碼失敗: {}", e)))
+}
+
+/// 系統字型只載入一次
+fn font_database() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+fn format_price(price: f64) -> String {
+    if price >= 100.0 {
+        format!("{:.1}", price)
+    } else {
+        format!("{:.2}", price)
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_use_warmup_closes() {
+        let closes = [10.0, 11.0, 12.0];
+        assert_eq!(
+            overlay_average(&[], &closes, 3),
+            vec![None, None, Some(11.0)]
+        );
+        assert_eq!(
+            overlay_average(&[8.0, 9.0], &closes, 3),
+            vec![Some(9.0), Some(10.0), Some(11.0)]
+        );
+        // 暖機資料不足時，前面仍為空
+        assert_eq!(
+            overlay_average(&[9.0], &closes, 3),
+            vec![None, Some(10.0), Some(11.0)]
+        );
+        assert_eq!(overlay_average(&[9.0], &closes, 1), closes.map(Some));
+    }
+}
@@ -0,0 +1,82 @@
+// src/services/comparison.rs
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    error::AppError,
+    services::{
+        risk::{correlation, daily_returns},
+        series::{Series, align_closes},
+    },
+};
+
+/// 單一標的以 100 為基準的累積績效
+#[derive(Debug, Serialize)]
+pub struct Performance {
+    pub code: String,
+    pub name: String,
+    /// 與 `Comparison::dates` 一一對應
+    pub values: Vec<f64>,
+    /// 因其他標的缺資料而被略過的交易日數
+    pub dropped_days: usize,
+}
+
+/// 多檔標的比較結果
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    /// 所有標的都有資料的共同交易日
+    pub dates: Vec<NaiveDate>,
+    pub codes: Vec<String>,
+    /// 日報酬相關係數矩陣，順序與 `codes` 相同；沒有波動的標的為 `None`
+    pub correlation: Vec<Vec<Option<f64>>>,
+    pub performance: Vec<Performance>,
+}
+
+/// 以共同交易日對齊後計算相關係數矩陣與重新定基為 100 的累積績效
+pub fn compare(series: &[Series]) -> Result<Comparison, AppError> {
+    let (dates, closes) = align_closes(series);
+    if dates.len() < 3 {
+        return Err(AppError::bad_request(
+            "共同交易日不足，至少需要三個交易日才能比較",
+        ));
+    }
+
+    let returns: Vec<Vec<f64>> = closes.iter().map(|c| daily_returns(c)).collect();
+    let correlation = returns
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            returns
+                .iter()
+                .enumerate()
+                .map(|(j, b)| {
+                    if i == j {
+                        // 對角線固定為 1，但沒有波動的標的同樣為 None
+                        correlation(a, a).map(|_| 1.0)
+                    } else {
+                        correlation(a, b)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let performance = series
+        .iter()
+        .zip(&closes)
+        .map(|(s, closes)| Performance {
+            code: s.code.clone(),
+            name: s.name.clone(),
+            values: closes.iter().map(|c| c / closes[0] * 100.0).collect(),
+            dropped_days: s.candles.len() - dates.len(),
+        })
+        .collect();
+
+    Ok(Comparison {
+        dates,
+        codes: series.iter().map(|s| s.code.clone()).collect(),
+        correlation,
+        performance,
+    })
+}
@@ -0,0 +1,318 @@
+// src/services/data_quality.rs
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+
+use crate::{config::DataQualityConfig, error::AppError, services::stock_day_all::StockDayRow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 資料本身矛盾，可設定為不寫入
+    Error,
+    /// 可能是除權息、新上市等合理情況，只記錄
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// 檢查出的單一問題
+#[derive(Debug, Clone)]
+pub struct QualityIssue {
+    pub stock_code: String,
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub details: serde_json::Value,
+}
+
+/// 已存入資料庫的問題
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StoredQualityIssue {
+    pub id: i32,
+    pub trade_date: NaiveDate,
+    pub stock_code: String,
+    pub rule: String,
+    pub severity: String,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    pub rejected: bool,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// 查詢條件
+#[derive(Debug, Default)]
+pub struct IssueFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub stock_code: Option<String>,
+    pub rule: Option<String>,
+    pub severity: Option<String>,
+}
+
+/// 取得每檔股票在 `trade_date` 之前最近一天的收盤價
+///
+/// 只往回找 30 天，避免掃整張表；更久沒成交的股票不做與前日相關的檢查。
+pub async fn load_previous_closes(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+) -> Result<HashMap<String, f64>, AppError> {
+    let since = trade_date
+        .checked_sub_days(Days::new(30))
+        .unwrap_or(trade_date);
+
+    let rows = sqlx::query_as::<_, (String, f64)>(
+        r#"
+        SELECT DISTINCT ON (stock_code) stock_code, close_price::float8
+        FROM stock_day_all
+        WHERE trade_date < $1
+          AND trade_date >= $2
+          AND close_price IS NOT NULL
+        ORDER BY stock_code, trade_date DESC
+        "#,
+    )
+    .bind(trade_date)
+    .bind(since)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// 依設定的規則檢查當日所有資料列
+pub fn validate(
+    rows: &[StockDayRow],
+    previous_closes: &HashMap<String, f64>,
+    config: &DataQualityConfig,
+) -> Vec<QualityIssue> {
+    let mut issues = Vec::new();
+    let tolerance = config.price_tolerance;
+
+    for row in rows {
+        let mut push = |rule: &'static str, severity, message: String, details| {
+            issues.push(QualityIssue {
+                stock_code: row.stock_code.clone(),
+                rule,
+                severity,
+                message,
+                details,
+            });
+        };
+
+        if config.is_enabled("ohlc")
+            && let (Some(open), Some(high), Some(low), Some(close)) = (
+                row.open_price,
+                row.high_price,
+                row.low_price,
+                row.close_price,
+            )
+        {
+            let inconsistent = high + tolerance < low
+                || high + tolerance < open.max(close)
+                || low - tolerance > open.min(close)
+                || low <= 0.0;
+            if inconsistent {
+                push(
+                    "ohlc",
+                    Severity::Error,
+                    "開高低收價格不一致".to_string(),
+                    json!({ "open": open, "high": high, "low": low, "close": close }),
+                );
+            }
+        }
+
+        if config.is_enabled("volume_amount")
+            && let (Some(volume), Some(amount)) = (row.trade_volume, row.trade_amount)
+            && ((volume == 0) != (amount == 0))
+        {
+            push(
+                "volume_amount",
+                Severity::Error,
+                "成交股數與成交金額不一致".to_string(),
+                json!({ "trade_volume": volume, "trade_amount": amount }),
+            );
+        }
+
+        let Some(previous_close) = previous_closes.get(&row.stock_code).copied() else {
+            continue;
+        };
+        let Some(close) = row.close_price else {
+            continue;
+        };
+
+        // 除權息日的參考價不是前日收盤價，所以只列為警告
+        if config.is_enabled("price_change")
+            && let Some(price_change) = row.price_change
+        {
+            let expected = close - previous_close;
+            if (expected - price_change).abs() > tolerance {
+                push(
+                    "price_change",
+                    Severity::Warning,
+                    format!(
+                        "漲跌價差 {} 與收盤價減前日收盤價 {:.2} 不符",
+                        price_change, expected
+                    ),
+                    json!({
+                        "price_change": price_change,
+                        "close": close,
+                        "previous_close": previous_close,
+                    }),
+                );
+            }
+        }
+
+        if config.is_enabled("price_limit") && previous_close > 0.0 {
+            let change_pct = (close - previous_close) / previous_close * 100.0;
+            // 價格跳動單位會讓漲跌停價略超過限制，多給 0.5% 緩衝
+            if change_pct.abs() > config.price_limit_pct + 0.5 {
+                push(
+                    "price_limit",
+                    Severity::Warning,
+                    format!("漲跌幅 {:.2}% 超過每日漲跌幅限制", change_pct),
+                    json!({
+                        "close": close,
+                        "previous_close": previous_close,
+                        "change_pct": change_pct,
+                    }),
+                );
+            }
+        }
+    }
+
+    issues
+}
+
+/// 設定為不寫入 error 等級問題時，回傳要排除的證券代號
+pub fn rejected_codes(issues: &[QualityIssue], config: &DataQualityConfig) -> HashSet<String> {
+    if !config.reject_errors {
+        return HashSet::new();
+    }
+    issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(|issue| issue.stock_code.clone())
+        .collect()
+}
+
+/// 寫入檢查結果；同一天同一檔同一規則重新檢查時覆蓋
+pub async fn record_issues(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+    issues: &[QualityIssue],
+    rejected_codes: &HashSet<String>,
+) -> Result<u64, AppError> {
+    if issues.is_empty() {
+        return Ok(0);
+    }
+
+    let mut stock_codes = Vec::with_capacity(issues.len());
+    let mut rules = Vec::with_capacity(issues.len());
+    let mut severities = Vec::with_capacity(issues.len());
+    let mut messages = Vec::with_capacity(issues.len());
+    let mut details = Vec::with_capacity(issues.len());
+    let mut rejected = Vec::with_capacity(issues.len());
+
+    for issue in issues {
+        stock_codes.push(issue.stock_code.as_str());
+        rules.push(issue.rule);
+        severities.push(issue.severity.as_str());
+        messages.push(issue.message.as_str());
+        details.push(issue.details.clone());
+        rejected.push(rejected_codes.contains(&issue.stock_code));
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO stock_quality_issues (
+            trade_date, stock_code, rule, severity, message, details, rejected
+        )
+        SELECT $1, * FROM UNNEST(
+            $2::text[], $3::text[], $4::text[], $5::text[], $6::jsonb[], $7::boolean[]
+        )
+        ON CONFLICT (trade_date, stock_code, rule) DO UPDATE
+        SET severity = EXCLUDED.severity,
+            message = EXCLUDED.message,
+            details = EXCLUDED.details,
+            rejected = EXCLUDED.rejected,
+            created_at = NOW();
+        "#,
+    )
+    .bind(trade_date)
+    .bind(&stock_codes)
+    .bind(&rules)
+    .bind(&severities)
+    .bind(&messages)
+    .bind(&details)
+    .bind(&rejected)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// 以新的檢查結果取代某日既有的結果
+///
+/// 重新匯入同一天時，上次發現但這次已經沒有的問題也要一併清掉。
+pub async fn replace_issues(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+    issues: &[QualityIssue],
+    rejected_codes: &HashSet<String>,
+) -> Result<u64, AppError> {
+    delete_issues(&mut *conn, trade_date).await?;
+    record_issues(conn, trade_date, issues, rejected_codes).await
+}
+
+/// 刪除某日的檢查結果
+pub async fn delete_issues(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+) -> Result<u64, AppError> {
+    let result = sqlx::query("DELETE FROM stock_quality_issues WHERE trade_date = $1")
+        .bind(trade_date)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// 查詢資料品質問題
+pub async fn list_issues(
+    db: &PgPool,
+    filter: &IssueFilter,
+    limit: i64,
+) -> Result<Vec<StoredQualityIssue>, AppError> {
+    let issues = sqlx::query_as::<_, StoredQualityIssue>(
+        r#"
+        SELECT id, trade_date, stock_code, rule, severity, message, details, rejected, created_at
+        FROM stock_quality_issues
+        WHERE ($1::date IS NULL OR trade_date >= $1)
+          AND ($2::date IS NULL OR trade_date <= $2)
+          AND ($3::text IS NULL OR stock_code = $3)
+          AND ($4::text IS NULL OR rule = $4)
+          AND ($5::text IS NULL OR severity = $5)
+        ORDER BY trade_date DESC, stock_code, rule
+        LIMIT $6
+        "#,
+    )
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(&filter.stock_code)
+    .bind(&filter.rule)
+    .bind(&filter.severity)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(issues)
+}
@@ -0,0 +1,518 @@
+// src/services/dca.rs
+
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+/// 判斷為除權息的最小隱含配息（元），低於此值視為價格捨入誤差
+const MIN_IMPLIED_DIVIDEND: f64 = 0.005;
+
+/// 定期定額模擬參數
+#[derive(Debug)]
+pub struct DcaParams {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// 每期投入金額（元）
+    pub amount: f64,
+    /// 每月扣款日，遇非交易日順延到下一個交易日
+    pub day_of_month: u32,
+    /// 是否將配息再投入
+    pub reinvest_dividends: bool,
+    /// 手續費率
+    pub fee_rate: f64,
+    /// 每筆最低手續費（元）
+    pub min_fee: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerKind {
+    /// 定期扣款買進
+    Buy,
+    /// 除權息配發（依前日收盤與參考價推算）
+    Dividend,
+    /// 配息再投入買進
+    Reinvest,
+}
+
+/// 模擬明細中的一筆
+#[derive(Debug, Serialize)]
+pub struct LedgerEntry {
+    pub date: NaiveDate,
+    pub kind: LedgerKind,
+    pub price: f64,
+    /// 本筆買進的股數（配息列為 0）
+    pub shares: i64,
+    /// 本筆金額：買進為成交金額，配息為配發金額
+    pub amount: f64,
+    pub fee: f64,
+    /// 之後累積持有的股數
+    pub total_shares: i64,
+    /// 之後累積投入的本金（含手續費）
+    pub total_invested: f64,
+    /// 之後的持股市值（以當日收盤計）加上未投入現金
+    pub market_value: f64,
+}
+
+/// 定期定額模擬結果
+#[derive(Debug, Serialize)]
+pub struct DcaResult {
+    pub code: String,
+    pub name: String,
+    pub periods: usize,
+    pub total_shares: i64,
+    /// 投入的本金合計
+    pub total_invested: f64,
+    /// 手續費合計
+    pub total_fees: f64,
+    /// 每股平均成本（投入本金扣除零頭現金後平均，含手續費）
+    pub average_cost: Option<f64>,
+    /// 配息合計（不論是否再投入）
+    pub dividends: f64,
+    /// 尚未買進股票的零頭現金
+    pub cash: f64,
+    pub final_date: NaiveDate,
+    pub final_price: f64,
+    /// 期末市值（持股市值 + 零頭現金）
+    pub final_value: f64,
+    /// 總報酬率（%），未再投入的配息計入報酬
+    pub total_return_pct: Option<f64>,
+    /// 年化內部報酬率（XIRR）
+    pub irr: Option<f64>,
+    pub ledger: Vec<LedgerEntry>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DailyClose {
+    trade_date: NaiveDate,
+    close: f64,
+    price_change: Option<f64>,
+    /// 依 trading_calendar 的前一個交易日；行事曆尚未同步到這段期間時為 `None`
+    previous_trading_day: Option<NaiveDate>,
+}
+
+/// 以現金流計算年化內部報酬率（XIRR），以牛頓法求解，不收斂時改用二分法
+///
+/// 現金流以投資人角度表示：投入為負、取回為正。
+pub fn xirr(cashflows: &[(NaiveDate, f64)]) -> Option<f64> {
+    let (start, _) = *cashflows.first()?;
+    let has_in = cashflows.iter().any(|(_, v)| *v < 0.0);
+    let has_out = cashflows.iter().any(|(_, v)| *v > 0.0);
+    if !(has_in && has_out) {
+        return None;
+    }
+
+    let years = |date: NaiveDate| (date - start).num_days() as f64 / 365.0;
+    let npv = |rate: f64| {
+        cashflows
+            .iter()
+            .map(|(date, v)| v / (1.0 + rate).powf(years(*date)))
+            .sum::<f64>()
+    };
+    let derivative = |rate: f64| {
+        cashflows
+            .iter()
+            .map(|(date, v)| {
+                let t = years(*date);
+                -t * v / (1.0 + rate).powf(t + 1.0)
+            })
+            .sum::<f64>()
+    };
+
+    let mut rate = 0.1;
+    for _ in 0..100 {
+        let (value, slope) = (npv(rate), derivative(rate));
+        if slope == 0.0 || !slope.is_finite() {
+            break;
+        }
+        let next = rate - value / slope;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        if (next - rate).abs() < 1e-10 {
+            return Some(next);
+        }
+        rate = next;
+    }
+
+    // NPV 隨利率單調遞減（先投入後取回），在 (-0.9999, 100) 之間二分
+    let (mut low, mut high) = (-0.9999, 100.0);
+    if npv(low).signum() == npv(high).signum() {
+        return None;
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+/// 每月的預定扣款日（超過月底時取該月最後一天）
+fn schedule(from: NaiveDate, to: NaiveDate, day_of_month: u32) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    let mut month = NaiveDate::from_ymd_opt(from.year(), from.month(), 1).expect("每月一日");
+    while month <= to {
+        let last_day = (month + Months::new(1)).pred_opt().expect("月底");
+        let date = month.with_day(day_of_month).unwrap_or(last_day);
+        if date >= from && date <= to {
+            dates.push(date);
+        }
+        month = month + Months::new(1);
+    }
+    dates
+}
+
+/// 模擬定期定額投資一檔股票
+///
+/// 每期以「投入金額 + 上期零頭」買進整數股，手續費依費率計算並至少收取最低手續費。
+/// 配息以「前日收盤 − 當日參考價（收盤 − 漲跌價差）」推算，股票股利也會被視為等值現金。
+/// 只有前一筆資料正好是 trading_calendar 上的前一個交易日才推算，缺資料的日子不會被誤判為除權息；
+/// 行事曆尚未同步到該日時，退回與序列中的前一筆比較。
+pub async fn simulate(db: &PgPool, code: &str, params: &DcaParams) -> Result<DcaResult, AppError> {
+    if params.amount <= 0.0 {
+        return Err(AppError::bad_request("每期投入金額必須大於 0"));
+    }
+    if !(1..=31).contains(&params.day_of_month) {
+        return Err(AppError::bad_request("扣款日必須介於 1 到 31 之間"));
+    }
+
+    let name = sqlx::query_scalar::<_, String>(
+        "SELECT stock_name FROM stock_day_all WHERE stock_code = $1 ORDER BY trade_date DESC LIMIT 1",
+    )
+    .bind(code)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("找不到股票代號 {}", code)))?;
+
+    let closes = sqlx::query_as::<_, DailyClose>(
+        r#"
+        SELECT s.trade_date, s.close_price::float8 AS close, s.price_change::float8 AS price_change,
+               (SELECT MAX(c.trade_date) FROM trading_calendar c
+                WHERE c.is_trading_day AND c.trade_date < s.trade_date) AS previous_trading_day
+        FROM stock_day_all s
+        WHERE s.stock_code = $1
+          AND s.close_price IS NOT NULL
+          AND s.trade_date BETWEEN $2 AND $3
+        ORDER BY s.trade_date
+        "#,
+    )
+    .bind(code)
+    .bind(params.from)
+    .bind(params.to)
+    .fetch_all(db)
+    .await?;
+
+    if closes.is_empty() {
+        return Err(AppError::not_found(format!(
+            "{} 在 {} ~ {} 沒有收盤資料",
+            code, params.from, params.to
+        )));
+    }
+
+    run(code, name, &closes, params)
+}
+
+/// 依已載入的收盤序列（依日期排序、至少一筆）執行模擬
+fn run(
+    code: &str,
+    name: String,
+    closes: &[DailyClose],
+    params: &DcaParams,
+) -> Result<DcaResult, AppError> {
+    let Some(last) = closes.last() else {
+        return Err(AppError::not_found(format!("{} 沒有收盤資料", code)));
+    };
+    let (final_date, final_price) = (last.trade_date, last.close);
+
+    let mut pending = schedule(params.from, params.to, params.day_of_month).into_iter();
+    let mut next_buy = pending.next();
+
+    let mut shares: i64 = 0;
+    let mut cash = 0.0;
+    let mut total_invested = 0.0;
+    let mut total_fees = 0.0;
+    let mut dividends = 0.0;
+    let mut periods = 0;
+    let mut cashflows: Vec<(NaiveDate, f64)> = Vec::new();
+    let mut ledger: Vec<LedgerEntry> = Vec::new();
+
+    let fee_for = |cost: f64| {
+        if cost > 0.0 {
+            (cost * params.fee_rate).floor().max(params.min_fee)
+        } else {
+            0.0
+        }
+    };
+    // 用手上現金買進整數股，回傳 (股數, 成交金額, 手續費)
+    let buy = |budget: f64, price: f64| {
+        let mut count = (budget / (price * (1.0 + params.fee_rate))).floor() as i64;
+        while count > 0 && count as f64 * price + fee_for(count as f64 * price) > budget {
+            count -= 1;
+        }
+        let cost = count as f64 * price;
+        (count, cost, fee_for(cost))
+    };
+
+    let mut previous: Option<(NaiveDate, f64)> = None;
+    for day in closes {
+        // 前一筆不是前一個交易日（中間缺資料或停牌）時，當日漲跌無法和前一筆收盤比較；
+        // 行事曆沒有資料時無從判斷，只能相信序列本身是連續的
+        let previous_close = previous
+            .filter(|(date, _)| day.previous_trading_day.is_none_or(|p| p == *date))
+            .map(|(_, close)| close);
+        // 除權息：前日收盤高於當日參考價的差額
+        if let (Some(previous_close), Some(change)) = (previous_close, day.price_change) {
+            let dividend = previous_close - (day.close - change);
+            if dividend >= MIN_IMPLIED_DIVIDEND && shares > 0 {
+                let payout = (dividend * shares as f64 * 100.0).round() / 100.0;
+                dividends += payout;
+                if params.reinvest_dividends {
+                    cash += payout;
+                } else {
+                    cashflows.push((day.trade_date, payout));
+                }
+                ledger.push(LedgerEntry {
+                    date: day.trade_date,
+                    kind: LedgerKind::Dividend,
+                    price: dividend,
+                    shares: 0,
+                    amount: payout,
+                    fee: 0.0,
+                    total_shares: shares,
+                    total_invested,
+                    market_value: shares as f64 * day.close + cash,
+                });
+
+                if params.reinvest_dividends {
+                    let (count, cost, fee) = buy(cash, day.close);
+                    if count > 0 {
+                        shares += count;
+                        cash -= cost + fee;
+                        total_fees += fee;
+                        ledger.push(LedgerEntry {
+                            date: day.trade_date,
+                            kind: LedgerKind::Reinvest,
+                            price: day.close,
+                            shares: count,
+                            amount: cost,
+                            fee,
+                            total_shares: shares,
+                            total_invested,
+                            market_value: shares as f64 * day.close + cash,
+                        });
+                    }
+                }
+            }
+        }
+        previous = Some((day.trade_date, day.close));
+
+        // 扣款日遇休市順延；同一個交易日可能補上好幾期（例如資料缺漏時）
+        while let Some(date) = next_buy
+            && date <= day.trade_date
+        {
+            next_buy = pending.next();
+            periods += 1;
+            cash += params.amount;
+            total_invested += params.amount;
+            cashflows.push((day.trade_date, -params.amount));
+
+            let (count, cost, fee) = buy(cash, day.close);
+            shares += count;
+            cash -= cost + fee;
+            total_fees += fee;
+            ledger.push(LedgerEntry {
+                date: day.trade_date,
+                kind: LedgerKind::Buy,
+                price: day.close,
+                shares: count,
+                amount: cost,
+                fee,
+                total_shares: shares,
+                total_invested,
+                market_value: shares as f64 * day.close + cash,
+            });
+        }
+    }
+
+    if periods == 0 {
+        return Err(AppError::bad_request("區間內沒有任何扣款日"));
+    }
+
+    let final_value = shares as f64 * final_price + cash;
+    cashflows.push((final_date, final_value));
+
+    let paid_out = if params.reinvest_dividends {
+        0.0
+    } else {
+        dividends
+    };
+    let round2 = |v: f64| (v * 100.0).round() / 100.0;
+
+    Ok(DcaResult {
+        code: code.to_string(),
+        name,
+        periods,
+        total_shares: shares,
+        total_invested,
+        total_fees,
+        average_cost: (shares > 0).then(|| round2((total_invested - cash) / shares as f64)),
+        dividends: round2(dividends),
+        cash: round2(cash),
+        final_date,
+        final_price,
+        final_value: round2(final_value),
+        total_return_pct: (total_invested > 0.0)
+            .then(|| round2((final_value + paid_out - total_invested) / total_invested * 100.0)),
+        irr: xirr(&cashflows),
+        ledger,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn close(
+        trade_date: &str,
+        close: f64,
+        price_change: Option<f64>,
+        previous_trading_day: Option<&str>,
+    ) -> DailyClose {
+        DailyClose {
+            trade_date: date(trade_date),
+            close,
+            price_change,
+            previous_trading_day: previous_trading_day.map(date),
+        }
+    }
+
+    fn params(reinvest_dividends: bool) -> DcaParams {
+        DcaParams {
+            from: date("2024-01-01"),
+            to: date("2024-01-31"),
+            amount: 10_000.0,
+            day_of_month: 1,
+            reinvest_dividends,
+            fee_rate: 0.001425,
+            min_fee: 20.0,
+        }
+    }
+
+    fn kinds(result: &DcaResult) -> Vec<&'static str> {
+        result
+            .ledger
+            .iter()
+            .map(|entry| match entry.kind {
+                LedgerKind::Buy => "buy",
+                LedgerKind::Dividend => "dividend",
+                LedgerKind::Reinvest => "reinvest",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn xirr_matches_known_rate() {
+        // 每年年初投入 1000，兩年後取回以 8% 複利滾存的金額
+        let final_value = 1000.0 * 1.08_f64.powi(2) + 1000.0 * 1.08;
+        let cashflows = [
+            (date("2023-01-01"), -1000.0),
+            (date("2024-01-01"), -1000.0),
+            (date("2024-12-31"), final_value),
+        ];
+        let irr = xirr(&cashflows).unwrap();
+        assert!((irr - 0.08).abs() < 1e-9, "irr = {}", irr);
+
+        // 虧損一半
+        let irr = xirr(&[(date("2023-01-01"), -1000.0), (date("2024-01-01"), 500.0)]).unwrap();
+        assert!((irr + 0.5).abs() < 1e-9, "irr = {}", irr);
+    }
+
+    #[test]
+    fn xirr_needs_money_in_and_out() {
+        assert_eq!(xirr(&[]), None);
+        assert_eq!(xirr(&[(date("2024-01-01"), -1000.0)]), None);
+        assert_eq!(
+            xirr(&[(date("2024-01-01"), 1000.0), (date("2024-06-01"), 10.0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn reinvests_dividends_without_trading_calendar() {
+        let closes = [
+            close("2024-01-02", 100.0, None, None),
+            close("2024-01-03", 98.0, Some(-2.0), None),
+            // 參考價 97 低於前日收盤 98：每股配 1 元
+            close("2024-01-04", 97.0, Some(0.0), None),
+        ];
+        let result = run("0056", "元大高股息".into(), &closes, &params(true)).unwrap();
+
+        assert_eq!(kinds(&result), ["buy", "dividend", "reinvest"]);
+        // 10000 元買 99 股（9900 + 最低手續費 20），配息 99 元加零頭 80 元再買 1 股
+        assert_eq!(result.ledger[0].shares, 99);
+        assert_eq!(result.dividends, 99.0);
+        assert_eq!(result.total_shares, 100);
+        assert_eq!(result.cash, 62.0);
+        assert_eq!(result.total_fees, 40.0);
+        assert_eq!(result.final_value, 9762.0);
+        assert_eq!(result.total_return_pct, Some(-2.38));
+    }
+
+    #[test]
+    fn paid_out_dividends_count_toward_return() {
+        let closes = [
+            close("2024-01-02", 100.0, None, None),
+            close("2024-01-03", 99.0, Some(0.0), None),
+        ];
+        let result = run("0056", "元大高股息".into(), &closes, &params(false)).unwrap();
+
+        assert_eq!(kinds(&result), ["buy", "dividend"]);
+        // 期末市值 99 * 99 + 80 = 9881，加上配息 99 元
+        assert_eq!(result.final_value, 9881.0);
+        assert_eq!(result.total_return_pct, Some(-0.2));
+        assert!(result.irr.is_some());
+    }
+
+    #[test]
+    fn gap_in_series_is_not_treated_as_dividend() {
+        // 行事曆顯示 1/3 有開市但沒有資料，1/4 的漲跌無法和 1/2 的收盤比較
+        let closes = [
+            close("2024-01-02", 100.0, None, Some("2023-12-29")),
+            close("2024-01-04", 97.0, Some(0.0), Some("2024-01-03")),
+        ];
+        let result = run("2330", "台積電".into(), &closes, &params(true)).unwrap();
+
+        assert_eq!(kinds(&result), ["buy"]);
+        assert_eq!(result.dividends, 0.0);
+    }
+
+    #[test]
+    fn missed_periods_are_bought_on_next_available_day() {
+        let mut params = params(false);
+        params.to = date("2024-03-31");
+        let closes = [
+            close("2024-01-02", 100.0, None, None),
+            close("2024-03-04", 100.0, Some(0.0), None),
+        ];
+        let result = run("2330", "台積電".into(), &closes, &params).unwrap();
+
+        assert_eq!(result.periods, 3);
+        assert_eq!(result.total_invested, 30_000.0);
+        assert_eq!(kinds(&result), ["buy", "buy", "buy"]);
+        assert!(
+            result.ledger[1..]
+                .iter()
+                .all(|e| e.date == date("2024-03-04"))
+        );
+    }
+}
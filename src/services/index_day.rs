@@ -0,0 +1,252 @@
+// src/services/index_day.rs
+
+use axum::http::StatusCode;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{
+    error::AppError,
+    services::{
+        cache,
+        payload_archive::{self, RawPayload},
+    },
+    state::AppState,
+};
+
+/// TWSE 每日收盤行情（`type=IND` 只取指數表）
+const MI_INDEX_URL: &str = "https://www.twse.com.tw/exchangeReport/MI_INDEX";
+/// 原始回應封存時使用的來源名稱
+const ARCHIVE_SOURCE: &str = "mi_index";
+/// 發行量加權股價指數（TAIEX）在 MI_INDEX 中的名稱
+pub const TAIEX: &str = "發行量加權股價指數";
+
+#[derive(Deserialize, Debug)]
+struct MiIndexResponse {
+    #[serde(default)]
+    stat: String,
+    date: Option<String>,
+    #[serde(default)]
+    tables: Vec<MiIndexTable>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MiIndexTable {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    fields: Vec<String>,
+    #[serde(default)]
+    data: Vec<Vec<String>>,
+}
+
+/// 整理後的一筆指數資料
+#[derive(Debug, Clone)]
+pub struct IndexDayRow {
+    pub index_name: String,
+    pub close_value: f64,
+    pub change_points: Option<f64>,
+    pub change_pct: Option<f64>,
+}
+
+/// 匯入結果摘要
+#[derive(Debug, Serialize)]
+pub struct IndexIngestSummary {
+    pub trade_date: NaiveDate,
+    pub parsed_rows: usize,
+    pub inserted_rows: u64,
+    pub deleted_rows: u64,
+}
+
+/// 指數清單中的一項
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct IndexInfo {
+    pub index_name: String,
+    pub first_trade_date: NaiveDate,
+    pub last_trade_date: NaiveDate,
+}
+
+async fn fetch(state: &AppState, date: NaiveDate) -> Result<RawPayload, AppError> {
+    let resp = state
+        .http_client
+        .get(MI_INDEX_URL)
+        .query(&[
+            ("response", "json".to_string()),
+            ("date", date.format("%Y%m%d").to_string()),
+            ("type", "IND".to_string()),
+        ])
+        .send()
+        .await?;
+
+    let url = resp.url().to_string();
+    let status_code = resp.status().as_u16();
+    let body = resp.bytes().await?.to_vec();
+
+    Ok(RawPayload {
+        url,
+        status_code,
+        body,
+    })
+}
+
+/// 解析 MI_INDEX 回應中的「價格指數」表格
+fn parse(body: &[u8]) -> Result<(NaiveDate, Vec<IndexDayRow>), AppError> {
+    let resp: MiIndexResponse = serde_json::from_slice(body)
+        .map_err(|e| AppError::with_source(StatusCode::BAD_GATEWAY, "MI_INDEX 回應格式錯誤", e))?;
+
+    let date = resp.date.as_deref().ok_or_else(|| {
+        AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("MI_INDEX 回應沒有日期: {}", resp.stat),
+        )
+    })?;
+    let trade_date = NaiveDate::parse_from_str(date, "%Y%m%d")?;
+
+    let parse_f64 = |s: &str| s.replace(",", "").trim().parse::<f64>().ok();
+    let mut rows: Vec<IndexDayRow> = Vec::new();
+
+    for table in resp.tables.iter().filter(|t| t.title.contains("價格指數")) {
+        let column = |name: &str| table.fields.iter().position(|f| f.contains(name));
+        let name_col = table.fields.iter().position(|f| f.trim() == "指數");
+        let (Some(name_col), Some(close_col)) = (name_col, column("收盤指數")) else {
+            continue;
+        };
+        let sign_col = column("漲跌(+/-)");
+        let points_col = column("漲跌點數");
+        let pct_col = column("漲跌百分比");
+
+        for row in &table.data {
+            let Some(close_value) = row.get(close_col).and_then(|s| parse_f64(s)) else {
+                continue;
+            };
+            let Some(index_name) = row.get(name_col).map(|s| s.trim().to_string()) else {
+                continue;
+            };
+            // 同一個指數可能出現在多張表，保留第一次出現的
+            if rows.iter().any(|r| r.index_name == index_name) {
+                continue;
+            }
+
+            // 漲跌符號欄位是帶顏色的 HTML，例如 <p style='color:green'>-</p>
+            let sign = match sign_col.and_then(|i| row.get(i)) {
+                Some(s) if s.contains('-') => -1.0,
+                _ => 1.0,
+            };
+            let signed = |col: Option<usize>| {
+                col.and_then(|i| row.get(i))
+                    .and_then(|s| parse_f64(s))
+                    .map(|v| v.abs() * sign)
+            };
+
+            rows.push(IndexDayRow {
+                index_name,
+                close_value,
+                change_points: signed(points_col),
+                change_pct: signed(pct_col),
+            });
+        }
+    }
+
+    Ok((trade_date, rows))
+}
+
+/// 抓取並匯入指定交易日的 TAIEX 與各類指數
+pub async fn ingest(
+    state: &AppState,
+    date: NaiveDate,
+    replace: bool,
+) -> Result<IndexIngestSummary, AppError> {
+    let payload = fetch(state, date).await?;
+    let parsed = parse(&payload.body);
+    let response_date = parsed.as_ref().ok().map(|(d, _)| *d);
+    payload_archive::archive(
+        &state.db,
+        ARCHIVE_SOURCE,
+        &payload,
+        Some(date),
+        response_date,
+    )
+    .await?;
+
+    let (trade_date, rows) = parsed?;
+    if trade_date != date {
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!(
+                "MI_INDEX 回傳的日期 {} 與要求的日期 {} 不符",
+                trade_date, date
+            ),
+        ));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let deleted_rows = if replace {
+        sqlx::query("DELETE FROM index_day WHERE trade_date = $1")
+            .bind(trade_date)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    } else {
+        0
+    };
+
+    let index_names: Vec<&str> = rows.iter().map(|r| r.index_name.as_str()).collect();
+    let close_values: Vec<f64> = rows.iter().map(|r| r.close_value).collect();
+    let change_points: Vec<Option<f64>> = rows.iter().map(|r| r.change_points).collect();
+    let change_pcts: Vec<Option<f64>> = rows.iter().map(|r| r.change_pct).collect();
+
+    let inserted_rows = sqlx::query(
+        r#"
+        INSERT INTO index_day (trade_date, index_name, close_value, change_points, change_pct)
+        SELECT $1, * FROM UNNEST(
+            $2::text[], $3::double precision[], $4::double precision[], $5::double precision[]
+        )
+        ON CONFLICT (trade_date, index_name) DO NOTHING;
+        "#,
+    )
+    .bind(trade_date)
+    .bind(&index_names)
+    .bind(&close_values)
+    .bind(&change_points)
+    .bind(&change_pcts)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    cache::bump_version(&state.redis, "index_day").await;
+
+    tracing::info!(
+        "📊 index_day {}: 解析 {} 筆, 寫入 {} 筆, 刪除 {} 筆",
+        trade_date,
+        rows.len(),
+        inserted_rows,
+        deleted_rows
+    );
+
+    Ok(IndexIngestSummary {
+        trade_date,
+        parsed_rows: rows.len(),
+        inserted_rows,
+        deleted_rows,
+    })
+}
+
+/// 列出所有已匯入的指數
+pub async fn list_indices(db: &PgPool) -> Result<Vec<IndexInfo>, AppError> {
+    let indices = sqlx::query_as::<_, IndexInfo>(
+        r#"
+        SELECT index_name,
+               MIN(trade_date) AS first_trade_date,
+               MAX(trade_date) AS last_trade_date
+        FROM index_day
+        GROUP BY index_name
+        ORDER BY index_name
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(indices)
+}
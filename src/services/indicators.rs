@@ -0,0 +1,21 @@
+// src/services/indicators.rs
+
+/// 簡單移動平均；資料不足 `window` 筆的位置為 `None`
+pub fn moving_average(values: &[f64], window: usize) -> Vec<Option<f64>> {
+    if window == 0 {
+        return vec![None; values.len()];
+    }
+
+    let mut result = Vec::with_capacity(values.len());
+    let mut sum = 0.0;
+
+    for (i, value) in values.iter().enumerate() {
+        sum += value;
+        if i >= window {
+            sum -= values[i - window];
+        }
+        result.push((i + 1 >= window).then(|| sum / window as f64));
+    }
+
+    result
+}
@@ -0,0 +1,194 @@
+// src/services/industry.rs
+
+use std::cmp::Reverse;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{error::AppError, services::market_summary};
+
+/// 沒有產業分類的股票（ETF、權證等）歸入的節點名稱
+pub const UNCLASSIFIED: &str = "未分類";
+
+/// 個股的產業分類
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StockIndustry {
+    pub stock_code: String,
+    pub industry: String,
+    pub source: String,
+}
+
+/// 熱力圖中的個股節點
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct HeatmapStock {
+    pub code: String,
+    pub name: String,
+    /// 成交金額，用於決定方塊大小
+    pub trade_amount: i64,
+    pub close: Option<f64>,
+    /// 漲跌幅（%），用於決定顏色；沒有前一日收盤時為 `None`
+    pub change_pct: Option<f64>,
+}
+
+/// 熱力圖中的產業節點
+#[derive(Debug, Serialize)]
+pub struct HeatmapIndustry {
+    pub name: String,
+    /// 產業內個股成交金額合計
+    pub trade_amount: i64,
+    /// 以成交金額加權的平均漲跌幅（%）
+    pub change_pct: Option<f64>,
+    pub children: Vec<HeatmapStock>,
+}
+
+/// 產業 → 個股的熱力圖資料
+#[derive(Debug, Serialize)]
+pub struct Heatmap {
+    pub trade_date: NaiveDate,
+    pub trade_amount: i64,
+    pub children: Vec<HeatmapIndustry>,
+}
+
+#[derive(sqlx::FromRow)]
+struct HeatmapRow {
+    industry: String,
+    #[sqlx(flatten)]
+    stock: HeatmapStock,
+}
+
+/// 以月營收中最新的產業別更新分類，手動維護的分類不會被覆蓋
+pub async fn sync_from_monthly_revenue(db: &PgPool) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO stock_industries (stock_code, industry, source)
+        SELECT DISTINCT ON (stock_code) stock_code, industry, 'monthly_revenue'
+        FROM monthly_revenue
+        WHERE industry IS NOT NULL
+        ORDER BY stock_code, revenue_month DESC
+        ON CONFLICT (stock_code) DO UPDATE
+        SET industry = EXCLUDED.industry,
+            updated_at = NOW()
+        WHERE stock_industries.source <> 'manual'
+          AND stock_industries.industry <> EXCLUDED.industry
+        "#,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// 手動設定個股的產業分類（標記為 manual，之後同步不會覆蓋）
+pub async fn upsert_manual(
+    db: &PgPool,
+    stock_code: &str,
+    industry: &str,
+) -> Result<StockIndustry, AppError> {
+    let industry = industry.trim();
+    if industry.is_empty() {
+        return Err(AppError::bad_request("產業別不可為空"));
+    }
+
+    let row = sqlx::query_as::<_, StockIndustry>(
+        r#"
+        INSERT INTO stock_industries (stock_code, industry, source)
+        VALUES ($1, $2, 'manual')
+        ON CONFLICT (stock_code) DO UPDATE
+        SET industry = EXCLUDED.industry,
+            source = 'manual',
+            updated_at = NOW()
+        RETURNING stock_code, industry, source
+        "#,
+    )
+    .bind(stock_code)
+    .bind(industry)
+    .fetch_one(db)
+    .await?;
+    market_summary::refresh_after_write(db).await;
+
+    Ok(row)
+}
+
+/// 產生指定交易日（預設為最新交易日）的產業熱力圖資料
+///
+/// 只納入有成交金額的股票；產業依成交金額由大到小排序，個股亦同。
+/// 產業的合計與加權漲跌幅來自 industry_daily_summary。
+pub async fn heatmap(db: &PgPool, date: Option<NaiveDate>) -> Result<Heatmap, AppError> {
+    let trade_date = match date {
+        Some(date) => date,
+        None => {
+            sqlx::query_scalar::<_, Option<NaiveDate>>("SELECT MAX(trade_date) FROM stock_day_all")
+                .fetch_one(db)
+                .await?
+                .ok_or_else(|| AppError::not_found("尚未匯入任何日成交資料"))?
+        }
+    };
+
+    let rows = sqlx::query_as::<_, HeatmapRow>(
+        r#"
+        SELECT COALESCE(i.industry, $2) AS industry,
+               d.stock_code AS code,
+               d.stock_name AS name,
+               d.trade_amount,
+               d.close_price::float8 AS close,
+               CASE WHEN d.close_price - d.price_change > 0
+                    THEN ROUND(d.price_change / (d.close_price - d.price_change) * 100, 2)::float8
+               END AS change_pct
+        FROM stock_day_all d
+        LEFT JOIN stock_industries i ON i.stock_code = d.stock_code
+        WHERE d.trade_date = $1
+          AND d.trade_amount > 0
+        ORDER BY d.trade_amount DESC
+        "#,
+    )
+    .bind(trade_date)
+    .bind(UNCLASSIFIED)
+    .fetch_all(db)
+    .await?;
+
+    if rows.is_empty() {
+        return Err(AppError::not_found(format!(
+            "{} 沒有日成交資料",
+            trade_date
+        )));
+    }
+
+    // 產業層級的合計與加權漲跌幅取自每日產業統計（物化視圖），個股再依分類掛上
+    let mut industries: Vec<HeatmapIndustry> = market_summary::list_industries(db, trade_date)
+        .await?
+        .into_iter()
+        .filter(|summary| summary.trade_amount > 0)
+        .map(|summary| HeatmapIndustry {
+            name: summary.industry,
+            trade_amount: summary.trade_amount,
+            change_pct: summary.change_pct,
+            children: Vec::new(),
+        })
+        .collect();
+
+    for row in rows {
+        match industries.iter_mut().find(|n| n.name == row.industry) {
+            Some(node) => node.children.push(row.stock),
+            None => industries.push(HeatmapIndustry {
+                name: row.industry,
+                trade_amount: 0,
+                change_pct: None,
+                children: vec![row.stock],
+            }),
+        }
+    }
+
+    // 統計尚未重新整理（例如剛調整分類）的產業，先以個股成交金額加總
+    industries.retain(|n| !n.children.is_empty());
+    for node in industries.iter_mut().filter(|n| n.trade_amount == 0) {
+        node.trade_amount = node.children.iter().map(|s| s.trade_amount).sum();
+    }
+    industries.sort_by_key(|n| Reverse(n.trade_amount));
+
+    Ok(Heatmap {
+        trade_date,
+        trade_amount: industries.iter().map(|n| n.trade_amount).sum(),
+        children: industries,
+    })
+}
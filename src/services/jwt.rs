@@ -0,0 +1,204 @@
+// src/services/jwt.rs
+
+use axum::http::{HeaderMap, header};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{config::JwtConfig, error::AppError};
+
+/// access token 的 claims
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessClaims {
+    pub sub: Uuid,
+    pub iss: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+fn ensure_enabled(config: &JwtConfig) -> Result<(), AppError> {
+    if config.keys.is_empty() {
+        return Err(AppError::not_found("未啟用 token 登入"));
+    }
+    Ok(())
+}
+
+/// 以目前的 kid 簽發 access token，回傳 (token, 有效秒數)
+pub fn issue_access_token(config: &JwtConfig, user_id: Uuid) -> Result<(String, u64), AppError> {
+    ensure_enabled(config)?;
+    let secret = config
+        .key(&config.active_kid)
+        .ok_or_else(|| AppError::internal_error("找不到簽章密鑰"))?;
+
+    let now = Utc::now().timestamp();
+    let ttl = config.access_ttl.as_secs();
+    let claims = AccessClaims {
+        sub: user_id,
+        iss: config.issuer.clone(),
+        iat: now,
+        exp: now + ttl as i64,
+    };
+    let header = Header {
+        kid: Some(config.active_kid.clone()),
+        ..Header::new(Algorithm::HS256)
+    };
+
+    let token = jsonwebtoken::encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|e| AppError::internal_error(format!("簽發 access token 失敗: {}", e)))?;
+    Ok((token, ttl))
+}
+
+/// 驗證 access token，依 header 的 kid 挑選密鑰，讓輪替前簽出的 token 仍可使用到過期
+pub fn verify_access_token(config: &JwtConfig, token: &str) -> Result<AccessClaims, AppError> {
+    ensure_enabled(config)?;
+    let invalid = || AppError::unauthorized("access token 無效或已過期");
+
+    let header = jsonwebtoken::decode_header(token).map_err(|_| invalid())?;
+    if header.alg != Algorithm::HS256 {
+        return Err(invalid());
+    }
+    let secret = header
+        .kid
+        .as_deref()
+        .and_then(|kid| config.key(kid))
+        .ok_or_else(invalid)?;
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[config.issuer.as_str()]);
+    validation.set_required_spec_claims(&["exp", "iss", "sub"]);
+
+    let data = jsonwebtoken::decode::<AccessClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|_| invalid())?;
+    Ok(data.claims)
+}
+
+/// 取出 `Authorization: Bearer ...` 中的 token
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const SECRET_1: &str = "first-secret-with-at-least-32-characters";
+    const SECRET_2: &str = "second-secret-with-at-least-32-characters";
+
+    fn config(keys: &[(&str, &str)], active_kid: &str) -> JwtConfig {
+        JwtConfig {
+            keys: keys
+                .iter()
+                .map(|(kid, secret)| (kid.to_string(), secret.to_string()))
+                .collect(),
+            active_kid: active_kid.to_string(),
+            issuer: "axum-app".to_string(),
+            access_ttl: Duration::from_secs(15 * 60),
+            refresh_ttl: Duration::from_secs(24 * 60 * 60),
+            refresh_max_age: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+
+    fn sign(kid: Option<&str>, secret: &str, claims: &AccessClaims) -> String {
+        let header = Header {
+            kid: kid.map(str::to_string),
+            ..Header::new(Algorithm::HS256)
+        };
+        jsonwebtoken::encode(
+            &header,
+            claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn claims(iss: &str, exp_in: i64) -> AccessClaims {
+        let now = Utc::now().timestamp();
+        AccessClaims {
+            sub: Uuid::new_v4(),
+            iss: iss.to_string(),
+            iat: now,
+            exp: now + exp_in,
+        }
+    }
+
+    #[test]
+    fn tokens_from_previous_kid_verify_until_key_is_removed() {
+        let user_id = Uuid::new_v4();
+        let before = config(&[("k1", SECRET_1)], "k1");
+        let (token, _) = issue_access_token(&before, user_id).unwrap();
+
+        // 輪替：新 token 以 k2 簽發，k1 簽出的 token 仍可使用
+        let rotated = config(&[("k1", SECRET_1), ("k2", SECRET_2)], "k2");
+        assert_eq!(verify_access_token(&rotated, &token).unwrap().sub, user_id);
+        let (new_token, _) = issue_access_token(&rotated, user_id).unwrap();
+        let header = jsonwebtoken::decode_header(&new_token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("k2"));
+        assert_eq!(
+            verify_access_token(&rotated, &new_token).unwrap().sub,
+            user_id
+        );
+
+        // 移除 k1 後，舊 token 失效
+        let after = config(&[("k2", SECRET_2)], "k2");
+        let err = verify_access_token(&after, &token).unwrap_err();
+        assert_eq!(err.status_code, axum::http::StatusCode::UNAUTHORIZED);
+        assert!(verify_access_token(&after, &new_token).is_ok());
+    }
+
+    #[test]
+    fn key_is_chosen_by_kid_not_by_trying_every_key() {
+        let config = config(&[("k1", SECRET_1), ("k2", SECRET_2)], "k2");
+        let claims = claims("axum-app", 60);
+
+        assert!(verify_access_token(&config, &sign(Some("k1"), SECRET_1, &claims)).is_ok());
+        // header 宣稱 k2 卻以 k1 的密鑰簽章
+        assert!(verify_access_token(&config, &sign(Some("k2"), SECRET_1, &claims)).is_err());
+        assert!(verify_access_token(&config, &sign(None, SECRET_2, &claims)).is_err());
+        assert!(verify_access_token(&config, &sign(Some("k3"), SECRET_2, &claims)).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_issuer_expired_and_other_algorithms() {
+        let config = config(&[("k1", SECRET_1)], "k1");
+        let verify = |token: &str| verify_access_token(&config, token);
+
+        assert!(verify(&sign(Some("k1"), SECRET_1, &claims("other-app", 60))).is_err());
+        assert!(verify(&sign(Some("k1"), SECRET_1, &claims("axum-app", -120))).is_err());
+
+        let header = Header {
+            kid: Some("k1".to_string()),
+            ..Header::new(Algorithm::HS512)
+        };
+        let hs512 = jsonwebtoken::encode(
+            &header,
+            &claims("axum-app", 60),
+            &EncodingKey::from_secret(SECRET_1.as_bytes()),
+        )
+        .unwrap();
+        assert!(verify(&hs512).is_err());
+        assert!(verify("not-a-jwt").is_err());
+    }
+
+    #[test]
+    fn disabled_without_keys() {
+        let config = config(&[], "");
+        let err = issue_access_token(&config, Uuid::new_v4()).unwrap_err();
+        assert_eq!(err.status_code, axum::http::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,103 @@
+// src/services/market_summary.rs
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+/// 每日全市場統計（market_daily_summary）
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MarketDailySummary {
+    pub trade_date: NaiveDate,
+    pub stock_count: i64,
+    pub advancers: i64,
+    pub decliners: i64,
+    pub unchanged: i64,
+    pub limit_up_count: i64,
+    pub limit_down_count: i64,
+    pub halted_count: i64,
+    pub total_volume: i64,
+    pub total_amount: i64,
+    pub total_transactions: i64,
+}
+
+/// 每日產業統計（industry_daily_summary）
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct IndustryDailySummary {
+    pub industry: String,
+    pub stock_count: i64,
+    pub advancers: i64,
+    pub decliners: i64,
+    pub trade_amount: i64,
+    /// 以成交金額加權的平均漲跌幅（%）
+    pub change_pct: Option<f64>,
+}
+
+/// 重新整理統計用的物化視圖；使用 CONCURRENTLY，整理期間讀取不會被阻擋
+pub async fn refresh(db: &PgPool) -> Result<(), AppError> {
+    for view in ["market_daily_summary", "industry_daily_summary"] {
+        sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", view))
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 匯入或分類變更後重新整理統計；失敗只記錄警告，不影響已完成的寫入
+pub async fn refresh_after_write(db: &PgPool) {
+    if let Err(e) = refresh(db).await {
+        tracing::warn!("重新整理每日統計失敗: {}", e);
+    }
+}
+
+/// 查詢區間內的每日全市場統計，取最新的 `limit` 個交易日
+pub async fn list_market(
+    db: &PgPool,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+) -> Result<Vec<MarketDailySummary>, AppError> {
+    let rows = sqlx::query_as::<_, MarketDailySummary>(
+        r#"
+        SELECT * FROM (
+            SELECT trade_date, stock_count, advancers, decliners, unchanged,
+                   limit_up_count, limit_down_count, halted_count,
+                   total_volume, total_amount, total_transactions
+            FROM market_daily_summary
+            WHERE ($1::date IS NULL OR trade_date >= $1)
+              AND ($2::date IS NULL OR trade_date <= $2)
+            ORDER BY trade_date DESC
+            LIMIT $3
+        ) t
+        ORDER BY trade_date
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows)
+}
+
+/// 某個交易日的各產業統計
+pub async fn list_industries(
+    db: &PgPool,
+    trade_date: NaiveDate,
+) -> Result<Vec<IndustryDailySummary>, AppError> {
+    let rows = sqlx::query_as::<_, IndustryDailySummary>(
+        r#"
+        SELECT industry, stock_count, advancers, decliners, trade_amount, change_pct
+        FROM industry_daily_summary
+        WHERE trade_date = $1
+        ORDER BY trade_amount DESC, industry
+        "#,
+    )
+    .bind(trade_date)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows)
+}
@@ -0,0 +1,556 @@
+// src/services/monthly_revenue.rs
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{
+    error::AppError,
+    services::{
+        industry, market_summary,
+        payload_archive::{self, RawPayload},
+        trading_calendar::parse_twse_date,
+    },
+    state::AppState,
+};
+
+/// 原始回應封存時使用的來源名稱
+const ARCHIVE_SOURCE: &str = "monthly_revenue";
+
+/// TWSE OpenAPI `t187ap05_L`（上市公司每月營業收入彙總表）的一筆資料
+///
+/// 欄位皆為字串，空白或 `-` 代表沒有資料。
+#[derive(Deserialize, Debug)]
+struct RevenueRecord {
+    #[serde(rename = "出表日期", default)]
+    published_date: Option<String>,
+    #[serde(rename = "資料年月", default)]
+    revenue_month: Option<String>,
+    #[serde(rename = "公司代號", default)]
+    stock_code: Option<String>,
+    #[serde(rename = "公司名稱", default)]
+    company_name: Option<String>,
+    #[serde(rename = "產業別", default)]
+    industry: Option<String>,
+    #[serde(rename = "營業收入-當月營收", default)]
+    revenue: Option<String>,
+    #[serde(rename = "營業收入-上月營收", default)]
+    last_month_revenue: Option<String>,
+    #[serde(rename = "營業收入-去年當月營收", default)]
+    last_year_revenue: Option<String>,
+    #[serde(rename = "營業收入-上月比較增減(%)", default)]
+    mom_pct: Option<String>,
+    #[serde(rename = "營業收入-去年同月增減(%)", default)]
+    yoy_pct: Option<String>,
+    #[serde(rename = "累計營業收入-當月累計營收", default)]
+    cumulative_revenue: Option<String>,
+    #[serde(rename = "累計營業收入-去年累計營收", default)]
+    last_year_cumulative_revenue: Option<String>,
+    #[serde(rename = "累計營業收入-前期比較增減(%)", default)]
+    cumulative_yoy_pct: Option<String>,
+    #[serde(rename = "備註", default)]
+    note: Option<String>,
+}
+
+/// 整理後的一筆月營收（金額單位為千元）
+#[derive(Debug, Clone)]
+pub struct MonthlyRevenueRow {
+    pub stock_code: String,
+    pub company_name: String,
+    pub industry: Option<String>,
+    pub revenue_month: NaiveDate,
+    pub revenue: Option<i64>,
+    pub last_month_revenue: Option<i64>,
+    pub last_year_revenue: Option<i64>,
+    pub mom_pct: Option<f64>,
+    pub yoy_pct: Option<f64>,
+    pub cumulative_revenue: Option<i64>,
+    pub last_year_cumulative_revenue: Option<i64>,
+    pub cumulative_yoy_pct: Option<f64>,
+    pub note: Option<String>,
+    pub published_date: Option<NaiveDate>,
+}
+
+/// 匯入結果摘要
+#[derive(Debug, Serialize)]
+pub struct RevenueIngestSummary {
+    pub source: String,
+    /// 本次資料涵蓋的營收月份
+    pub revenue_months: Vec<NaiveDate>,
+    pub parsed_rows: usize,
+    pub skipped_rows: usize,
+    pub upserted_rows: u64,
+}
+
+/// 個股月營收與同月份股價
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StockMonthlyRevenue {
+    pub revenue_month: NaiveDate,
+    pub revenue: Option<i64>,
+    pub mom_pct: Option<f64>,
+    pub yoy_pct: Option<f64>,
+    pub cumulative_revenue: Option<i64>,
+    pub cumulative_yoy_pct: Option<f64>,
+    pub published_date: Option<NaiveDate>,
+    /// 該月最後一個交易日的收盤價
+    pub month_close: Option<f64>,
+    /// 該月平均收盤價
+    pub avg_close: Option<f64>,
+    /// 該月收盤價相對上月最後收盤的漲跌幅（%）
+    pub month_change_pct: Option<f64>,
+}
+
+/// 個股月營收回應
+#[derive(Debug, Serialize)]
+pub struct StockRevenueHistory {
+    pub stock_code: String,
+    pub company_name: String,
+    pub industry: Option<String>,
+    pub months: Vec<StockMonthlyRevenue>,
+}
+
+/// 月營收篩選條件
+#[derive(Debug)]
+pub struct RevenueScreen {
+    /// 營收月份；`None` 代表資料庫中最新的月份
+    pub revenue_month: Option<NaiveDate>,
+    pub min_yoy: Option<f64>,
+    pub min_mom: Option<f64>,
+    pub industry: Option<String>,
+}
+
+/// 月營收篩選結果，附上最新收盤價
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RevenueScreenRow {
+    pub stock_code: String,
+    pub company_name: String,
+    pub industry: Option<String>,
+    pub revenue_month: NaiveDate,
+    pub revenue: Option<i64>,
+    pub mom_pct: Option<f64>,
+    pub yoy_pct: Option<f64>,
+    pub cumulative_yoy_pct: Option<f64>,
+    pub last_trade_date: Option<NaiveDate>,
+    pub last_close: Option<f64>,
+}
+
+/// 讀取資料來源：`http(s)://` 開頭時向上游抓取，其餘視為本機檔案（可加 `file://` 前綴）
+async fn fetch(http_client: &reqwest::Client, source: &str) -> Result<RawPayload, AppError> {
+    if is_remote(source) {
+        let resp = http_client.get(source).send().await?;
+        let url = resp.url().to_string();
+        let status_code = resp.status().as_u16();
+        let body = resp.bytes().await?.to_vec();
+        return Ok(RawPayload {
+            url,
+            status_code,
+            body,
+        });
+    }
+
+    let path = source.strip_prefix("file://").unwrap_or(source);
+    let body = tokio::fs::read(path).await.map_err(|e| {
+        AppError::with_source(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("無法讀取月營收檔案 {}", path),
+            e,
+        )
+    })?;
+    Ok(RawPayload {
+        url: source.to_string(),
+        status_code: 200,
+        body,
+    })
+}
+
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// 解析民國年月（例如 `11401`）為該月第一天
+fn parse_roc_month(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    if s.len() < 4 || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (year, month) = s.split_at(s.len() - 2);
+    NaiveDate::from_ymd_opt(year.parse::<i32>().ok()? + 1911, month.parse().ok()?, 1)
+}
+
+fn parse_i64(s: &Option<String>) -> Option<i64> {
+    s.as_deref()?.replace(",", "").trim().parse().ok()
+}
+
+fn parse_f64(s: &Option<String>) -> Option<f64> {
+    s.as_deref()?
+        .replace(",", "")
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+}
+
+/// 來源沒有提供增減百分比時自行計算
+fn growth_pct(current: Option<i64>, base: Option<i64>) -> Option<f64> {
+    match (current, base) {
+        (Some(current), Some(base)) if base != 0 => {
+            Some(((current - base) as f64 / base.abs() as f64 * 10000.0).round() / 100.0)
+        }
+        _ => None,
+    }
+}
+
+/// 解析月營收 JSON，回傳整理後的資料與略過的筆數
+fn parse(body: &[u8]) -> Result<(Vec<MonthlyRevenueRow>, usize), AppError> {
+    let records: Vec<RevenueRecord> = serde_json::from_slice(body)
+        .map_err(|e| AppError::with_source(StatusCode::BAD_GATEWAY, "月營收資料格式錯誤", e))?;
+
+    let total = records.len();
+    let rows: Vec<MonthlyRevenueRow> = records
+        .into_iter()
+        .filter_map(|r| {
+            let stock_code = r.stock_code.as_deref()?.trim().to_string();
+            let revenue_month = parse_roc_month(r.revenue_month.as_deref()?)?;
+            if stock_code.is_empty() {
+                return None;
+            }
+
+            let revenue = parse_i64(&r.revenue);
+            let last_month_revenue = parse_i64(&r.last_month_revenue);
+            let last_year_revenue = parse_i64(&r.last_year_revenue);
+            let cumulative_revenue = parse_i64(&r.cumulative_revenue);
+            let last_year_cumulative_revenue = parse_i64(&r.last_year_cumulative_revenue);
+            let text = |s: Option<String>| {
+                s.map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty() && s != "-")
+            };
+
+            Some(MonthlyRevenueRow {
+                company_name: text(r.company_name).unwrap_or_else(|| stock_code.clone()),
+                stock_code,
+                industry: text(r.industry),
+                revenue_month,
+                revenue,
+                last_month_revenue,
+                last_year_revenue,
+                mom_pct: parse_f64(&r.mom_pct).or_else(|| growth_pct(revenue, last_month_revenue)),
+                yoy_pct: parse_f64(&r.yoy_pct).or_else(|| growth_pct(revenue, last_year_revenue)),
+                cumulative_revenue,
+                last_year_cumulative_revenue,
+                cumulative_yoy_pct: parse_f64(&r.cumulative_yoy_pct)
+                    .or_else(|| growth_pct(cumulative_revenue, last_year_cumulative_revenue)),
+                note: text(r.note),
+                published_date: r.published_date.as_deref().and_then(parse_twse_date),
+            })
+        })
+        .collect();
+
+    let skipped = total - rows.len();
+    Ok((rows, skipped))
+}
+
+/// 從設定的來源匯入月營收；同一公司同一月份的資料會以最新內容覆蓋（更正後的營收）
+pub async fn ingest(state: &AppState) -> Result<RevenueIngestSummary, AppError> {
+    let source = state.config.monthly_revenue.source.as_str();
+    let payload = fetch(&state.http_client, source).await?;
+    if !(200..300).contains(&payload.status_code) {
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("月營收來源回應 HTTP {}", payload.status_code),
+        ));
+    }
+
+    // 只封存上游回應，本機測試檔案不需要
+    if is_remote(source) {
+        payload_archive::archive(&state.db, ARCHIVE_SOURCE, &payload, None, None).await?;
+    }
+
+    let (rows, skipped_rows) = parse(&payload.body)?;
+    let upserted_rows = upsert_rows(&state.db, &rows).await?;
+    // 月營收的產業別同時作為熱力圖的產業分類
+    industry::sync_from_monthly_revenue(&state.db).await?;
+    market_summary::refresh_after_write(&state.db).await;
+
+    let mut revenue_months: Vec<NaiveDate> = rows.iter().map(|r| r.revenue_month).collect();
+    revenue_months.sort();
+    revenue_months.dedup();
+
+    tracing::info!(
+        "💰 monthly_revenue {:?}: 解析 {} 筆, 略過 {} 筆, 寫入 {} 筆",
+        revenue_months,
+        rows.len(),
+        skipped_rows,
+        upserted_rows
+    );
+
+    Ok(RevenueIngestSummary {
+        source: source.to_string(),
+        revenue_months,
+        parsed_rows: rows.len(),
+        skipped_rows,
+        upserted_rows,
+    })
+}
+
+async fn upsert_rows(db: &PgPool, rows: &[MonthlyRevenueRow]) -> Result<u64, AppError> {
+    let stock_codes: Vec<&str> = rows.iter().map(|r| r.stock_code.as_str()).collect();
+    let company_names: Vec<&str> = rows.iter().map(|r| r.company_name.as_str()).collect();
+    let industries: Vec<Option<&str>> = rows.iter().map(|r| r.industry.as_deref()).collect();
+    let revenue_months: Vec<NaiveDate> = rows.iter().map(|r| r.revenue_month).collect();
+    let revenues: Vec<Option<i64>> = rows.iter().map(|r| r.revenue).collect();
+    let last_month_revenues: Vec<Option<i64>> = rows.iter().map(|r| r.last_month_revenue).collect();
+    let last_year_revenues: Vec<Option<i64>> = rows.iter().map(|r| r.last_year_revenue).collect();
+    let mom_pcts: Vec<Option<f64>> = rows.iter().map(|r| r.mom_pct).collect();
+    let yoy_pcts: Vec<Option<f64>> = rows.iter().map(|r| r.yoy_pct).collect();
+    let cumulative_revenues: Vec<Option<i64>> = rows.iter().map(|r| r.cumulative_revenue).collect();
+    let last_year_cumulative_revenues: Vec<Option<i64>> = rows
+        .iter()
+        .map(|r| r.last_year_cumulative_revenue)
+        .collect();
+    let cumulative_yoy_pcts: Vec<Option<f64>> = rows.iter().map(|r| r.cumulative_yoy_pct).collect();
+    let notes: Vec<Option<&str>> = rows.iter().map(|r| r.note.as_deref()).collect();
+    let published_dates: Vec<Option<NaiveDate>> = rows.iter().map(|r| r.published_date).collect();
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO monthly_revenue (
+            stock_code, company_name, industry, revenue_month,
+            revenue, last_month_revenue, last_year_revenue, mom_pct, yoy_pct,
+            cumulative_revenue, last_year_cumulative_revenue, cumulative_yoy_pct,
+            note, published_date
+        )
+        SELECT * FROM UNNEST(
+            $1::text[], $2::text[], $3::text[], $4::date[],
+            $5::bigint[], $6::bigint[], $7::bigint[], $8::double precision[], $9::double precision[],
+            $10::bigint[], $11::bigint[], $12::double precision[],
+            $13::text[], $14::date[]
+        )
+        ON CONFLICT (stock_code, revenue_month) DO UPDATE
+        SET company_name = EXCLUDED.company_name,
+            industry = EXCLUDED.industry,
+            revenue = EXCLUDED.revenue,
+            last_month_revenue = EXCLUDED.last_month_revenue,
+            last_year_revenue = EXCLUDED.last_year_revenue,
+            mom_pct = EXCLUDED.mom_pct,
+            yoy_pct = EXCLUDED.yoy_pct,
+            cumulative_revenue = EXCLUDED.cumulative_revenue,
+            last_year_cumulative_revenue = EXCLUDED.last_year_cumulative_revenue,
+            cumulative_yoy_pct = EXCLUDED.cumulative_yoy_pct,
+            note = EXCLUDED.note,
+            published_date = EXCLUDED.published_date,
+            updated_at = NOW();
+        "#,
+    )
+    .bind(&stock_codes)
+    .bind(&company_names)
+    .bind(&industries)
+    .bind(&revenue_months)
+    .bind(&revenues)
+    .bind(&last_month_revenues)
+    .bind(&last_year_revenues)
+    .bind(&mom_pcts)
+    .bind(&yoy_pcts)
+    .bind(&cumulative_revenues)
+    .bind(&last_year_cumulative_revenues)
+    .bind(&cumulative_yoy_pcts)
+    .bind(&notes)
+    .bind(&published_dates)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// 依設定的間隔在背景定期匯入月營收；間隔為 0 時不啟動
+pub fn spawn_scheduler(state: Arc<AppState>) {
+    let interval = state.config.monthly_revenue.interval;
+    if interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = ingest(&state).await {
+                tracing::warn!("月營收定期匯入失敗: {}", e);
+            }
+        }
+    });
+}
+
+/// 個股最近 `months` 個月的月營收，並對照同月份的收盤價
+pub async fn stock_history(
+    db: &PgPool,
+    code: &str,
+    months: i64,
+) -> Result<StockRevenueHistory, AppError> {
+    let (company_name, industry) = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        SELECT company_name, industry FROM monthly_revenue
+        WHERE stock_code = $1
+        ORDER BY revenue_month DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(code)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("找不到股票代號 {} 的月營收", code)))?;
+
+    let months = sqlx::query_as::<_, StockMonthlyRevenue>(
+        r#"
+        SELECT * FROM (
+            SELECT r.revenue_month, r.revenue,
+                   r.mom_pct::float8 AS mom_pct,
+                   r.yoy_pct::float8 AS yoy_pct,
+                   r.cumulative_revenue,
+                   r.cumulative_yoy_pct::float8 AS cumulative_yoy_pct,
+                   r.published_date,
+                   price.month_close, price.avg_close,
+                   ROUND(((price.month_close / prev.close - 1) * 100)::numeric, 2)::float8
+                       AS month_change_pct
+            FROM monthly_revenue r
+            LEFT JOIN LATERAL (
+                SELECT (ARRAY_AGG(close_price ORDER BY trade_date DESC))[1]::float8 AS month_close,
+                       AVG(close_price)::float8 AS avg_close
+                FROM stock_day_all
+                WHERE stock_code = r.stock_code
+                  AND close_price IS NOT NULL
+                  AND trade_date >= r.revenue_month
+                  AND trade_date < r.revenue_month + INTERVAL '1 month'
+            ) price ON true
+            LEFT JOIN LATERAL (
+                SELECT close_price::float8 AS close
+                FROM stock_day_all
+                WHERE stock_code = r.stock_code
+                  AND close_price IS NOT NULL
+                  AND trade_date < r.revenue_month
+                  AND trade_date >= r.revenue_month - INTERVAL '1 month'
+                ORDER BY trade_date DESC
+                LIMIT 1
+            ) prev ON true
+            WHERE r.stock_code = $1
+            ORDER BY r.revenue_month DESC
+            LIMIT $2
+        ) t
+        ORDER BY revenue_month
+        "#,
+    )
+    .bind(code)
+    .bind(months)
+    .fetch_all(db)
+    .await?;
+
+    Ok(StockRevenueHistory {
+        stock_code: code.to_string(),
+        company_name,
+        industry,
+        months,
+    })
+}
+
+/// 依成長率篩選某個月份的月營收，附上最新收盤價，依年增率由高到低排序
+pub async fn screen(
+    db: &PgPool,
+    filter: &RevenueScreen,
+    limit: i64,
+) -> Result<Vec<RevenueScreenRow>, AppError> {
+    let rows = sqlx::query_as::<_, RevenueScreenRow>(
+        r#"
+        SELECT r.stock_code, r.company_name, r.industry, r.revenue_month, r.revenue,
+               r.mom_pct::float8 AS mom_pct,
+               r.yoy_pct::float8 AS yoy_pct,
+               r.cumulative_yoy_pct::float8 AS cumulative_yoy_pct,
+               price.trade_date AS last_trade_date,
+               price.close AS last_close
+        FROM monthly_revenue r
+        LEFT JOIN LATERAL (
+            SELECT trade_date, close_price::float8 AS close
+            FROM stock_day_all
+            WHERE stock_code = r.stock_code
+              AND close_price IS NOT NULL
+            ORDER BY trade_date DESC
+            LIMIT 1
+        ) price ON true
+        WHERE r.revenue_month = COALESCE($1::date, (SELECT MAX(revenue_month) FROM monthly_revenue))
+          AND ($2::float8 IS NULL OR r.yoy_pct >= $2)
+          AND ($3::float8 IS NULL OR r.mom_pct >= $3)
+          AND ($4::text IS NULL OR r.industry = $4)
+        ORDER BY r.yoy_pct DESC NULLS LAST, r.stock_code
+        LIMIT $5
+        "#,
+    )
+    .bind(filter.revenue_month)
+    .bind(filter.min_yoy)
+    .bind(filter.min_mom)
+    .bind(&filter.industry)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows)
+}
+
+/// 解析 `YYYY-MM` 格式的月份參數為該月第一天
+pub fn parse_month_param(s: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(&format!("{}-01", s.trim()), "%Y-%m-%d")
+        .map_err(|_| AppError::bad_request(format!("無效的月份: {}（格式為 YYYY-MM）", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(
+        "file://",
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/t187ap05_L.json"
+    );
+
+    #[tokio::test]
+    async fn parses_fixture_through_file_source() {
+        let payload = fetch(&reqwest::Client::new(), FIXTURE).await.unwrap();
+        assert_eq!(payload.status_code, 200);
+        assert_eq!(payload.url, FIXTURE);
+
+        let (rows, skipped) = parse(&payload.body).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(rows.len(), 2);
+
+        let tsmc = &rows[0];
+        assert_eq!(tsmc.stock_code, "2330");
+        assert_eq!(tsmc.company_name, "台積電");
+        assert_eq!(tsmc.industry.as_deref(), Some("半導體業"));
+        assert_eq!(
+            tsmc.revenue_month,
+            NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()
+        );
+        assert_eq!(tsmc.revenue, Some(293_288_329));
+        assert_eq!(tsmc.mom_pct, Some(5.43));
+        assert_eq!(tsmc.yoy_pct, Some(35.91));
+        assert_eq!(tsmc.note, None);
+        assert_eq!(tsmc.published_date, NaiveDate::from_ymd_opt(2025, 2, 10));
+
+        // 來源沒有給增減百分比時由營收自行計算
+        let hon_hai = &rows[1];
+        assert_eq!(hon_hai.stock_code, "2317");
+        assert_eq!(hon_hai.mom_pct, Some(-5.36));
+        assert_eq!(hon_hai.yoy_pct, Some(32.81));
+        assert_eq!(hon_hai.cumulative_yoy_pct, Some(32.8));
+    }
+
+    #[tokio::test]
+    async fn missing_file_source_is_an_error() {
+        let missing = concat!(
+            "file://",
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/missing.json"
+        );
+        assert!(fetch(&reqwest::Client::new(), missing).await.is_err());
+    }
+}
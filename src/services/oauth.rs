@@ -0,0 +1,1632 @@
+// src/services/oauth.rs
+
+use axum::http::{HeaderMap, StatusCode, uri::PathAndQuery};
+use chrono::{DateTime, Duration, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    config::{AuthConfig, OAuthClientConfig},
+    error::AppError,
+    services::{
+        oidc::{self, Discovery},
+        session,
+        users::{self, SignIn, User},
+    },
+    state::AppState,
+    utils::token::{random_token, sha256_base64url},
+};
+
+/// 登入流程暫存（state → PKCE verifier）的有效時間
+const PENDING_LOGIN_TTL_SECS: u64 = 10 * 60;
+/// 把 state 綁定在發起授權的瀏覽器上的 cookie，內容為 state 的雜湊
+const STATE_COOKIE: &str = "oauth_state";
+/// state cookie 只需要送到 `/auth` 底下的回呼
+const STATE_COOKIE_PATH: &str = "/auth";
+const MERGE_COOKIE: &str = "oauth_merge";
+/// merge_token 只送到 `/auth/merge` 與 `/auth/merge/decline`
+const MERGE_COOKIE_PATH: &str = "/auth/merge";
+
+/// 支援的第三方登入提供者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Provider {
+    Google,
+    Discord,
+    /// 以 `OIDC_PROVIDERS` 設定的 OpenID Connect 提供者（名稱即 user_identities.provider）
+    Oidc(String),
+}
+
+/// 解析後的提供者端點與用戶端資訊
+struct ResolvedClient {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    auth_url: String,
+    token_url: String,
+    userinfo_url: Option<String>,
+    scopes: String,
+    /// OIDC 提供者的 discovery 文件，用於驗證 ID token
+    discovery: Option<Discovery>,
+}
+
+impl ResolvedClient {
+    fn from_config(client: &OAuthClientConfig) -> Self {
+        Self {
+            client_id: client.client_id.clone(),
+            client_secret: client.client_secret.clone(),
+            redirect_uri: client.redirect_uri.clone(),
+            auth_url: client.auth_url.clone(),
+            token_url: client.token_url.clone(),
+            userinfo_url: Some(client.userinfo_url.clone()),
+            scopes: client.scopes.clone(),
+            discovery: None,
+        }
+    }
+}
+
+impl Provider {
+    /// 依名稱取得已設定的 OIDC 提供者
+    pub fn oidc(state: &AppState, name: &str) -> Result<Self, AppError> {
+        state
+            .config
+            .auth
+            .oidc
+            .iter()
+            .any(|provider| provider.name == name)
+            .then(|| Self::Oidc(name.to_string()))
+            .ok_or_else(|| AppError::not_found(format!("未設定 OIDC 提供者 {}", name)))
+    }
+
+    /// 依 user_identities.provider 取得提供者；該提供者目前沒有設定時為 `None`
+    pub fn from_name(state: &AppState, name: &str) -> Option<Self> {
+        let auth = &state.config.auth;
+        match name {
+            "google" => auth.google.as_ref().map(|_| Self::Google),
+            "discord" => auth.discord.as_ref().map(|_| Self::Discord),
+            _ => Self::oidc(state, name).ok(),
+        }
+    }
+
+    /// 寫入 user_identities.provider 的名稱
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Google => "google",
+            Self::Discord => "discord",
+            Self::Oidc(name) => name,
+        }
+    }
+
+    async fn resolve(&self, state: &AppState) -> Result<ResolvedClient, AppError> {
+        let auth = &state.config.auth;
+        let client = match self {
+            Self::Google => auth.google.as_ref().map(ResolvedClient::from_config),
+            Self::Discord => auth.discord.as_ref().map(ResolvedClient::from_config),
+            Self::Oidc(name) => match auth.oidc.iter().find(|provider| &provider.name == name) {
+                Some(config) => {
+                    let discovery = oidc::discover(state, config).await?;
+                    Some(ResolvedClient {
+                        client_id: config.client_id.clone(),
+                        client_secret: config.client_secret.clone(),
+                        redirect_uri: config.redirect_uri.clone(),
+                        auth_url: discovery.authorization_endpoint.clone(),
+                        token_url: discovery.token_endpoint.clone(),
+                        userinfo_url: discovery.userinfo_endpoint.clone(),
+                        scopes: config.scopes.clone(),
+                        discovery: Some(discovery),
+                    })
+                }
+                None => None,
+            },
+        };
+        client.ok_or_else(|| AppError::not_found(format!("未啟用 {} 登入", self.name())))
+    }
+
+    /// 提供者特有的授權參數
+    fn extra_auth_params(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            // 要求 refresh token；prompt=consent 確保重新登入時也會再發一次
+            Self::Google => &[("access_type", "offline"), ("prompt", "consent")],
+            Self::Discord | Self::Oidc(_) => &[],
+        }
+    }
+
+    /// 將使用者資料端點的回應轉成共用格式
+    fn parse_profile(&self, value: &Value) -> Option<ProviderProfile> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        match self {
+            // OIDC 標準 claims；部分提供者的 email_verified 是字串
+            Self::Google | Self::Oidc(_) => Some(ProviderProfile {
+                subject: text("sub")?,
+                email: text("email"),
+                email_verified: match value.get("email_verified") {
+                    Some(Value::Bool(verified)) => *verified,
+                    Some(Value::String(verified)) => verified == "true",
+                    _ => false,
+                },
+                display_name: text("name").or_else(|| text("preferred_username")),
+                avatar_url: text("picture"),
+            }),
+            // https://discord.com/developers/docs/resources/user#user-object
+            Self::Discord => {
+                let id = text("id")?;
+                let avatar_url = text("avatar").map(|hash| {
+                    let ext = if hash.starts_with("a_") { "gif" } else { "png" };
+                    format!("https://cdn.discordapp.com/avatars/{}/{}.{}", id, hash, ext)
+                });
+                Some(ProviderProfile {
+                    email: text("email"),
+                    email_verified: value
+                        .get("verified")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                    // global_name 是顯示名稱，未設定時退回帳號名稱
+                    display_name: text("global_name").or_else(|| text("username")),
+                    avatar_url,
+                    subject: id,
+                })
+            }
+        }
+    }
+}
+
+/// 提供者回傳的使用者資料
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    /// 提供者端的使用者 id（寫入 provider_user_id）
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl ProviderProfile {
+    /// 經提供者驗證過的 email
+    pub fn verified_email(&self) -> Option<&str> {
+        self.email.as_deref().filter(|_| self.email_verified)
+    }
+}
+
+/// 以授權碼換得的 token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// 以 refresh token 換發 access token 的結果
+pub enum TokenRefresh {
+    Refreshed(ProviderTokens),
+    /// 提供者回應 `invalid_grant`：授權已被撤銷或 refresh token 失效，需要使用者重新授權
+    Revoked(String),
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    id_token: Option<String>,
+}
+
+/// 發出授權請求到回呼之間暫存在 Valkey 的資料
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    provider: String,
+    code_verifier: String,
+    redirect_to: String,
+    /// OIDC 的 nonce，回呼時與 ID token 內的值比對
+    #[serde(default)]
+    nonce: Option<String>,
+    /// 已登入的使用者要綁定新的登入方式時，記錄該使用者
+    #[serde(default)]
+    link_user_id: Option<Uuid>,
+}
+
+/// 等待使用者決定是否合併時暫存在 Valkey 的資料
+#[derive(Serialize, Deserialize)]
+struct PendingMerge {
+    provider: String,
+    profile: ProviderProfile,
+    tokens: ProviderTokens,
+    /// email 相同的既有使用者
+    target_user_id: Uuid,
+}
+
+/// 提供者導回時的查詢參數
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// 回呼處理完成的結果，`redirect_to` 為登入前指定的導向位置
+pub enum LoginOutcome {
+    /// 登入成功，應建立 session
+    SignedIn { user: User, redirect_to: String },
+    /// 已把登入方式綁定到目前的使用者
+    Linked { redirect_to: String },
+    /// email 屬於既有使用者，需先以既有帳號登入後呼叫合併，或選擇另外建立帳號
+    MergeRequired {
+        merge_token: String,
+        redirect_to: String,
+    },
+}
+
+fn pending_key(state: &str) -> String {
+    format!("oauth:state:{}", state)
+}
+
+fn merge_key(token: &str) -> String {
+    format!("oauth:merge:{}", token)
+}
+
+/// 只接受站內的相對路徑，避免被當成開放式重新導向
+///
+/// 瀏覽器會忽略路徑中的 tab、換行，`/\t/evil.com` 會被當成 `//evil.com`；
+/// 含控制字元或空白的值也無法放進 `Location` 標頭，一律改用預設位置。
+fn safe_redirect(redirect: Option<&str>, fallback: &str) -> String {
+    match redirect {
+        Some(path)
+            if path.starts_with('/')
+                && !path.starts_with("//")
+                && !path.contains('\\')
+                && !path.chars().any(|c| c.is_control() || c.is_whitespace())
+                && path.parse::<PathAndQuery>().is_ok() =>
+        {
+            path.to_string()
+        }
+        _ => fallback.to_string(),
+    }
+}
+
+/// 授權網址與要設定在瀏覽器上的 state cookie
+pub struct AuthorizationRequest {
+    pub url: String,
+    /// `Set-Cookie` 值，回呼時必須帶回相同的 state 才能完成登入
+    pub state_cookie: String,
+}
+
+/// 清除 state cookie 的 `Set-Cookie` 值
+pub fn clear_state_cookie(config: &AuthConfig) -> String {
+    session::build_cookie(config, STATE_COOKIE, STATE_COOKIE_PATH, "", 0)
+}
+
+/// 從 Cookie 標頭取出 state cookie
+pub fn state_from_cookie(headers: &HeaderMap) -> Option<String> {
+    session::cookie_from_headers(headers, STATE_COOKIE)
+}
+
+/// 帶著 merge_token 的 `Set-Cookie` 值，存活時間與等待合併的資料相同
+pub fn merge_cookie(config: &AuthConfig, merge_token: &str) -> String {
+    session::build_cookie(
+        config,
+        MERGE_COOKIE,
+        MERGE_COOKIE_PATH,
+        merge_token,
+        PENDING_LOGIN_TTL_SECS,
+    )
+}
+
+/// 清除 merge cookie 的 `Set-Cookie` 值
+pub fn clear_merge_cookie(config: &AuthConfig) -> String {
+    session::build_cookie(config, MERGE_COOKIE, MERGE_COOKIE_PATH, "", 0)
+}
+
+/// 從 Cookie 標頭取出 merge_token
+pub fn merge_token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    session::cookie_from_headers(headers, MERGE_COOKIE)
+}
+
+/// 產生授權網址，並把 state 與 PKCE verifier 存入 Valkey
+///
+/// state 的雜湊另外放在短效的 HttpOnly cookie，回呼時比對，避免攻擊者把自己的授權碼塞給受害者的瀏覽器（login CSRF）。
+pub async fn authorization_url(
+    state: &AppState,
+    provider: &Provider,
+    redirect: Option<&str>,
+    link_user_id: Option<Uuid>,
+) -> Result<AuthorizationRequest, AppError> {
+    let client = provider.resolve(state).await?;
+
+    let csrf_state = random_token(32);
+    let code_verifier = random_token(48);
+    let code_challenge = sha256_base64url(&code_verifier);
+    let nonce = client.discovery.as_ref().map(|_| random_token(32));
+    let pending = PendingLogin {
+        provider: provider.name().to_string(),
+        code_verifier,
+        redirect_to: safe_redirect(redirect, &state.config.auth.post_login_redirect),
+        nonce: nonce.clone(),
+        link_user_id,
+    };
+    let payload = serde_json::to_string(&pending)
+        .map_err(|e| AppError::internal_error(format!("序列化登入狀態失敗: {}", e)))?;
+
+    let mut conn = state.redis.clone();
+    conn.set_ex::<_, _, ()>(pending_key(&csrf_state), payload, PENDING_LOGIN_TTL_SECS)
+        .await?;
+
+    let mut params = vec![
+        ("response_type", "code"),
+        ("client_id", client.client_id.as_str()),
+        ("redirect_uri", client.redirect_uri.as_str()),
+        ("scope", client.scopes.as_str()),
+        ("state", csrf_state.as_str()),
+        ("code_challenge", code_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    if let Some(nonce) = &nonce {
+        params.push(("nonce", nonce.as_str()));
+    }
+    params.extend_from_slice(provider.extra_auth_params());
+
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let separator = if client.auth_url.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+
+    Ok(AuthorizationRequest {
+        url: format!("{}{}{}", client.auth_url, separator, query),
+        state_cookie: session::build_cookie(
+            &state.config.auth,
+            STATE_COOKIE,
+            STATE_COOKIE_PATH,
+            &sha256_base64url(&csrf_state),
+            PENDING_LOGIN_TTL_SECS,
+        ),
+    })
+}
+
+/// 處理提供者的回呼：驗證 state、以授權碼換 token、取得使用者資料並寫入資料庫
+///
+/// OIDC 提供者以驗證過的 ID token 取得使用者資料，其他提供者則呼叫使用者資料端點。
+/// `state_cookie` 為回呼請求帶來的 state cookie，必須與查詢參數的 state 相符；
+/// `current_user_id` 為回呼請求目前登入的使用者，綁定流程必須與發起綁定的使用者相同。
+pub async fn complete_login(
+    state: &AppState,
+    provider: &Provider,
+    params: CallbackParams,
+    state_cookie: Option<&str>,
+    current_user_id: Option<Uuid>,
+) -> Result<LoginOutcome, AppError> {
+    if let Some(error) = params.error {
+        let detail = params.error_description.unwrap_or_default();
+        return Err(AppError::bad_request(format!(
+            "{} 登入被拒絕: {} {}",
+            provider.name(),
+            error,
+            detail
+        )));
+    }
+    let (Some(code), Some(csrf_state)) = (params.code, params.state) else {
+        return Err(AppError::bad_request("缺少 code 或 state 參數"));
+    };
+    if state_cookie != Some(sha256_base64url(&csrf_state).as_str()) {
+        return Err(AppError::bad_request(
+            "登入狀態與此瀏覽器不符，請從同一個瀏覽器重新登入",
+        ));
+    }
+
+    // GETDEL 讓同一個 state 只能使用一次
+    let mut conn = state.redis.clone();
+    let payload: Option<String> = conn.get_del(pending_key(&csrf_state)).await?;
+    let pending = payload
+        .and_then(|payload| serde_json::from_str::<PendingLogin>(&payload).ok())
+        .filter(|pending| pending.provider == provider.name())
+        .ok_or_else(|| AppError::bad_request("登入狀態無效或已過期，請重新登入"))?;
+
+    let client = provider.resolve(state).await?;
+    let (tokens, id_token) = exchange_code(state, &client, &code, &pending.code_verifier).await?;
+
+    let profile = match &client.discovery {
+        Some(discovery) => {
+            let id_token = id_token
+                .ok_or_else(|| AppError::new(StatusCode::BAD_GATEWAY, "提供者沒有回傳 ID token"))?;
+            let nonce = pending
+                .nonce
+                .as_deref()
+                .ok_or_else(|| AppError::bad_request("登入狀態缺少 nonce，請重新登入"))?;
+            let claims =
+                oidc::verify_id_token(state, discovery, &client.client_id, &id_token, nonce)
+                    .await?;
+            provider
+                .parse_profile(&claims)
+                .ok_or_else(|| AppError::unauthorized("ID token 缺少 sub"))?
+        }
+        None => fetch_profile(state, provider, &client, &tokens.access_token).await?,
+    };
+
+    let redirect_to = pending.redirect_to;
+
+    if let Some(link_user_id) = pending.link_user_id {
+        if current_user_id != Some(link_user_id) {
+            return Err(AppError::forbidden("綁定流程與目前登入的帳號不符"));
+        }
+        users::link_identity(
+            &state.db,
+            &state.config.auth.token_encryption,
+            link_user_id,
+            provider.name(),
+            &profile,
+            &tokens,
+        )
+        .await?;
+        tracing::info!("🔗 {} 綁定: user {}", provider.name(), link_user_id);
+        return Ok(LoginOutcome::Linked { redirect_to });
+    }
+
+    match users::sign_in(
+        &state.db,
+        &state.config.auth.token_encryption,
+        provider.name(),
+        &profile,
+        &tokens,
+    )
+    .await?
+    {
+        SignIn::User(user) => {
+            tracing::info!("🔑 {} 登入: user {}", provider.name(), user.id);
+            Ok(LoginOutcome::SignedIn { user, redirect_to })
+        }
+        SignIn::EmailMatch(target_user_id) => {
+            let merge = PendingMerge {
+                provider: provider.name().to_string(),
+                profile,
+                tokens,
+                target_user_id,
+            };
+            let payload = serde_json::to_string(&merge)
+                .map_err(|e| AppError::internal_error(format!("序列化合併狀態失敗: {}", e)))?;
+            let merge_token = random_token(32);
+            conn.set_ex::<_, _, ()>(merge_key(&merge_token), payload, PENDING_LOGIN_TTL_SECS)
+                .await?;
+
+            Ok(LoginOutcome::MergeRequired {
+                merge_token,
+                redirect_to,
+            })
+        }
+    }
+}
+
+/// 已取出（並從 Valkey 刪除）的等待合併資料
+struct ClaimedMerge {
+    merge: PendingMerge,
+    payload: String,
+    /// 取出時剩餘的存活秒數
+    ttl_secs: i64,
+}
+
+impl ClaimedMerge {
+    /// 合併沒有完成時放回 Valkey，讓使用者可以用同一個 merge_token 重試
+    async fn restore(&self, state: &AppState, merge_token: &str) {
+        if self.ttl_secs <= 0 {
+            return;
+        }
+        let mut conn = state.redis.clone();
+        if let Err(e) = conn
+            .set_ex::<_, _, ()>(merge_key(merge_token), &self.payload, self.ttl_secs as u64)
+            .await
+        {
+            tracing::warn!("放回合併請求失敗: {}", e);
+        }
+    }
+}
+
+/// 以 GETDEL 取出等待合併的資料，同一個 merge_token 不會被兩個請求同時使用
+async fn take_merge(state: &AppState, merge_token: &str) -> Result<ClaimedMerge, AppError> {
+    let key = merge_key(merge_token);
+    let mut conn = state.redis.clone();
+    let (ttl_secs, payload): (i64, Option<String>) = redis::pipe()
+        .atomic()
+        .ttl(&key)
+        .get_del(&key)
+        .query_async(&mut conn)
+        .await?;
+    let invalid = || AppError::bad_request("合併請求無效或已過期，請重新登入");
+    let payload = payload.ok_or_else(invalid)?;
+    let merge = serde_json::from_str(&payload).map_err(|_| invalid())?;
+
+    Ok(ClaimedMerge {
+        merge,
+        payload,
+        ttl_secs,
+    })
+}
+
+/// 把等待合併的登入方式綁定到目前使用者；必須以 email 相同的既有帳號登入才能合併
+///
+/// 綁定失敗或登入的帳號不符時會放回 merge_token，換成正確的帳號後可以重試。
+pub async fn complete_merge(
+    state: &AppState,
+    merge_token: &str,
+    current_user_id: Uuid,
+) -> Result<(), AppError> {
+    let claimed = take_merge(state, merge_token).await?;
+    let merge = &claimed.merge;
+    if merge.target_user_id != current_user_id {
+        claimed.restore(state, merge_token).await;
+        return Err(AppError::forbidden("請先以 email 相同的既有帳號登入再合併"));
+    }
+
+    if let Err(e) = users::link_identity(
+        &state.db,
+        &state.config.auth.token_encryption,
+        current_user_id,
+        &merge.provider,
+        &merge.profile,
+        &merge.tokens,
+    )
+    .await
+    {
+        claimed.restore(state, merge_token).await;
+        return Err(e);
+    }
+    tracing::info!("🔗 {} 合併: user {}", merge.provider, current_user_id);
+    Ok(())
+}
+
+/// 不合併，以等待合併的登入方式另外建立新使用者；建立失敗時放回 merge_token
+pub async fn decline_merge(state: &AppState, merge_token: &str) -> Result<User, AppError> {
+    let claimed = take_merge(state, merge_token).await?;
+    let merge = &claimed.merge;
+    let user = match users::create_with_identity(
+        &state.db,
+        &state.config.auth.token_encryption,
+        &merge.provider,
+        &merge.profile,
+        &merge.tokens,
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            claimed.restore(state, merge_token).await;
+            return Err(e);
+        }
+    };
+    tracing::info!("🔑 {} 登入（不合併）: user {}", merge.provider, user.id);
+    Ok(user)
+}
+
+async fn exchange_code(
+    state: &AppState,
+    client: &ResolvedClient,
+    code: &str,
+    code_verifier: &str,
+) -> Result<(ProviderTokens, Option<String>), AppError> {
+    let res = state
+        .http_client
+        .post(&client.token_url)
+        .header("Accept", "application/json")
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", client.redirect_uri.as_str()),
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("code_verifier", code_verifier),
+        ])
+        .send()
+        .await?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().await.unwrap_or_default();
+        tracing::warn!("授權碼交換失敗 {}: {}", status, body);
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            "向登入提供者換取 token 失敗",
+        ));
+    }
+
+    let token = res.json::<TokenResponse>().await?;
+    let tokens = ProviderTokens {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token
+            .expires_in
+            .map(|secs| Utc::now() + Duration::seconds(secs)),
+    };
+    Ok((tokens, token.id_token))
+}
+
+/// 以 refresh token 向提供者換發新的 access token
+///
+/// 提供者沒有回傳新的 refresh token 時，`refresh_token` 為 `None`，應沿用原本的。
+/// 網路錯誤或提供者暫時失敗回傳 `Err`，只有明確的 `invalid_grant` 才視為授權撤銷。
+pub async fn refresh_access_token(
+    state: &AppState,
+    provider: &Provider,
+    refresh_token: &str,
+) -> Result<TokenRefresh, AppError> {
+    let client = provider.resolve(state).await?;
+    let res = state
+        .http_client
+        .post(&client.token_url)
+        .header("Accept", "application/json")
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+        ])
+        .send()
+        .await?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().await.unwrap_or_default();
+        if let Ok(error) = serde_json::from_str::<TokenErrorResponse>(&body)
+            && error.error == "invalid_grant"
+        {
+            return Ok(TokenRefresh::Revoked(
+                error.error_description.unwrap_or(error.error),
+            ));
+        }
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!(
+                "{} 換發 token 失敗 {}: {}",
+                provider.name(),
+                status,
+                body.chars().take(200).collect::<String>()
+            ),
+        ));
+    }
+
+    let token = res.json::<TokenResponse>().await?;
+    Ok(TokenRefresh::Refreshed(ProviderTokens {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token
+            .expires_in
+            .map(|secs| Utc::now() + Duration::seconds(secs)),
+    }))
+}
+
+async fn fetch_profile(
+    state: &AppState,
+    provider: &Provider,
+    client: &ResolvedClient,
+    access_token: &str,
+) -> Result<ProviderProfile, AppError> {
+    let Some(userinfo_url) = &client.userinfo_url else {
+        return Err(AppError::internal_error(format!(
+            "{} 沒有使用者資料端點",
+            provider.name()
+        )));
+    };
+    let res = state
+        .http_client
+        .get(userinfo_url)
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+
+    let status = res.status();
+    if !status.is_success() {
+        tracing::warn!("取得 {} 使用者資料失敗: {}", provider.name(), status);
+        return Err(AppError::new(StatusCode::BAD_GATEWAY, "取得使用者資料失敗"));
+    }
+
+    let value = res.json::<Value>().await?;
+    provider
+        .parse_profile(&value)
+        .ok_or_else(|| AppError::new(StatusCode::BAD_GATEWAY, "使用者資料缺少必要欄位"))
+}
+
+/// 以本機的模擬 IdP 測試授權碼流程
+///
+/// 不會用到 Postgres 與 Valkey 的路徑（回呼檢查、token 交換）直接執行；走完整個流程的測試標記為 ignore，
+/// 需要 Postgres（已套用 migrations）與 Valkey，以
+/// `TEST_DATABASE_URL=... TEST_VALKEY_URL=... cargo test -- --ignored` 執行。
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        AppConfig, AuthConfig, DataQualityConfig, JwtConfig, MonthlyRevenueConfig,
+        OidcProviderConfig, ProviderTokenRefreshConfig, TokenEncryptionConfig,
+    };
+    use axum::{
+        Form, Json, Router,
+        extract::{Query, State},
+        response::{IntoResponse, Redirect, Response},
+        routing::{get, post},
+    };
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+        time::Duration as StdDuration,
+    };
+
+    const CLIENT_ID: &str = "test-client";
+    const SIGNING_KEY: &[u8] = include_bytes!("../../fixtures/oidc_test_key.pem");
+    const JWKS: &str = include_str!("../../fixtures/oidc_test_jwks.json");
+
+    /// 模擬 IdP 的狀態
+    #[derive(Default)]
+    struct MockIdp {
+        issuer: String,
+        /// userinfo 端點與 ID token 回傳的使用者資料
+        profile: Value,
+        /// 授權碼對應的 code_challenge 與 nonce
+        codes: HashMap<String, (String, Option<String>)>,
+        /// token 端點收到的 code_verifier
+        verifiers: Vec<String>,
+        /// 已核發的 access token
+        issued: Vec<String>,
+        /// 仍有效的 refresh token，換發時會輪替
+        refresh_tokens: HashSet<String>,
+    }
+
+    type SharedIdp = Arc<Mutex<MockIdp>>;
+
+    #[derive(Deserialize)]
+    struct AuthorizeQuery {
+        redirect_uri: String,
+        state: String,
+        code_challenge: String,
+        code_challenge_method: String,
+        nonce: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct TokenForm {
+        grant_type: String,
+        code: Option<String>,
+        code_verifier: Option<String>,
+        refresh_token: Option<String>,
+        client_id: String,
+    }
+
+    fn invalid_grant() -> Response {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "invalid_grant",
+                "error_description": "Token has been expired or revoked.",
+            })),
+        )
+            .into_response()
+    }
+
+    /// 核發一組新的 access token 與 refresh token
+    fn issue_tokens(idp: &mut MockIdp) -> (String, String) {
+        let access_token = format!("access-{}", random_token(8));
+        let refresh_token = format!("refresh-{}", random_token(8));
+        idp.issued.push(access_token.clone());
+        idp.refresh_tokens.insert(refresh_token.clone());
+        (access_token, refresh_token)
+    }
+
+    async fn authorize(
+        State(idp): State<SharedIdp>,
+        Query(query): Query<AuthorizeQuery>,
+    ) -> Redirect {
+        assert_eq!(query.code_challenge_method, "S256");
+        let code = random_token(16);
+        idp.lock()
+            .unwrap()
+            .codes
+            .insert(code.clone(), (query.code_challenge, query.nonce));
+        Redirect::to(&format!(
+            "{}?code={}&state={}",
+            query.redirect_uri, code, query.state
+        ))
+    }
+
+    async fn token(State(idp): State<SharedIdp>, Form(form): Form<TokenForm>) -> Response {
+        let mut idp = idp.lock().unwrap();
+        assert_eq!(form.client_id, CLIENT_ID);
+        if form.grant_type == "refresh_token" {
+            // refresh token 只能使用一次，換發時輪替
+            if !idp.refresh_tokens.remove(&form.refresh_token.unwrap()) {
+                return invalid_grant();
+            }
+            let (access_token, refresh_token) = issue_tokens(&mut idp);
+            return Json(json!({
+                "access_token": access_token,
+                "refresh_token": refresh_token,
+                "expires_in": 3600,
+            }))
+            .into_response();
+        }
+
+        assert_eq!(form.grant_type, "authorization_code");
+        // 授權碼只能使用一次，且 code_verifier 必須符合授權請求的 code_challenge
+        let Some((challenge, nonce)) = idp.codes.remove(&form.code.unwrap()) else {
+            return invalid_grant();
+        };
+        let code_verifier = form.code_verifier.unwrap();
+        idp.verifiers.push(code_verifier.clone());
+        if sha256_base64url(&code_verifier) != challenge {
+            return invalid_grant();
+        }
+
+        let (access_token, refresh_token) = issue_tokens(&mut idp);
+        let id_token = nonce.map(|nonce| {
+            let mut claims = idp.profile.clone();
+            claims["iss"] = json!(idp.issuer);
+            claims["aud"] = json!(CLIENT_ID);
+            claims["exp"] = json!(Utc::now().timestamp() + 300);
+            claims["nonce"] = json!(nonce);
+            let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+            header.kid = Some("test-key".to_string());
+            jsonwebtoken::encode(
+                &header,
+                &claims,
+                &EncodingKey::from_rsa_pem(SIGNING_KEY).unwrap(),
+            )
+            .unwrap()
+        });
+
+        Json(json!({
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "expires_in": 3600,
+            "id_token": id_token,
+        }))
+        .into_response()
+    }
+
+    async fn userinfo(State(idp): State<SharedIdp>) -> Json<Value> {
+        Json(idp.lock().unwrap().profile.clone())
+    }
+
+    async fn discovery(State(idp): State<SharedIdp>) -> Json<Value> {
+        let issuer = idp.lock().unwrap().issuer.clone();
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "userinfo_endpoint": format!("{}/userinfo", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+        }))
+    }
+
+    async fn jwks() -> Json<Value> {
+        Json(serde_json::from_str(JWKS).unwrap())
+    }
+
+    /// 在隨機埠啟動模擬 IdP
+    async fn spawn_idp() -> SharedIdp {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let idp = Arc::new(Mutex::new(MockIdp {
+            issuer,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .route("/userinfo", get(userinfo))
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        idp
+    }
+
+    /// Google、Discord 的端點與 OIDC 提供者 `mock` 的 issuer 都指向模擬 IdP
+    fn test_config(idp: &SharedIdp, database_url: String, valkey_url: String) -> AppConfig {
+        let issuer = idp.lock().unwrap().issuer.clone();
+        let client = |name: &str| OAuthClientConfig {
+            client_id: CLIENT_ID.to_string(),
+            client_secret: "test-secret".to_string(),
+            redirect_uri: format!("http://localhost/auth/{}/callback", name),
+            auth_url: format!("{}/authorize", issuer),
+            token_url: format!("{}/token", issuer),
+            userinfo_url: format!("{}/userinfo", issuer),
+            scopes: "openid email profile".to_string(),
+        };
+        AppConfig {
+            port: 0,
+            host: "127.0.0.1".to_string(),
+            request_timeout: StdDuration::from_secs(5),
+            database_url,
+            db_max_connections: 2,
+            valkey_url,
+            data_quality: DataQualityConfig::default(),
+            monthly_revenue: MonthlyRevenueConfig::default(),
+            bulk_insert_threshold: 5000,
+            auth: AuthConfig {
+                google: Some(client("google")),
+                discord: Some(client("discord")),
+                oidc: vec![OidcProviderConfig {
+                    name: "mock".to_string(),
+                    issuer: issuer.clone(),
+                    client_id: CLIENT_ID.to_string(),
+                    client_secret: "test-secret".to_string(),
+                    redirect_uri: "http://localhost/auth/oidc/mock/callback".to_string(),
+                    scopes: "openid email profile".to_string(),
+                }],
+                post_login_redirect: "/".to_string(),
+                session_cookie: "sid".to_string(),
+                session_idle_timeout: StdDuration::from_secs(60 * 60),
+                session_max_age: StdDuration::from_secs(24 * 60 * 60),
+                cookie_secure: false,
+                trusted_proxies: Vec::new(),
+                jwt: JwtConfig::default(),
+                token_encryption: TokenEncryptionConfig {
+                    keys: Vec::new(),
+                    active_kid: None,
+                },
+                token_refresh: ProviderTokenRefreshConfig::default(),
+            },
+        }
+    }
+
+    /// 連上 `TEST_DATABASE_URL` 與 `TEST_VALKEY_URL` 的狀態
+    async fn test_state(idp: &SharedIdp) -> AppState {
+        let database_url = std::env::var("TEST_DATABASE_URL").expect("Not Found TEST_DATABASE_URL");
+        let valkey_url = std::env::var("TEST_VALKEY_URL").expect("Not Found TEST_VALKEY_URL");
+        let config = test_config(idp, database_url, valkey_url);
+
+        AppState {
+            db: sqlx::PgPool::connect(&config.database_url).await.unwrap(),
+            http_client: reqwest::Client::new(),
+            redis: redis::Client::open(config.valkey_url.as_str())
+                .unwrap()
+                .get_connection_manager()
+                .await
+                .unwrap(),
+            config,
+        }
+    }
+
+    /// 不連線的狀態，只能測試不會用到 Postgres 與 Valkey 的路徑
+    fn offline_state(idp: &SharedIdp) -> AppState {
+        let config = test_config(
+            idp,
+            "postgres://127.0.0.1:1/unused".to_string(),
+            "redis://127.0.0.1:1".to_string(),
+        );
+
+        AppState {
+            db: sqlx::PgPool::connect_lazy(&config.database_url).unwrap(),
+            http_client: reqwest::Client::new(),
+            redis: redis::aio::ConnectionManager::new_lazy_with_config(
+                redis::Client::open(config.valkey_url.as_str()).unwrap(),
+                Default::default(),
+            )
+            .unwrap(),
+            config,
+        }
+    }
+
+    /// 直接向模擬 IdP 取得授權碼
+    async fn issue_code(idp: &SharedIdp, code_challenge: &str) -> String {
+        let issuer = idp.lock().unwrap().issuer.clone();
+        let no_redirect = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let res = no_redirect
+            .get(format!("{}/authorize", issuer))
+            .query(&[
+                ("redirect_uri", "http://localhost/callback"),
+                ("state", "state"),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ])
+            .send()
+            .await
+            .unwrap();
+        let location = res.headers()[reqwest::header::LOCATION].to_str().unwrap();
+        reqwest::Url::parse(location)
+            .unwrap()
+            .query_pairs()
+            .find(|(name, _)| name == "code")
+            .map(|(_, code)| code.into_owned())
+            .unwrap()
+    }
+
+    /// 瀏覽器端走完授權頁面後拿到的東西
+    struct Authorized {
+        code: String,
+        state: String,
+        /// state cookie 的值
+        cookie: String,
+        code_challenge: String,
+    }
+
+    impl Authorized {
+        fn params(&self) -> CallbackParams {
+            CallbackParams {
+                code: Some(self.code.clone()),
+                state: Some(self.state.clone()),
+                error: None,
+                error_description: None,
+            }
+        }
+    }
+
+    /// 產生授權網址並交給模擬 IdP，取回導回時的授權碼與 state
+    async fn sign_in_at_idp(state: &AppState, provider: &Provider) -> Authorized {
+        let request = authorization_url(state, provider, Some("/after"), None)
+            .await
+            .unwrap();
+        let cookie = request
+            .state_cookie
+            .split(';')
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .map(|(_, value)| value.to_string())
+            .unwrap();
+
+        let url = reqwest::Url::parse(&request.url).unwrap();
+        let query = |url: &reqwest::Url, key: &str| {
+            url.query_pairs()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+        let no_redirect = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let res = no_redirect.get(url.clone()).send().await.unwrap();
+        let location = res.headers()[reqwest::header::LOCATION].to_str().unwrap();
+        let callback = reqwest::Url::parse(location).unwrap();
+
+        Authorized {
+            code: query(&callback, "code"),
+            state: query(&callback, "state"),
+            cookie,
+            code_challenge: query(&url, "code_challenge"),
+        }
+    }
+
+    fn set_profile(idp: &SharedIdp, profile: Value) {
+        idp.lock().unwrap().profile = profile;
+    }
+
+    fn signed_in(outcome: LoginOutcome) -> User {
+        match outcome {
+            LoginOutcome::SignedIn { user, redirect_to } => {
+                assert_eq!(redirect_to, "/after");
+                user
+            }
+            _ => panic!("預期登入成功"),
+        }
+    }
+
+    async fn delete_user(state: &AppState, user_id: Uuid) {
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn state_is_single_use_and_pkce_verifier_is_forwarded() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let subject = Uuid::new_v4().to_string();
+        set_profile(&idp, json!({ "sub": subject, "name": "Tester" }));
+
+        let authorized = sign_in_at_idp(&state, &Provider::Google).await;
+        let user = signed_in(
+            complete_login(
+                &state,
+                &Provider::Google,
+                authorized.params(),
+                Some(&authorized.cookie),
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+
+        // token 端點收到的 verifier 正是授權請求中 code_challenge 的原文
+        let verifiers = idp.lock().unwrap().verifiers.clone();
+        assert_eq!(verifiers.len(), 1);
+        assert_eq!(sha256_base64url(&verifiers[0]), authorized.code_challenge);
+
+        // 重送同一個回呼：state 已被使用
+        let err = complete_login(
+            &state,
+            &Provider::Google,
+            authorized.params(),
+            Some(&authorized.cookie),
+            None,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        assert_eq!(idp.lock().unwrap().verifiers.len(), 1);
+
+        delete_user(&state, user.id).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn callback_requires_state_cookie_from_same_browser() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        set_profile(&idp, json!({ "sub": Uuid::new_v4().to_string() }));
+
+        let authorized = sign_in_at_idp(&state, &Provider::Google).await;
+        for cookie in [None, Some("someone-else")] {
+            let err = complete_login(&state, &Provider::Google, authorized.params(), cookie, None)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        }
+        assert!(idp.lock().unwrap().verifiers.is_empty());
+
+        // 不符的回呼不會消耗 state，原本的瀏覽器仍可完成登入
+        let user = signed_in(
+            complete_login(
+                &state,
+                &Provider::Google,
+                authorized.params(),
+                Some(&authorized.cookie),
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+        delete_user(&state, user.id).await;
+    }
+
+    #[test]
+    fn safe_redirect_only_accepts_plain_relative_paths() {
+        for path in ["/", "/after", "/stocks/2330?range=1y#chart", "/a%20b"] {
+            assert_eq!(safe_redirect(Some(path), "/"), path);
+        }
+        for path in [
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\t/evil.com",
+            "/\n",
+            "/\r\nSet-Cookie: a=b",
+            "/ /evil.com",
+            "/\u{2028}",
+            "after",
+            "",
+        ] {
+            assert_eq!(safe_redirect(Some(path), "/home"), "/home", "{:?}", path);
+        }
+        assert_eq!(safe_redirect(None, "/home"), "/home");
+    }
+
+    #[test]
+    fn merge_token_cookie_is_scoped_to_merge_routes() {
+        let config = AuthConfig {
+            cookie_secure: true,
+            ..test_config(&Arc::default(), String::new(), String::new()).auth
+        };
+        let cookie = merge_cookie(&config, "token-123");
+        assert_eq!(
+            cookie,
+            "oauth_merge=token-123; Path=/auth/merge; HttpOnly; SameSite=Lax; Max-Age=600; Secure"
+        );
+        assert!(clear_merge_cookie(&config).starts_with("oauth_merge=; Path=/auth/merge;"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::COOKIE,
+            "sid=abc; oauth_merge=token-123".parse().unwrap(),
+        );
+        assert_eq!(
+            merge_token_from_cookie(&headers).as_deref(),
+            Some("token-123")
+        );
+    }
+
+    #[test]
+    fn unverified_email_is_not_trusted() {
+        let google = |verified: Value| {
+            Provider::Google
+                .parse_profile(
+                    &json!({ "sub": "1", "email": "a@example.test", "email_verified": verified }),
+                )
+                .unwrap()
+        };
+        assert_eq!(google(json!(true)).verified_email(), Some("a@example.test"));
+        assert_eq!(
+            google(json!("true")).verified_email(),
+            Some("a@example.test")
+        );
+        assert_eq!(google(json!(false)).verified_email(), None);
+        assert_eq!(google(json!("false")).verified_email(), None);
+        assert_eq!(google(Value::Null).verified_email(), None);
+
+        let discord = Provider::Discord
+            .parse_profile(&json!({ "id": "2", "email": "b@example.test", "verified": false }))
+            .unwrap();
+        assert_eq!(discord.email.as_deref(), Some("b@example.test"));
+        assert_eq!(discord.verified_email(), None);
+    }
+
+    #[tokio::test]
+    async fn provider_error_callback_is_rejected() {
+        let idp = spawn_idp().await;
+        let state = offline_state(&idp);
+
+        let params = CallbackParams {
+            code: None,
+            state: Some("state".to_string()),
+            error: Some("access_denied".to_string()),
+            error_description: Some("使用者取消".to_string()),
+        };
+        let err = complete_login(&state, &Provider::Discord, params, None, None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        assert!(err.message.contains("access_denied"));
+        assert!(idp.lock().unwrap().verifiers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn callback_without_matching_state_cookie_is_rejected() {
+        let idp = spawn_idp().await;
+        let state = offline_state(&idp);
+
+        // 比對在讀取 Valkey 之前；cookie 存的是 state 的雜湊，直接帶 state 原文也不行
+        for cookie in [None, Some("someone-else"), Some("state")] {
+            let params = CallbackParams {
+                code: Some("code".to_string()),
+                state: Some("state".to_string()),
+                error: None,
+                error_description: None,
+            };
+            let err = complete_login(&state, &Provider::Google, params, cookie, None)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        }
+        assert!(idp.lock().unwrap().verifiers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn token_exchange_forwards_pkce_verifier() {
+        let idp = spawn_idp().await;
+        let state = offline_state(&idp);
+        let client = ResolvedClient::from_config(state.config.auth.google.as_ref().unwrap());
+
+        let verifier = random_token(48);
+        let code = issue_code(&idp, &sha256_base64url(&verifier)).await;
+        let (tokens, id_token) = exchange_code(&state, &client, &code, &verifier)
+            .await
+            .unwrap();
+        assert_eq!(idp.lock().unwrap().verifiers, vec![verifier.clone()]);
+        assert_eq!(
+            idp.lock().unwrap().issued.last(),
+            Some(&tokens.access_token)
+        );
+        assert!(tokens.refresh_token.is_some());
+        assert!(tokens.expires_at.is_some());
+        assert_eq!(id_token, None);
+
+        // 授權碼只能換一次；verifier 不符時 IdP 拒絕
+        let err = exchange_code(&state, &client, &code, &verifier)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_GATEWAY);
+        let code = issue_code(&idp, &sha256_base64url(&verifier)).await;
+        let err = exchange_code(&state, &client, &code, "wrong-verifier")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_token_and_reports_revoked_grant() {
+        let idp = spawn_idp().await;
+        let state = offline_state(&idp);
+        idp.lock()
+            .unwrap()
+            .refresh_tokens
+            .insert("refresh-old".to_string());
+
+        let tokens = match refresh_access_token(&state, &Provider::Google, "refresh-old")
+            .await
+            .unwrap()
+        {
+            TokenRefresh::Refreshed(tokens) => tokens,
+            TokenRefresh::Revoked(reason) => panic!("預期換發成功: {}", reason),
+        };
+        assert_eq!(
+            idp.lock().unwrap().issued.last(),
+            Some(&tokens.access_token)
+        );
+        let rotated = tokens.refresh_token.unwrap();
+        assert_ne!(rotated, "refresh-old");
+        assert!(tokens.expires_at.is_some());
+
+        // 已輪替掉的 refresh token 被提供者以 invalid_grant 拒絕，視為授權已撤銷
+        match refresh_access_token(&state, &Provider::Google, "refresh-old")
+            .await
+            .unwrap()
+        {
+            TokenRefresh::Revoked(reason) => {
+                assert_eq!(reason, "Token has been expired or revoked.")
+            }
+            TokenRefresh::Refreshed(_) => panic!("預期授權已撤銷"),
+        }
+        assert!(matches!(
+            refresh_access_token(&state, &Provider::Google, &rotated).await,
+            Ok(TokenRefresh::Refreshed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refresh_failure_other_than_invalid_grant_is_an_error() {
+        let idp = spawn_idp().await;
+        let mut state = offline_state(&idp);
+        // token 端點不存在：不是撤銷，不應要求使用者重新授權
+        let google = state.config.auth.google.as_mut().unwrap();
+        google.token_url = format!("{}/missing", google.token_url);
+
+        let err = refresh_access_token(&state, &Provider::Google, "refresh-any")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn background_refresh_saves_rotated_token_and_flags_revoked_identity() {
+        use crate::services::provider_token_refresh::refresh_expiring;
+
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let subject = Uuid::new_v4().to_string();
+        set_profile(&idp, json!({ "sub": subject }));
+
+        let authorized = sign_in_at_idp(&state, &Provider::Google).await;
+        let user = signed_in(
+            complete_login(
+                &state,
+                &Provider::Google,
+                authorized.params(),
+                Some(&authorized.cookie),
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+
+        let expire_now = || {
+            sqlx::query(
+                r#"
+                UPDATE user_identities
+                SET expires_at = NOW() - INTERVAL '1 minute', last_refresh_attempt_at = NULL
+                WHERE provider = 'google' AND provider_user_id = $1
+                "#,
+            )
+            .bind(&subject)
+        };
+        let load = || {
+            sqlx::query_as::<_, (Option<String>, i32, bool)>(
+                r#"
+                SELECT refresh_token, refresh_failures, needs_reconsent
+                FROM user_identities
+                WHERE provider = 'google' AND provider_user_id = $1
+                "#,
+            )
+            .bind(&subject)
+        };
+
+        // 換發成功：輪替後的 refresh token 寫回資料庫
+        expire_now().execute(&state.db).await.unwrap();
+        refresh_expiring(&state).await.unwrap();
+        let (refresh_token, failures, needs_reconsent) = load().fetch_one(&state.db).await.unwrap();
+        let refresh_token = refresh_token.unwrap();
+        assert!(idp.lock().unwrap().refresh_tokens.contains(&refresh_token));
+        assert_eq!((failures, needs_reconsent), (0, false));
+
+        // 使用者在提供者端撤銷授權
+        idp.lock().unwrap().refresh_tokens.clear();
+        expire_now().execute(&state.db).await.unwrap();
+        refresh_expiring(&state).await.unwrap();
+        let (_, failures, needs_reconsent) = load().fetch_one(&state.db).await.unwrap();
+        assert_eq!((failures, needs_reconsent), (1, true));
+
+        delete_user(&state, user.id).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn unverified_email_is_not_stored_on_user() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let id = Uuid::new_v4().to_string();
+        let email = format!("{}@example.test", id);
+        set_profile(
+            &idp,
+            json!({ "id": id, "username": "tester", "email": email, "verified": false }),
+        );
+
+        let authorized = sign_in_at_idp(&state, &Provider::Discord).await;
+        let user = signed_in(
+            complete_login(
+                &state,
+                &Provider::Discord,
+                authorized.params(),
+                Some(&authorized.cookie),
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(user.email, None);
+
+        // 提供者給的 email 仍記在身分上，只是不會成為帳號的 email
+        let provider_email = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT provider_email FROM user_identities WHERE provider = 'discord' AND provider_user_id = $1",
+        )
+        .bind(&id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(provider_email, Some(email));
+
+        delete_user(&state, user.id).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn relogin_updates_existing_identity() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let subject = Uuid::new_v4().to_string();
+
+        let mut user_ids = Vec::new();
+        for name in ["Before", "After"] {
+            set_profile(&idp, json!({ "sub": subject, "name": name }));
+            let authorized = sign_in_at_idp(&state, &Provider::Google).await;
+            let user = signed_in(
+                complete_login(
+                    &state,
+                    &Provider::Google,
+                    authorized.params(),
+                    Some(&authorized.cookie),
+                    None,
+                )
+                .await
+                .unwrap(),
+            );
+            assert_eq!(user.display_name.as_deref(), Some(name));
+            user_ids.push(user.id);
+        }
+        assert_eq!(user_ids[0], user_ids[1]);
+
+        // 沒有設定加密金鑰，token 以明文保存
+        let rows = sqlx::query_as::<_, (Uuid, Option<String>)>(
+            "SELECT user_id, access_token FROM user_identities WHERE provider = 'google' AND provider_user_id = $1",
+        )
+        .bind(&subject)
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        let latest = idp.lock().unwrap().issued.last().cloned();
+        assert_eq!(rows, vec![(user_ids[0], latest)]);
+
+        delete_user(&state, user_ids[0]).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn oidc_login_verifies_id_token_from_issuer() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let subject = Uuid::new_v4().to_string();
+        let email = format!("{}@example.test", subject);
+        set_profile(
+            &idp,
+            json!({ "sub": subject, "email": email, "email_verified": true }),
+        );
+
+        let provider = Provider::oidc(&state, "mock").unwrap();
+        let authorized = sign_in_at_idp(&state, &provider).await;
+        let user = signed_in(
+            complete_login(
+                &state,
+                &provider,
+                authorized.params(),
+                Some(&authorized.cookie),
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(user.email, Some(email));
+
+        delete_user(&state, user.id).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres 與 Valkey（TEST_DATABASE_URL、TEST_VALKEY_URL）"]
+    async fn merge_token_is_single_use_and_restored_on_failure() {
+        let idp = spawn_idp().await;
+        let state = test_state(&idp).await;
+        let subject = Uuid::new_v4().to_string();
+        let email = format!("{}@example.test", subject);
+        let existing =
+            sqlx::query_scalar::<_, Uuid>("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(&email)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        set_profile(
+            &idp,
+            json!({ "sub": subject, "email": email, "email_verified": true }),
+        );
+
+        let authorized = sign_in_at_idp(&state, &Provider::Google).await;
+        let merge_token = match complete_login(
+            &state,
+            &Provider::Google,
+            authorized.params(),
+            Some(&authorized.cookie),
+            None,
+        )
+        .await
+        .unwrap()
+        {
+            LoginOutcome::MergeRequired { merge_token, .. } => merge_token,
+            _ => panic!("預期需要合併"),
+        };
+
+        // 以其他帳號合併會被拒絕，但 merge_token 仍可使用
+        let err = complete_merge(&state, &merge_token, Uuid::new_v4())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::FORBIDDEN);
+
+        complete_merge(&state, &merge_token, existing)
+            .await
+            .unwrap();
+        let err = complete_merge(&state, &merge_token, existing)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+
+        let owner = sqlx::query_scalar::<_, Uuid>(
+            "SELECT user_id FROM user_identities WHERE provider = 'google' AND provider_user_id = $1",
+        )
+        .bind(&subject)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(owner, existing);
+
+        delete_user(&state, existing).await;
+    }
+}
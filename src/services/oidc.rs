@@ -0,0 +1,278 @@
+// src/services/oidc.rs
+
+use axum::http::StatusCode;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Header, Validation,
+    jwk::{Jwk, JwkSet},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::OidcProviderConfig, error::AppError, services::cache, state::AppState};
+
+/// discovery 文件與 JWKS 在 Valkey 的快取時間
+const DISCOVERY_CACHE_TTL_SECS: u64 = 60 * 60;
+const JWKS_CACHE_TTL_SECS: u64 = 60 * 60;
+
+/// `.well-known/openid-configuration` 中用到的欄位
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Discovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+}
+
+/// 取得提供者的 discovery 文件（先查 Valkey 快取）
+pub async fn discover(
+    state: &AppState,
+    config: &OidcProviderConfig,
+) -> Result<Discovery, AppError> {
+    let cache_key = format!("oidc:discovery:{}", config.issuer);
+    if let Some(bytes) = cache::get_bytes(&state.redis, &cache_key).await
+        && let Ok(discovery) = serde_json::from_slice::<Discovery>(&bytes)
+    {
+        return Ok(discovery);
+    }
+
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        config.issuer.trim_end_matches('/')
+    );
+    let res = state.http_client.get(&url).send().await?;
+    if !res.status().is_success() {
+        tracing::warn!("取得 {} discovery 文件失敗: {}", config.name, res.status());
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("無法取得 {} 的 OIDC 設定", config.name),
+        ));
+    }
+    let bytes = res.bytes().await?;
+    let discovery = serde_json::from_slice::<Discovery>(&bytes).map_err(|e| {
+        AppError::with_source(StatusCode::BAD_GATEWAY, "OIDC discovery 文件格式錯誤", e)
+    })?;
+
+    // 規範要求文件中的 issuer 必須與設定的 issuer 相同
+    if discovery.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!(
+                "{} 的 issuer 不符: 設定為 {}，文件為 {}",
+                config.name, config.issuer, discovery.issuer
+            ),
+        ));
+    }
+
+    cache::set_bytes(&state.redis, &cache_key, &bytes, DISCOVERY_CACHE_TTL_SECS).await;
+    Ok(discovery)
+}
+
+/// 取得提供者的簽章公鑰；`refresh` 為 true 時略過快取（遇到未知 kid 時使用）
+async fn jwks(state: &AppState, discovery: &Discovery, refresh: bool) -> Result<JwkSet, AppError> {
+    let cache_key = format!("oidc:jwks:{}", discovery.jwks_uri);
+    if !refresh
+        && let Some(bytes) = cache::get_bytes(&state.redis, &cache_key).await
+        && let Ok(jwks) = serde_json::from_slice::<JwkSet>(&bytes)
+    {
+        return Ok(jwks);
+    }
+
+    let res = state.http_client.get(&discovery.jwks_uri).send().await?;
+    if !res.status().is_success() {
+        tracing::warn!("取得 JWKS 失敗 {}: {}", discovery.jwks_uri, res.status());
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            "無法取得 ID token 簽章公鑰",
+        ));
+    }
+    let bytes = res.bytes().await?;
+    let jwks = serde_json::from_slice::<JwkSet>(&bytes)
+        .map_err(|e| AppError::with_source(StatusCode::BAD_GATEWAY, "JWKS 格式錯誤", e))?;
+
+    cache::set_bytes(&state.redis, &cache_key, &bytes, JWKS_CACHE_TTL_SECS).await;
+    Ok(jwks)
+}
+
+fn invalid(e: jsonwebtoken::errors::Error) -> AppError {
+    AppError::with_source(StatusCode::UNAUTHORIZED, "ID token 驗證失敗", e)
+}
+
+/// 依 header 的 kid 挑選公鑰；沒有 kid 時只有在 JWKS 僅有一把金鑰時才採用
+fn find_key(keys: &JwkSet, header: &Header) -> Option<Jwk> {
+    match &header.kid {
+        Some(kid) => keys.find(kid).cloned(),
+        None if keys.keys.len() == 1 => keys.keys.first().cloned(),
+        None => None,
+    }
+}
+
+/// 取出 ID token 的 header，只接受非對稱簽章，避免以公開的金鑰當成 HMAC 密鑰偽造
+fn decode_header(id_token: &str) -> Result<Header, AppError> {
+    let header = jsonwebtoken::decode_header(id_token).map_err(invalid)?;
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(AppError::unauthorized("不支援的 ID token 簽章演算法"));
+    }
+    Ok(header)
+}
+
+/// 驗證 ID token 的簽章、issuer、audience、有效期限與 nonce，回傳其中的 claims
+pub async fn verify_id_token(
+    state: &AppState,
+    discovery: &Discovery,
+    client_id: &str,
+    id_token: &str,
+    nonce: &str,
+) -> Result<Value, AppError> {
+    let header = decode_header(id_token)?;
+
+    // 找不到 kid 時重新抓一次 JWKS，以支援提供者輪替金鑰
+    let mut keys = jwks(state, discovery, false).await?;
+    if find_key(&keys, &header).is_none() {
+        keys = jwks(state, discovery, true).await?;
+    }
+    verify_with_keys(&keys, discovery, client_id, id_token, nonce)
+}
+
+/// 以已取得的 JWKS 驗證 ID token
+fn verify_with_keys(
+    keys: &JwkSet,
+    discovery: &Discovery,
+    client_id: &str,
+    id_token: &str,
+    nonce: &str,
+) -> Result<Value, AppError> {
+    let header = decode_header(id_token)?;
+    let jwk = find_key(keys, &header)
+        .ok_or_else(|| AppError::unauthorized("找不到 ID token 的簽章公鑰"))?;
+    let key = DecodingKey::from_jwk(&jwk).map_err(invalid)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[client_id]);
+    validation.set_issuer(&[discovery.issuer.as_str()]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = jsonwebtoken::decode::<Value>(id_token, &key, &validation)
+        .map_err(invalid)?
+        .claims;
+
+    if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+        return Err(AppError::unauthorized("ID token 的 nonce 不符"));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use jsonwebtoken::EncodingKey;
+    use serde_json::json;
+
+    const SIGNING_KEY: &[u8] = include_bytes!("../../fixtures/oidc_test_key.pem");
+    const JWKS: &str = include_str!("../../fixtures/oidc_test_jwks.json");
+    const ISSUER: &str = "https://idp.example.test";
+    const CLIENT_ID: &str = "test-client";
+    const NONCE: &str = "expected-nonce";
+
+    fn discovery() -> Discovery {
+        Discovery {
+            issuer: ISSUER.to_string(),
+            authorization_endpoint: format!("{}/authorize", ISSUER),
+            token_endpoint: format!("{}/token", ISSUER),
+            userinfo_endpoint: None,
+            jwks_uri: format!("{}/jwks", ISSUER),
+        }
+    }
+
+    fn keys() -> JwkSet {
+        serde_json::from_str(JWKS).unwrap()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "user-1",
+            "iss": ISSUER,
+            "aud": CLIENT_ID,
+            "exp": Utc::now().timestamp() + 300,
+            "nonce": NONCE,
+        })
+    }
+
+    fn sign(kid: Option<&str>, claims: &Value) -> String {
+        let header = Header {
+            kid: kid.map(str::to_string),
+            ..Header::new(Algorithm::RS256)
+        };
+        jsonwebtoken::encode(
+            &header,
+            claims,
+            &EncodingKey::from_rsa_pem(SIGNING_KEY).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn verify(id_token: &str) -> Result<Value, AppError> {
+        verify_with_keys(&keys(), &discovery(), CLIENT_ID, id_token, NONCE)
+    }
+
+    fn rejected(id_token: &str) -> String {
+        let err = verify(id_token).unwrap_err();
+        assert_eq!(err.status_code, StatusCode::UNAUTHORIZED);
+        err.message
+    }
+
+    #[test]
+    fn accepts_token_signed_by_jwks_key() {
+        let claims = verify(&sign(Some("test-key"), &claims())).unwrap();
+        assert_eq!(claims["sub"], "user-1");
+        // 沒有 kid 但 JWKS 只有一把金鑰
+        assert!(verify(&sign(None, &claims)).is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_nonce_audience_issuer_and_expiry() {
+        let with = |field: &str, value: Value| {
+            let mut claims = claims();
+            claims[field] = value;
+            sign(Some("test-key"), &claims)
+        };
+
+        assert!(rejected(&with("nonce", json!("other-nonce"))).contains("nonce"));
+        rejected(&with("aud", json!("other-client")));
+        rejected(&with("iss", json!("https://evil.example.test")));
+        rejected(&with("exp", json!(Utc::now().timestamp() - 120)));
+
+        let mut missing_sub = claims();
+        missing_sub.as_object_mut().unwrap().remove("sub");
+        rejected(&sign(Some("test-key"), &missing_sub));
+    }
+
+    #[test]
+    fn rejects_hmac_downgrade() {
+        // 攻擊者以公開的 JWKS 內容當成 HMAC 密鑰簽章
+        for alg in [Algorithm::HS256, Algorithm::HS512] {
+            let header = Header {
+                kid: Some("test-key".to_string()),
+                ..Header::new(alg)
+            };
+            let token = jsonwebtoken::encode(
+                &header,
+                &claims(),
+                &EncodingKey::from_secret(JWKS.as_bytes()),
+            )
+            .unwrap();
+            assert!(rejected(&token).contains("演算法"));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_kid() {
+        assert!(rejected(&sign(Some("rotated-away"), &claims())).contains("公鑰"));
+        assert!(rejected("not-a-jwt").contains("ID token"));
+    }
+}
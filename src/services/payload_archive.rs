@@ -0,0 +1,135 @@
+// src/services/payload_archive.rs
+
+use std::io::{Read, Write};
+
+use chrono::NaiveDate;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+/// 從上游取得的原始回應
+#[derive(Debug)]
+pub struct RawPayload {
+    pub url: String,
+    pub status_code: u16,
+    pub body: Vec<u8>,
+}
+
+/// 從封存中取回的原始回應
+#[derive(Debug)]
+pub struct ArchivedPayload {
+    pub id: i64,
+    pub trade_date: NaiveDate,
+    pub body: Vec<u8>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ArchivedRow {
+    id: i64,
+    trade_date: NaiveDate,
+    body: Vec<u8>,
+    sha256: String,
+}
+
+/// 封存原始回應（gzip 壓縮），回傳封存 id
+///
+/// 內容完全相同的回應只存一份，重複抓取時只更新 `last_fetched_at`。
+/// * `request_date`: 要求的日期（抓最新資料時為 `None`）
+/// * `trade_date`: 從回應解析出的交易日，解析失敗時為 `None`
+pub async fn archive(
+    db: &PgPool,
+    source: &str,
+    payload: &RawPayload,
+    request_date: Option<NaiveDate>,
+    trade_date: Option<NaiveDate>,
+) -> Result<i64, AppError> {
+    let sha256 = hex::encode(Sha256::digest(&payload.body));
+    let compressed = gzip(&payload.body)?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO upstream_payloads (
+            source, url, status_code, request_date, trade_date,
+            body_gzip, body_size, sha256
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (source, sha256) DO UPDATE
+        SET last_fetched_at = NOW()
+        RETURNING id
+        "#,
+    )
+    .bind(source)
+    .bind(&payload.url)
+    .bind(payload.status_code as i32)
+    .bind(request_date)
+    .bind(trade_date)
+    .bind(&compressed)
+    .bind(payload.body.len() as i32)
+    .bind(&sha256)
+    .fetch_one(db)
+    .await?;
+
+    Ok(id)
+}
+
+/// 取出區間內每個交易日最後抓到的原始回應（已解壓並驗證雜湊）
+///
+/// 解析失敗而沒有交易日的回應會以要求的日期歸類；同一天有解析成功的回應時優先採用，
+/// 避免之後一次失敗的抓取蓋掉原本可用的封存。
+pub async fn load_latest_by_date(
+    db: &PgPool,
+    source: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<ArchivedPayload>, AppError> {
+    let rows = sqlx::query_as::<_, ArchivedRow>(
+        r#"
+        SELECT DISTINCT ON (COALESCE(trade_date, request_date))
+               id, COALESCE(trade_date, request_date) AS trade_date, body_gzip AS body, sha256
+        FROM upstream_payloads
+        WHERE source = $1
+          AND COALESCE(trade_date, request_date) BETWEEN $2 AND $3
+        ORDER BY COALESCE(trade_date, request_date), (trade_date IS NULL), last_fetched_at DESC
+        "#,
+    )
+    .bind(source)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let body = gunzip(&row.body)?;
+            if hex::encode(Sha256::digest(&body)) != row.sha256 {
+                return Err(AppError::internal_error(format!(
+                    "封存資料 {} 的雜湊不符",
+                    row.id
+                )));
+            }
+            Ok(ArchivedPayload {
+                id: row.id,
+                trade_date: row.trade_date,
+                body,
+            })
+        })
+        .collect()
+}
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| AppError::internal_error(format!("壓縮原始資料失敗: {}", e)))
+}
+
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut body = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut body)
+        .map_err(|e| AppError::internal_error(format!("解壓縮原始資料失敗: {}", e)))?;
+    Ok(body)
+}
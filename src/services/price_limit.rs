@@ -0,0 +1,407 @@
+// src/services/price_limit.rs
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    error::AppError,
+    services::{data_quality, market_summary, stock_day_all::StockDayRow},
+};
+
+/// 漲跌停相關標記
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PriceLimitFlags {
+    /// 收盤漲停
+    pub limit_up: bool,
+    /// 收盤跌停
+    pub limit_down: bool,
+    /// 盤中觸及漲停但收盤未鎖住（打開漲停）
+    pub touched_limit_up: bool,
+    /// 盤中觸及跌停但收盤未鎖住
+    pub touched_limit_down: bool,
+    /// 當日沒有成交價（暫停交易或全日無成交）
+    pub halted: bool,
+}
+
+/// 可查詢的標記種類
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceLimitFlag {
+    LimitUp,
+    LimitDown,
+    TouchedLimitUp,
+    TouchedLimitDown,
+    Halted,
+}
+
+impl PriceLimitFlag {
+    fn column(self) -> &'static str {
+        match self {
+            Self::LimitUp => "limit_up",
+            Self::LimitDown => "limit_down",
+            Self::TouchedLimitUp => "touched_limit_up",
+            Self::TouchedLimitDown => "touched_limit_down",
+            Self::Halted => "halted",
+        }
+    }
+}
+
+/// 帶有標記的個股日資料
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct FlaggedStock {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub close: Option<f64>,
+    pub price_change: Option<f64>,
+    pub trade_volume: Option<i64>,
+    pub trade_amount: Option<i64>,
+    pub limit_up: bool,
+    pub limit_down: bool,
+    pub touched_limit_up: bool,
+    pub touched_limit_down: bool,
+    pub halted: bool,
+}
+
+/// 某個交易日帶有指定標記的股票
+#[derive(Debug, Serialize)]
+pub struct FlaggedStocks {
+    pub trade_date: NaiveDate,
+    pub flag: PriceLimitFlag,
+    pub stocks: Vec<FlaggedStock>,
+}
+
+/// 重新計算標記的結果
+#[derive(Debug, Serialize)]
+pub struct RecomputeSummary {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub trade_days: usize,
+    pub updated_rows: u64,
+}
+
+/// 決定升降單位的證券種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SecurityKind {
+    /// 股票（含存託憑證）
+    Stock,
+    /// ETF 與受益憑證，代號以 `00` 開頭（例如 0050、00878）
+    Fund,
+}
+
+impl SecurityKind {
+    fn from_code(stock_code: &str) -> Self {
+        if stock_code.starts_with("00") {
+            Self::Fund
+        } else {
+            Self::Stock
+        }
+    }
+
+    /// 證交所的升降單位（以「分」表示，避免浮點誤差）
+    fn tick_cents(self, price_cents: i64) -> i64 {
+        match self {
+            Self::Stock => match price_cents {
+                ..1_000 => 1,
+                1_000..5_000 => 5,
+                5_000..10_000 => 10,
+                10_000..50_000 => 50,
+                50_000..100_000 => 100,
+                _ => 500,
+            },
+            Self::Fund => match price_cents {
+                ..5_000 => 1,
+                _ => 5,
+            },
+        }
+    }
+}
+
+/// 依參考價計算漲停價與跌停價（單位：分）
+///
+/// 漲停價為參考價加上限制幅度後，依該價位的升降單位無條件捨去；跌停價則無條件進位。
+fn limit_prices_cents(reference: f64, limit_pct: f64, kind: SecurityKind) -> (i64, i64) {
+    let raw_up = reference * (100.0 + limit_pct);
+    let raw_down = reference * (100.0 - limit_pct);
+
+    // 先四捨五入到 0.0001 分，避免 10.1 * 110 這類浮點誤差跨過升降單位
+    let raw_up = (raw_up * 10_000.0).round() / 10_000.0;
+    let raw_down = (raw_down * 10_000.0).round() / 10_000.0;
+
+    let up_tick = kind.tick_cents(raw_up.floor() as i64);
+    let down_tick = kind.tick_cents(raw_down.ceil() as i64);
+    let up = (raw_up / up_tick as f64).floor() as i64 * up_tick;
+    let down = (raw_down / down_tick as f64).ceil() as i64 * down_tick;
+    (up, down)
+}
+
+fn to_cents(price: f64) -> i64 {
+    (price * 100.0).round() as i64
+}
+
+/// 判斷一筆日成交資料的漲跌停狀態
+///
+/// 參考價優先使用「收盤價 − 漲跌價差」，這樣除權息日也能得到正確的參考價；
+/// 缺少漲跌價差時才退回前一交易日收盤價。兩者都沒有（例如新上市）時只判斷是否停止交易。
+pub fn classify(row: &StockDayRow, previous_close: Option<f64>, limit_pct: f64) -> PriceLimitFlags {
+    let Some(close) = row.close_price else {
+        return PriceLimitFlags {
+            halted: true,
+            ..Default::default()
+        };
+    };
+
+    let reference = row
+        .price_change
+        .map(|change| close - change)
+        .or(previous_close)
+        .filter(|reference| *reference > 0.0);
+    let Some(reference) = reference else {
+        return PriceLimitFlags::default();
+    };
+
+    let kind = SecurityKind::from_code(&row.stock_code);
+    let (up, down) = limit_prices_cents(reference, limit_pct, kind);
+    let close = to_cents(close);
+    let high = row.high_price.map(to_cents).unwrap_or(close);
+    let low = row.low_price.map(to_cents).unwrap_or(close);
+
+    let limit_up = close >= up;
+    let limit_down = close <= down;
+    PriceLimitFlags {
+        limit_up,
+        limit_down,
+        touched_limit_up: high >= up && !limit_up,
+        touched_limit_down: low <= down && !limit_down,
+        halted: false,
+    }
+}
+
+/// 計算整批資料列的標記（順序與輸入相同）
+pub fn classify_all(
+    rows: &[StockDayRow],
+    previous_closes: &HashMap<String, f64>,
+    limit_pct: f64,
+) -> Vec<PriceLimitFlags> {
+    rows.iter()
+        .map(|row| {
+            classify(
+                row,
+                previous_closes.get(&row.stock_code).copied(),
+                limit_pct,
+            )
+        })
+        .collect()
+}
+
+/// 以資料庫中既有的日資料重新計算區間內的漲跌停標記（匯入此功能前的舊資料用）
+pub async fn recompute(
+    db: &PgPool,
+    from: NaiveDate,
+    to: NaiveDate,
+    limit_pct: f64,
+) -> Result<RecomputeSummary, AppError> {
+    let dates = sqlx::query_scalar::<_, NaiveDate>(
+        "SELECT DISTINCT trade_date FROM stock_day_all WHERE trade_date BETWEEN $1 AND $2 ORDER BY trade_date",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    let mut updated_rows = 0;
+    for &trade_date in &dates {
+        let mut tx = db.begin().await?;
+        let rows = load_rows(&mut tx, trade_date).await?;
+        let previous_closes = data_quality::load_previous_closes(&mut tx, trade_date).await?;
+        let flags = classify_all(&rows, &previous_closes, limit_pct);
+        updated_rows += update_flags(&mut tx, trade_date, &rows, &flags).await?;
+        tx.commit().await?;
+    }
+    market_summary::refresh_after_write(db).await;
+
+    tracing::info!(
+        "🚦 漲跌停標記 {} ~ {}: {} 個交易日, 更新 {} 筆",
+        from,
+        to,
+        dates.len(),
+        updated_rows
+    );
+
+    Ok(RecomputeSummary {
+        from,
+        to,
+        trade_days: dates.len(),
+        updated_rows,
+    })
+}
+
+async fn load_rows(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+) -> Result<Vec<StockDayRow>, AppError> {
+    let rows = sqlx::query_as::<_, StockDayRow>(
+        r#"
+        SELECT stock_code, stock_name,
+               trade_volume, trade_amount,
+               open_price::float8 AS open_price,
+               high_price::float8 AS high_price,
+               low_price::float8 AS low_price,
+               close_price::float8 AS close_price,
+               price_change::float8 AS price_change,
+               transaction_count
+        FROM stock_day_all
+        WHERE trade_date = $1
+        "#,
+    )
+    .bind(trade_date)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows)
+}
+
+async fn update_flags(
+    conn: &mut PgConnection,
+    trade_date: NaiveDate,
+    rows: &[StockDayRow],
+    flags: &[PriceLimitFlags],
+) -> Result<u64, AppError> {
+    let stock_codes: Vec<&str> = rows.iter().map(|r| r.stock_code.as_str()).collect();
+    let column = |f: fn(&PriceLimitFlags) -> bool| flags.iter().map(f).collect::<Vec<bool>>();
+
+    let result = sqlx::query(
+        r#"
+        UPDATE stock_day_all d
+        SET limit_up = f.limit_up,
+            limit_down = f.limit_down,
+            touched_limit_up = f.touched_limit_up,
+            touched_limit_down = f.touched_limit_down,
+            halted = f.halted
+        FROM UNNEST($2::text[], $3::bool[], $4::bool[], $5::bool[], $6::bool[], $7::bool[])
+             AS f(stock_code, limit_up, limit_down, touched_limit_up, touched_limit_down, halted)
+        WHERE d.trade_date = $1
+          AND d.stock_code = f.stock_code
+        "#,
+    )
+    .bind(trade_date)
+    .bind(&stock_codes)
+    .bind(column(|f| f.limit_up))
+    .bind(column(|f| f.limit_down))
+    .bind(column(|f| f.touched_limit_up))
+    .bind(column(|f| f.touched_limit_down))
+    .bind(column(|f| f.halted))
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// 列出某個交易日（預設為最新交易日）帶有指定標記的股票，依成交金額由大到小排序
+pub async fn list_flagged(
+    db: &PgPool,
+    date: Option<NaiveDate>,
+    flag: PriceLimitFlag,
+) -> Result<FlaggedStocks, AppError> {
+    let trade_date = match date {
+        Some(date) => date,
+        None => {
+            sqlx::query_scalar::<_, Option<NaiveDate>>("SELECT MAX(trade_date) FROM stock_day_all")
+                .fetch_one(db)
+                .await?
+                .ok_or_else(|| AppError::not_found("尚未匯入任何日成交資料"))?
+        }
+    };
+
+    // 欄位名稱來自固定的列舉值，不是使用者輸入
+    let query = format!(
+        r#"
+        SELECT stock_code, stock_name,
+               close_price::float8 AS close,
+               price_change::float8 AS price_change,
+               trade_volume, trade_amount,
+               limit_up, limit_down, touched_limit_up, touched_limit_down, halted
+        FROM stock_day_all
+        WHERE trade_date = $1
+          AND {}
+        ORDER BY trade_amount DESC NULLS LAST, stock_code
+        "#,
+        flag.column()
+    );
+
+    let stocks = sqlx::query_as::<_, FlaggedStock>(&query)
+        .bind(trade_date)
+        .fetch_all(db)
+        .await?;
+
+    Ok(FlaggedStocks {
+        trade_date,
+        flag,
+        stocks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(reference: f64, kind: SecurityKind) -> (i64, i64) {
+        limit_prices_cents(reference, 10.0, kind)
+    }
+
+    #[test]
+    fn stock_limits_follow_equity_ticks() {
+        // 10.1 * 1.1 = 11.11 浮點上是 11.110000000000001，不能因此多算一個升降單位
+        assert_eq!(limits(10.1, SecurityKind::Stock), (1110, 909));
+        assert_eq!(limits(100.0, SecurityKind::Stock), (11000, 9000));
+        assert_eq!(limits(595.0, SecurityKind::Stock), (65400, 53600));
+        assert_eq!(limits(1085.0, SecurityKind::Stock), (119000, 97700));
+    }
+
+    #[test]
+    fn stock_limits_use_tick_of_the_limit_price_itself() {
+        // 漲停 10.01 已進入 10~50 元區間（0.05），捨去為 10.00；跌停 8.19 仍在 0.01 區間
+        assert_eq!(limits(9.1, SecurityKind::Stock), (1000, 819));
+        // 跌停 49.95 在 10~50 元區間（0.05），漲停 61.05 在 50~100 元區間（0.1）
+        assert_eq!(limits(55.5, SecurityKind::Stock), (6100, 4995));
+    }
+
+    #[test]
+    fn fund_limits_tick_by_cent_below_fifty() {
+        assert_eq!(SecurityKind::from_code("0050"), SecurityKind::Fund);
+        assert_eq!(SecurityKind::from_code("00878"), SecurityKind::Fund);
+        assert_eq!(SecurityKind::from_code("2330"), SecurityKind::Stock);
+
+        assert_eq!(limits(10.1, SecurityKind::Fund), (1111, 909));
+        assert_eq!(limits(21.37, SecurityKind::Fund), (2350, 1924));
+        // 漲停 50.05 已達 50 元（0.05），股票則是 0.1 而捨去為 50.00
+        assert_eq!(limits(45.5, SecurityKind::Fund), (5005, 4095));
+        assert_eq!(limits(45.5, SecurityKind::Stock), (5000, 4095));
+        assert_eq!(limits(150.0, SecurityKind::Fund), (16500, 13500));
+        assert_eq!(limits(187.3, SecurityKind::Fund), (20600, 16860));
+    }
+
+    #[test]
+    fn classify_picks_ticks_by_stock_code() {
+        let row = |code: &str, close: f64| StockDayRow {
+            stock_code: code.to_string(),
+            stock_name: String::new(),
+            trade_volume: None,
+            trade_amount: None,
+            open_price: None,
+            high_price: None,
+            low_price: None,
+            close_price: Some(close),
+            price_change: None,
+            transaction_count: None,
+        };
+
+        // 參考價 21.37：ETF 以 0.01 為單位，漲停 23.50
+        assert!(classify(&row("00878", 23.5), Some(21.37), 10.0).limit_up);
+        // 參考價 10.1：ETF 漲停 11.11，收 11.10 不算漲停；股票則剛好鎖住
+        assert!(!classify(&row("00878", 11.1), Some(10.1), 10.0).limit_up);
+        assert!(classify(&row("2330", 11.1), Some(10.1), 10.0).limit_up);
+    }
+}
@@ -0,0 +1,267 @@
+// src/services/provider_token_refresh.rs
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    services::{
+        oauth::{self, Provider, TokenRefresh},
+        token_crypto,
+    },
+    state::AppState,
+};
+
+/// 每批換發的身分數
+const REFRESH_BATCH_ROWS: i64 = 100;
+/// 連續失敗時的退避上限（秒）
+const MAX_BACKOFF_SECS: f64 = 24.0 * 60.0 * 60.0;
+
+#[derive(sqlx::FromRow)]
+struct DueIdentity {
+    id: Uuid,
+    provider: String,
+    provider_user_id: String,
+    refresh_token: String,
+    token_key_id: Option<String>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// 一輪換發的結果
+#[derive(Debug, Default, Serialize)]
+pub struct RefreshSummary {
+    pub refreshed: u64,
+    /// 換發期間使用者重新登入，已改用登入取得的 token
+    pub superseded: u64,
+    pub revoked: u64,
+    pub failed: u64,
+}
+
+/// 換發單一身分的結果
+enum RefreshOutcome {
+    /// 新的 token 已寫回資料庫
+    Saved,
+    /// 換發期間該列已被重新登入更新，換發到的 token 沒有寫入
+    Superseded,
+    /// 提供者回應 `invalid_grant`
+    Revoked(String),
+}
+
+/// 換發所有即將到期（或已到期）的第三方 access token
+///
+/// 先把要處理的列標記 `last_refresh_attempt_at` 再逐一呼叫提供者，多個執行個體同時執行也不會重複換發；
+/// 連續失敗的身分依失敗次數拉長重試間隔。
+pub async fn refresh_expiring(state: &AppState) -> Result<RefreshSummary, AppError> {
+    let config = &state.config.auth.token_refresh;
+    let providers = configured_providers(state);
+    let mut summary = RefreshSummary::default();
+    if providers.is_empty() {
+        return Ok(summary);
+    }
+
+    loop {
+        let due = sqlx::query_as::<_, DueIdentity>(
+            r#"
+            UPDATE user_identities
+            SET last_refresh_attempt_at = NOW()
+            WHERE id IN (
+                SELECT id FROM user_identities
+                WHERE refresh_token IS NOT NULL
+                  AND NOT needs_reconsent
+                  AND expires_at <= NOW() + make_interval(secs => $1)
+                  AND provider = ANY($2)
+                  AND (last_refresh_attempt_at IS NULL
+                       OR last_refresh_attempt_at
+                          + make_interval(secs => LEAST($3 * (refresh_failures + 1), $4))
+                          <= NOW())
+                ORDER BY expires_at
+                LIMIT $5
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, provider, provider_user_id, refresh_token, token_key_id, updated_at
+            "#,
+        )
+        .bind(config.ahead.as_secs_f64())
+        .bind(&providers)
+        .bind(config.interval.as_secs_f64())
+        .bind(MAX_BACKOFF_SECS)
+        .bind(REFRESH_BATCH_ROWS)
+        .fetch_all(&state.db)
+        .await?;
+
+        for identity in &due {
+            match refresh_one(state, identity).await {
+                Ok(RefreshOutcome::Saved) => summary.refreshed += 1,
+                Ok(RefreshOutcome::Superseded) => {
+                    tracing::info!(
+                        "{} 換發期間使用者已重新登入，捨棄換發結果: identity {}",
+                        identity.provider,
+                        identity.id
+                    );
+                    summary.superseded += 1;
+                }
+                Ok(RefreshOutcome::Revoked(reason)) => {
+                    tracing::warn!(
+                        "🔒 {} 授權已撤銷，需重新授權: identity {}, {}",
+                        identity.provider,
+                        identity.id,
+                        reason
+                    );
+                    record_failure(state, identity.id, &reason, true).await?;
+                    summary.revoked += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "{} token 換發失敗: identity {}, {}",
+                        identity.provider,
+                        identity.id,
+                        e
+                    );
+                    record_failure(state, identity.id, &e.to_string(), false).await?;
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        if (due.len() as i64) < REFRESH_BATCH_ROWS {
+            break;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// 目前有設定的提供者名稱，沒有設定的提供者無法換發，留給設定恢復後處理
+fn configured_providers(state: &AppState) -> Vec<String> {
+    let auth = &state.config.auth;
+    auth.google
+        .as_ref()
+        .map(|_| Provider::Google.name().to_string())
+        .into_iter()
+        .chain(
+            auth.discord
+                .as_ref()
+                .map(|_| Provider::Discord.name().to_string()),
+        )
+        .chain(auth.oidc.iter().map(|provider| provider.name.clone()))
+        .collect()
+}
+
+async fn refresh_one(state: &AppState, identity: &DueIdentity) -> Result<RefreshOutcome, AppError> {
+    let crypto = &state.config.auth.token_encryption;
+    let provider = Provider::from_name(state, &identity.provider)
+        .ok_or_else(|| AppError::internal_error(format!("未設定 {} 登入", identity.provider)))?;
+    let refresh_token = token_crypto::open(
+        crypto,
+        identity.token_key_id.as_deref(),
+        &identity.provider,
+        &identity.provider_user_id,
+        "refresh_token",
+        &identity.refresh_token,
+    )?;
+
+    let tokens = match oauth::refresh_access_token(state, &provider, &refresh_token).await? {
+        TokenRefresh::Refreshed(tokens) => tokens,
+        TokenRefresh::Revoked(reason) => return Ok(RefreshOutcome::Revoked(reason)),
+    };
+
+    // 有輪替的提供者會回傳新的 refresh token，否則沿用原本的
+    let sealed = token_crypto::seal(
+        crypto,
+        &identity.provider,
+        &identity.provider_user_id,
+        Some(&tokens.access_token),
+        Some(tokens.refresh_token.as_deref().unwrap_or(&refresh_token)),
+    )?;
+
+    // 換發期間使用者重新登入寫入了新的 token 時，以登入的結果為準
+    let result = sqlx::query(
+        r#"
+        UPDATE user_identities
+        SET access_token = $3,
+            refresh_token = $4,
+            token_key_id = $5,
+            expires_at = $6,
+            last_refreshed_at = NOW(),
+            refresh_failures = 0,
+            last_refresh_error = NULL,
+            updated_at = NOW()
+        WHERE id = $1 AND updated_at IS NOT DISTINCT FROM $2
+        "#,
+    )
+    .bind(identity.id)
+    .bind(identity.updated_at)
+    .bind(&sealed.access_token)
+    .bind(&sealed.refresh_token)
+    .bind(&sealed.key_id)
+    .bind(tokens.expires_at)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(RefreshOutcome::Superseded);
+    }
+    Ok(RefreshOutcome::Saved)
+}
+
+async fn record_failure(
+    state: &AppState,
+    id: Uuid,
+    error: &str,
+    revoked: bool,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE user_identities
+        SET refresh_failures = refresh_failures + 1,
+            last_refresh_error = $2,
+            needs_reconsent = needs_reconsent OR $3
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(error)
+    .bind(revoked)
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+/// 啟動背景換發，間隔為 0 時不啟用
+pub fn spawn_scheduler(state: Arc<AppState>) {
+    let interval = state.config.auth.token_refresh.interval;
+    if interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match refresh_expiring(&state).await {
+                Ok(summary)
+                    if summary.refreshed
+                        + summary.superseded
+                        + summary.revoked
+                        + summary.failed
+                        > 0 =>
+                {
+                    tracing::info!(
+                        "🔄 第三方 token 換發: 成功 {}, 已重新登入 {}, 撤銷 {}, 失敗 {}",
+                        summary.refreshed,
+                        summary.superseded,
+                        summary.revoked,
+                        summary.failed
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("第三方 token 定期換發失敗: {}", e),
+            }
+        }
+    });
+}
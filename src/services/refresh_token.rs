@@ -0,0 +1,314 @@
+// src/services/refresh_token.rs
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{config::JwtConfig, error::AppError, services::jwt, utils::token::random_token};
+
+/// 回傳給行動端的 token 組
+#[derive(Debug, Serialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub token_type: &'static str,
+    /// access token 的有效秒數
+    pub expires_in: u64,
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredToken {
+    id: Uuid,
+    user_id: Uuid,
+    family_id: Uuid,
+    expires_at: DateTime<Utc>,
+    family_expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+    is_active: bool,
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// 新 refresh token 的到期時間：一般有效期限，但不超過家族的絕對期限
+fn next_expires_at(
+    config: &JwtConfig,
+    now: DateTime<Utc>,
+    family_expires_at: DateTime<Utc>,
+) -> DateTime<Utc> {
+    (now + Duration::seconds(config.refresh_ttl.as_secs() as i64)).min(family_expires_at)
+}
+
+/// 簽發一組新的 token（開啟新的 refresh token 家族），用於登入後取得 token
+pub async fn issue(
+    db: &PgPool,
+    config: &JwtConfig,
+    user_id: Uuid,
+    user_agent: Option<&str>,
+) -> Result<TokenPair, AppError> {
+    let (access_token, expires_in) = jwt::issue_access_token(config, user_id)?;
+
+    let refresh_token = random_token(32);
+    let now = Utc::now();
+    let family_expires_at = now + Duration::seconds(config.refresh_max_age.as_secs() as i64);
+    let refresh_expires_at = next_expires_at(config, now, family_expires_at);
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens
+            (user_id, family_id, token_hash, user_agent, expires_at, family_expires_at)
+        VALUES ($1, uuid_generate_v4(), $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash(&refresh_token))
+    .bind(user_agent)
+    .bind(refresh_expires_at)
+    .bind(family_expires_at)
+    .execute(db)
+    .await?;
+
+    Ok(TokenPair {
+        access_token,
+        token_type: "Bearer",
+        expires_in,
+        refresh_token,
+        refresh_expires_at,
+    })
+}
+
+/// 以 refresh token 換發新的一組 token，舊的 refresh token 隨即失效
+///
+/// 已使用過或已撤銷的 refresh token 再次出現，代表 token 可能外洩，
+/// 此時撤銷整個家族，持有者必須重新登入。新 token 沿用家族的絕對期限，輪替無法無限延長登入。
+pub async fn rotate(
+    db: &PgPool,
+    config: &JwtConfig,
+    refresh_token: &str,
+    user_agent: Option<&str>,
+) -> Result<TokenPair, AppError> {
+    let invalid = || AppError::unauthorized("refresh token 無效或已過期");
+
+    let mut tx = db.begin().await?;
+    let stored = sqlx::query_as::<_, StoredToken>(
+        r#"
+        SELECT t.id, t.user_id, t.family_id, t.expires_at, t.family_expires_at,
+               t.used_at, t.revoked_at,
+               COALESCE(u.is_active, TRUE) AS is_active
+        FROM refresh_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(hash(refresh_token))
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(invalid)?;
+
+    if stored.used_at.is_some() || stored.revoked_at.is_some() {
+        let revoked = revoke_family_in(&mut tx, stored.family_id).await?;
+        tx.commit().await?;
+        tracing::warn!(
+            "⚠️ refresh token 重複使用: user {}, 撤銷家族 {} 共 {} 個 token",
+            stored.user_id,
+            stored.family_id,
+            revoked
+        );
+        return Err(invalid());
+    }
+    let now = Utc::now();
+    if stored.expires_at <= now || stored.family_expires_at <= now || !stored.is_active {
+        return Err(invalid());
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(stored.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let next_token = random_token(32);
+    let refresh_expires_at = next_expires_at(config, now, stored.family_expires_at);
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens
+            (user_id, family_id, parent_id, token_hash, user_agent, expires_at, family_expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(stored.user_id)
+    .bind(stored.family_id)
+    .bind(stored.id)
+    .bind(hash(&next_token))
+    .bind(user_agent)
+    .bind(refresh_expires_at)
+    .bind(stored.family_expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let (access_token, expires_in) = jwt::issue_access_token(config, stored.user_id)?;
+    tx.commit().await?;
+
+    Ok(TokenPair {
+        access_token,
+        token_type: "Bearer",
+        expires_in,
+        refresh_token: next_token,
+        refresh_expires_at,
+    })
+}
+
+/// 撤銷 refresh token 所屬的整個家族（登出），token 不存在時不視為錯誤
+pub async fn revoke(db: &PgPool, refresh_token: &str) -> Result<u64, AppError> {
+    let mut tx = db.begin().await?;
+    let family_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT family_id FROM refresh_tokens WHERE token_hash = $1")
+            .bind(hash(refresh_token))
+            .fetch_optional(&mut *tx)
+            .await?;
+
+    let revoked = match family_id {
+        Some(family_id) => revoke_family_in(&mut tx, family_id).await?,
+        None => 0,
+    };
+    tx.commit().await?;
+    Ok(revoked)
+}
+
+async fn revoke_family_in(conn: &mut sqlx::PgConnection, family_id: Uuid) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn config() -> JwtConfig {
+        JwtConfig {
+            keys: vec![(
+                "k1".to_string(),
+                "test-secret-with-at-least-32-characters".to_string(),
+            )],
+            active_kid: "k1".to_string(),
+            issuer: "axum-app".to_string(),
+            access_ttl: std::time::Duration::from_secs(15 * 60),
+            refresh_ttl: std::time::Duration::from_secs(24 * 60 * 60),
+            refresh_max_age: std::time::Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+
+    /// 連上 `TEST_DATABASE_URL` 並建立一個測試使用者
+    async fn test_user() -> (PgPool, Uuid) {
+        let url = std::env::var("TEST_DATABASE_URL").expect("Not Found TEST_DATABASE_URL");
+        let db = PgPool::connect(&url).await.unwrap();
+        let user_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (display_name) VALUES ('refresh token test') RETURNING id",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        (db, user_id)
+    }
+
+    async fn delete_user(db: &PgPool, user_id: Uuid) {
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn new_tokens_never_outlive_family() {
+        let config = config();
+        let now = Utc::now();
+        let far = now + Duration::days(30);
+        assert_eq!(next_expires_at(&config, now, far), now + Duration::days(1));
+        let soon = now + Duration::hours(2);
+        assert_eq!(next_expires_at(&config, now, soon), soon);
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres（TEST_DATABASE_URL）"]
+    async fn reused_token_revokes_whole_family() {
+        let (db, user_id) = test_user().await;
+        let config = config();
+
+        let first = issue(&db, &config, user_id, None).await.unwrap();
+        let second = rotate(&db, &config, &first.refresh_token, None)
+            .await
+            .unwrap();
+
+        // 已輪替掉的 token 再次出現：拒絕並撤銷整個家族，連合法持有者的新 token 也失效
+        let err = rotate(&db, &config, &first.refresh_token, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::UNAUTHORIZED);
+        let err = rotate(&db, &config, &second.refresh_token, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::UNAUTHORIZED);
+
+        let active = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(active, 0);
+
+        // 另一次登入的家族不受影響
+        let other = issue(&db, &config, user_id, None).await.unwrap();
+        assert!(
+            rotate(&db, &config, &other.refresh_token, None)
+                .await
+                .is_ok()
+        );
+
+        delete_user(&db, user_id).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres（TEST_DATABASE_URL）"]
+    async fn rotation_keeps_family_deadline() {
+        let (db, user_id) = test_user().await;
+        let config = config();
+
+        let first = issue(&db, &config, user_id, None).await.unwrap();
+        let set_deadline = |interval: &'static str| {
+            sqlx::query(
+                "UPDATE refresh_tokens SET family_expires_at = NOW() + $2::interval WHERE user_id = $1",
+            )
+            .bind(user_id)
+            .bind(interval)
+        };
+
+        // 家族只剩一小時：輪替出的 token 也只能用到那時
+        set_deadline("1 hour").execute(&db).await.unwrap();
+        let second = rotate(&db, &config, &first.refresh_token, None)
+            .await
+            .unwrap();
+        assert!(second.refresh_expires_at <= Utc::now() + Duration::hours(1));
+
+        // 家族已過期：即使 token 本身還沒到期也不能再輪替
+        set_deadline("-1 second").execute(&db).await.unwrap();
+        let err = rotate(&db, &config, &second.refresh_token, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::UNAUTHORIZED);
+
+        delete_user(&db, user_id).await;
+    }
+}
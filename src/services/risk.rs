@@ -0,0 +1,231 @@
+// src/services/risk.rs
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::error::AppError;
+
+// 變異數、標準差、下檔標準差與共變異數一律以 n - 1 為分母（樣本統計量），
+// 個股、投資組合與多檔比較的相關係數都用同一組函式計算，數值可以互相對照。
+
+/// 一年的交易日數，用於年化
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// 最大回撤
+#[derive(Debug, Serialize)]
+pub struct Drawdown {
+    /// 回撤幅度（負值，例如 -0.25 代表 -25%）
+    pub max_drawdown: f64,
+    /// 回撤開始的高點日期
+    pub peak_date: NaiveDate,
+    /// 回撤最深的低點日期
+    pub trough_date: NaiveDate,
+}
+
+/// 風險指標
+#[derive(Debug, Serialize)]
+pub struct RiskMetrics {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    /// 實際使用的日報酬筆數
+    pub observations: usize,
+    /// 年化波動率
+    pub annualized_volatility: f64,
+    /// 年化下檔標準差（以 0 為門檻）
+    pub downside_deviation: f64,
+    /// 相對基準（TAIEX）的 beta；沒有基準資料時為 `None`
+    pub beta: Option<f64>,
+    /// 與基準日報酬的相關係數
+    pub correlation: Option<f64>,
+    pub drawdown: Drawdown,
+}
+
+/// 簡單日報酬
+pub fn daily_returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|w| w[1] / w[0] - 1.0).collect()
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// 樣本共變異數（分母 n - 1）
+pub fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - mean_a) * (y - mean_b))
+        .sum::<f64>()
+        / (a.len() as f64 - 1.0)
+}
+
+/// 樣本標準差（分母 n - 1）
+pub fn std_dev(values: &[f64]) -> f64 {
+    covariance(values, values).sqrt()
+}
+
+/// 相關係數；任一邊沒有波動時為 `None`
+pub fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let denominator = std_dev(a) * std_dev(b);
+    (denominator > 0.0).then(|| covariance(a, b) / denominator)
+}
+
+/// 以 0 為門檻的樣本下檔標準差（分母 n - 1，與 `std_dev` 一致）
+pub fn downside_deviation(returns: &[f64]) -> f64 {
+    (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / (returns.len() as f64 - 1.0)).sqrt()
+}
+
+/// 計算價值序列的最大回撤
+pub fn max_drawdown(values: &[(NaiveDate, f64)]) -> Option<Drawdown> {
+    let (first_date, first_value) = *values.first()?;
+    let mut peak = (first_date, first_value);
+    let mut worst = Drawdown {
+        max_drawdown: 0.0,
+        peak_date: first_date,
+        trough_date: first_date,
+    };
+
+    for &(date, value) in values {
+        if value > peak.1 {
+            peak = (date, value);
+        }
+        let drawdown = value / peak.1 - 1.0;
+        if drawdown < worst.max_drawdown {
+            worst = Drawdown {
+                max_drawdown: drawdown,
+                peak_date: peak.0,
+                trough_date: date,
+            };
+        }
+    }
+
+    Some(worst)
+}
+
+/// 計算一條價值序列（依日期由舊到新）的風險指標
+///
+/// `benchmark` 為基準的收盤序列，只有兩邊在相鄰兩個交易日都有資料時才納入 beta 與相關係數計算。
+pub fn compute(
+    values: &[(NaiveDate, f64)],
+    benchmark: Option<&[(NaiveDate, f64)]>,
+) -> Result<RiskMetrics, AppError> {
+    if values.len() < 3 {
+        return Err(AppError::bad_request("資料不足，至少需要三個交易日"));
+    }
+    if values.iter().any(|(_, v)| *v <= 0.0) {
+        return Err(AppError::bad_request("價值序列必須為正數"));
+    }
+
+    let drawdown = max_drawdown(values).ok_or_else(|| AppError::bad_request("資料不足"))?;
+    let closes: Vec<f64> = values.iter().map(|(_, v)| *v).collect();
+    let returns = daily_returns(&closes);
+    let annualize = TRADING_DAYS_PER_YEAR.sqrt();
+
+    let downside = downside_deviation(&returns);
+
+    let (beta, correlation) = match benchmark {
+        Some(benchmark) => {
+            let benchmark: HashMap<NaiveDate, f64> = benchmark.iter().copied().collect();
+            let (asset, market): (Vec<f64>, Vec<f64>) = values
+                .windows(2)
+                .filter_map(|w| {
+                    let (prev, curr) = (benchmark.get(&w[0].0)?, benchmark.get(&w[1].0)?);
+                    Some((w[1].1 / w[0].1 - 1.0, curr / prev - 1.0))
+                })
+                .unzip();
+
+            if asset.len() < 2 {
+                (None, None)
+            } else {
+                let market_variance = covariance(&market, &market);
+                let beta =
+                    (market_variance > 0.0).then(|| covariance(&asset, &market) / market_variance);
+                (beta, self::correlation(&asset, &market))
+            }
+        }
+        None => (None, None),
+    };
+
+    Ok(RiskMetrics {
+        start_date: values[0].0,
+        end_date: values[values.len() - 1].0,
+        observations: returns.len(),
+        annualized_volatility: std_dev(&returns) * annualize,
+        downside_deviation: downside * annualize,
+        beta,
+        correlation,
+        drawdown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn dispersion_uses_sample_divisor() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        // 離均差平方和 32，樣本變異數 32 / 7
+        assert_close(covariance(&values, &values), 32.0 / 7.0);
+        assert_close(std_dev(&values), (32.0_f64 / 7.0).sqrt());
+
+        // 0.01 與 0.03 不計入，負報酬平方和 0.0005
+        let returns = [0.01, -0.02, 0.03, -0.01];
+        assert_close(downside_deviation(&returns), (0.0005_f64 / 3.0).sqrt());
+    }
+
+    #[test]
+    fn correlation_and_beta_are_consistent() {
+        let market = [0.01, -0.02, 0.015, 0.005, -0.01];
+        let doubled: Vec<f64> = market.iter().map(|r| r * 2.0).collect();
+        let inverse: Vec<f64> = market.iter().map(|r| -r).collect();
+
+        assert_close(correlation(&doubled, &market).unwrap(), 1.0);
+        assert_close(correlation(&inverse, &market).unwrap(), -1.0);
+        assert_close(
+            covariance(&doubled, &market) / covariance(&market, &market),
+            2.0,
+        );
+        assert_eq!(correlation(&[0.0, 0.0, 0.0], &market[..3]), None);
+    }
+
+    #[test]
+    fn compute_annualizes_sample_statistics() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let values = [
+            (date(2), 100.0),
+            (date(3), 110.0),
+            (date(4), 99.0),
+            (date(5), 99.0),
+        ];
+        let benchmark = [
+            (date(2), 50.0),
+            (date(3), 52.5),
+            (date(4), 49.875),
+            (date(5), 49.875),
+        ];
+
+        let metrics = compute(&values, Some(&benchmark)).unwrap();
+        let returns = [0.1, -0.1, 0.0];
+        let annualize = TRADING_DAYS_PER_YEAR.sqrt();
+        assert_eq!(metrics.observations, 3);
+        assert_close(metrics.annualized_volatility, std_dev(&returns) * annualize);
+        assert_close(metrics.annualized_volatility, 0.1 * annualize);
+        assert_close(
+            metrics.downside_deviation,
+            (0.01_f64 / 2.0).sqrt() * annualize,
+        );
+        // 基準每日報酬剛好是一半
+        assert_close(metrics.beta.unwrap(), 2.0);
+        assert_close(metrics.correlation.unwrap(), 1.0);
+        assert_close(metrics.drawdown.max_drawdown, -0.1);
+        assert_eq!(metrics.drawdown.peak_date, date(3));
+        assert_eq!(metrics.drawdown.trough_date, date(4));
+    }
+}
@@ -0,0 +1,369 @@
+// src/services/series.rs
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDate;
+use redis::aio::ConnectionManager;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    services::{basket, cache},
+};
+
+/// 單日 K 線資料；缺少開高低價時以收盤價補上
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Candle {
+    pub trade_date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: Option<i64>,
+}
+
+/// 一檔標的的日 K 線序列（依日期由舊到新）
+#[derive(Debug, Serialize)]
+pub struct Series {
+    pub code: String,
+    pub name: String,
+    pub candles: Vec<Candle>,
+}
+
+impl Series {
+    /// 依日期排列的收盤價
+    pub fn closes(&self) -> Vec<(NaiveDate, f64)> {
+        self.candles
+            .iter()
+            .map(|c| (c.trade_date, c.close))
+            .collect()
+    }
+}
+
+/// 將多檔標的的收盤價以「共同交易日」對齊，任何一檔缺資料的日期都會略過
+///
+/// 回傳共同日期，以及每檔標的在這些日期的收盤價（順序與輸入相同）。
+pub fn align_closes(series: &[Series]) -> (Vec<NaiveDate>, Vec<Vec<f64>>) {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for s in series {
+        for candle in &s.candles {
+            *counts.entry(candle.trade_date).or_default() += 1;
+        }
+    }
+    let dates: Vec<NaiveDate> = counts
+        .into_iter()
+        .filter(|(_, count)| *count == series.len())
+        .map(|(date, _)| date)
+        .collect();
+
+    let closes = series
+        .iter()
+        .map(|s| {
+            let by_date: HashMap<NaiveDate, f64> = s.closes().into_iter().collect();
+            dates.iter().map(|d| by_date[d]).collect()
+        })
+        .collect();
+
+    (dates, closes)
+}
+
+/// 時間序列的來源，讓圖表與指標可以共用同一套程式
+#[derive(Debug, Clone)]
+pub enum SeriesSource {
+    /// 個股（stock_day_all），值為證券代號
+    Stock(String),
+    /// 指數（index_day），值為指數名稱
+    Index(String),
+    /// 自訂組合的合成指數，只有建立者 `owner` 可以讀取
+    Basket { id: i32, owner: Uuid },
+}
+
+impl SeriesSource {
+    /// 解析接受證券代號的參數：`basket:{id}` 代表自訂組合，其餘視為個股
+    ///
+    /// 組合只有建立者 `viewer` 讀得到，未登入時一律回傳 404，不透露組合是否存在。
+    pub fn from_stock_code(
+        code: impl Into<String>,
+        viewer: Option<Uuid>,
+    ) -> Result<Self, AppError> {
+        let code = code.into();
+        let Some(id) = code.strip_prefix("basket:") else {
+            return Ok(Self::Stock(code));
+        };
+        let id = id
+            .parse()
+            .map_err(|_| AppError::bad_request(format!("無效的組合代號 {}", code)))?;
+        match viewer {
+            Some(owner) => Ok(Self::Basket { id, owner }),
+            None => Err(AppError::not_found(format!("找不到組合 {}", id))),
+        }
+    }
+
+    /// 用於快取鍵的識別字串
+    pub fn key(&self) -> String {
+        match self {
+            Self::Stock(code) => format!("stock:{}", code),
+            Self::Index(name) => format!("index:{}", name),
+            Self::Basket { id, .. } => format!("basket:{}", id),
+        }
+    }
+
+    /// 讀取日 K 線，取區間內最新的 `limit` 個交易日
+    pub async fn load(
+        &self,
+        db: &PgPool,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        limit: i64,
+    ) -> Result<Series, AppError> {
+        match self {
+            Self::Stock(code) => load_stock_series(db, code, from, to, limit).await,
+            Self::Index(name) => load_index_series(db, name, from, to, limit).await,
+            Self::Basket { id, owner } => {
+                basket::load_series(db, *owner, *id, from, to, limit).await
+            }
+        }
+    }
+
+    /// 讀取日 K 線，另外回傳第一筆之前最多 `warmup` 個交易日的收盤價
+    ///
+    /// 均線等需要前置資料的指標用這些收盤價暖機，區間開頭的數值才不會是空的。
+    pub async fn load_with_warmup(
+        &self,
+        db: &PgPool,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        limit: i64,
+        warmup: usize,
+    ) -> Result<(Series, Vec<f64>), AppError> {
+        let mut series = self.load(db, None, to, limit + warmup as i64).await?;
+        let (candles, warmup) =
+            split_warmup(std::mem::take(&mut series.candles), from, limit, warmup);
+        series.candles = candles;
+        Ok((series, warmup))
+    }
+
+    /// 快取版本：資料來源最後一次匯入的交易日與寫入版本，組合另外加上定義的修改時間
+    pub async fn cache_version(
+        &self,
+        db: &PgPool,
+        redis: &ConnectionManager,
+    ) -> Result<String, AppError> {
+        let table = match self {
+            Self::Stock(_) | Self::Basket { .. } => "stock_day_all",
+            Self::Index(_) => "index_day",
+        };
+        let date = sqlx::query_scalar::<_, Option<NaiveDate>>(&format!(
+            "SELECT MAX(trade_date) FROM {}",
+            table
+        ))
+        .fetch_one(db)
+        .await?
+        .map(|d| d.to_string())
+        .unwrap_or_default();
+        let date = format!("{}.{}", date, cache::version(redis, table).await);
+
+        match self {
+            Self::Basket { id, owner } => {
+                let updated_at = basket::version(db, *owner, *id)
+                    .await?
+                    .ok_or_else(|| AppError::not_found(format!("找不到組合 {}", id)))?;
+                Ok(format!("{}:{}", date, updated_at.timestamp_millis()))
+            }
+            _ => Ok(date),
+        }
+    }
+}
+
+/// 把不限起始日讀出的 K 線切成區間內最新的 `limit` 筆，以及之前最多 `warmup` 筆的收盤價
+fn split_warmup(
+    mut candles: Vec<Candle>,
+    from: Option<NaiveDate>,
+    limit: i64,
+    warmup: usize,
+) -> (Vec<Candle>, Vec<f64>) {
+    let in_range = candles
+        .iter()
+        .position(|c| from.is_none_or(|from| c.trade_date >= from))
+        .unwrap_or(candles.len());
+    let start = in_range.max(candles.len().saturating_sub(limit.max(0) as usize));
+    let visible = candles.split_off(start);
+    let warmup = candles[candles.len().saturating_sub(warmup)..]
+        .iter()
+        .map(|c| c.close)
+        .collect();
+    (visible, warmup)
+}
+
+/// 讀取個股日 K 線，取區間內最新的 `limit` 個交易日
+pub async fn load_stock_series(
+    db: &PgPool,
+    code: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+) -> Result<Series, AppError> {
+    let candles = sqlx::query_as::<_, Candle>(
+        r#"
+        SELECT * FROM (
+            SELECT trade_date,
+                   COALESCE(open_price, close_price)::float8 AS open,
+                   COALESCE(high_price, close_price)::float8 AS high,
+                   COALESCE(low_price, close_price)::float8 AS low,
+                   close_price::float8 AS close,
+                   trade_volume AS volume
+            FROM stock_day_all
+            WHERE stock_code = $1
+              AND close_price IS NOT NULL
+              AND ($2::date IS NULL OR trade_date >= $2)
+              AND ($3::date IS NULL OR trade_date <= $3)
+            ORDER BY trade_date DESC
+            LIMIT $4
+        ) t
+        ORDER BY trade_date
+        "#,
+    )
+    .bind(code)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let name = sqlx::query_scalar::<_, String>(
+        "SELECT stock_name FROM stock_day_all WHERE stock_code = $1 ORDER BY trade_date DESC LIMIT 1",
+    )
+    .bind(code)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("找不到股票代號 {}", code)))?;
+
+    Ok(Series {
+        code: code.to_string(),
+        name,
+        candles,
+    })
+}
+
+/// 讀取指數日資料；MI_INDEX 只有收盤指數，開高低都以收盤補上
+pub async fn load_index_series(
+    db: &PgPool,
+    name: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+) -> Result<Series, AppError> {
+    let candles = sqlx::query_as::<_, Candle>(
+        r#"
+        SELECT * FROM (
+            SELECT trade_date,
+                   close_value::float8 AS open,
+                   close_value::float8 AS high,
+                   close_value::float8 AS low,
+                   close_value::float8 AS close,
+                   NULL::bigint AS volume
+            FROM index_day
+            WHERE index_name = $1
+              AND ($2::date IS NULL OR trade_date >= $2)
+              AND ($3::date IS NULL OR trade_date <= $3)
+            ORDER BY trade_date DESC
+            LIMIT $4
+        ) t
+        ORDER BY trade_date
+        "#,
+    )
+    .bind(name)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    if candles.is_empty() {
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM index_day WHERE index_name = $1)",
+        )
+        .bind(name)
+        .fetch_one(db)
+        .await?;
+        if !exists {
+            return Err(AppError::not_found(format!("找不到指數 {}", name)));
+        }
+    }
+
+    Ok(Series {
+        code: name.to_string(),
+        name: name.to_string(),
+        candles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn candles(days: std::ops::RangeInclusive<u32>) -> Vec<Candle> {
+        days.map(|day| Candle {
+            trade_date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            open: day as f64,
+            high: day as f64,
+            low: day as f64,
+            close: day as f64,
+            volume: None,
+        })
+        .collect()
+    }
+
+    fn days(candles: &[Candle]) -> Vec<u32> {
+        candles.iter().map(|c| c.close as u32).collect()
+    }
+
+    #[test]
+    fn warmup_comes_right_before_the_range() {
+        let from = NaiveDate::from_ymd_opt(2024, 1, 10);
+
+        // 起始日限制了範圍：前面留下暖機資料
+        let (visible, warmup) = split_warmup(candles(1..=20), from, 100, 4);
+        assert_eq!(days(&visible), (10..=20).collect::<Vec<_>>());
+        assert_eq!(warmup, vec![6.0, 7.0, 8.0, 9.0]);
+
+        // 筆數限制了範圍：暖機資料是被 limit 截掉的那幾天
+        let (visible, warmup) = split_warmup(candles(1..=20), from, 5, 3);
+        assert_eq!(days(&visible), vec![16, 17, 18, 19, 20]);
+        assert_eq!(warmup, vec![13.0, 14.0, 15.0]);
+
+        // 之前的資料不夠時有多少給多少
+        let (visible, warmup) = split_warmup(candles(8..=12), from, 100, 4);
+        assert_eq!(days(&visible), vec![10, 11, 12]);
+        assert_eq!(warmup, vec![8.0, 9.0]);
+
+        let (visible, warmup) = split_warmup(candles(1..=5), None, 100, 4);
+        assert_eq!(days(&visible), vec![1, 2, 3, 4, 5]);
+        assert!(warmup.is_empty());
+    }
+
+    #[test]
+    fn basket_codes_need_a_viewer() {
+        let owner = Uuid::new_v4();
+        assert!(matches!(
+            SeriesSource::from_stock_code("2330", None),
+            Ok(SeriesSource::Stock(code)) if code == "2330"
+        ));
+        assert!(matches!(
+            SeriesSource::from_stock_code("basket:7", Some(owner)),
+            Ok(SeriesSource::Basket { id: 7, owner: o }) if o == owner
+        ));
+
+        let err = SeriesSource::from_stock_code("basket:7", None)
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::NOT_FOUND);
+        let err = SeriesSource::from_stock_code("basket:x", Some(owner))
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,394 @@
+// src/services/session.rs
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::{HeaderMap, header};
+use chrono::{DateTime, Duration, Utc};
+use ipnet::IpNet;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{config::AuthConfig, error::AppError, utils::token::random_token};
+
+/// 距離上次更新超過這個秒數才寫回 last_seen_at，避免每個請求都寫入 Valkey
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// 存在 Valkey 的 session 內容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// 絕對到期時間，不論是否持續使用都會在此時失效
+    pub expires_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// 建立 session 時記錄的裝置資訊
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    /// 連線來自可信任的反向代理時採用 `X-Forwarded-For`，否則使用連線來源位址
+    ///
+    /// 由右往左略過可信任代理自己加上的位址，取第一個不可信任的位址；
+    /// 最左邊的值可由用戶端任意偽造，不能直接採用。
+    pub fn from_request(
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+        trusted_proxies: &[IpNet],
+    ) -> Self {
+        let header_text = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+        let forwarded = || {
+            let mut client = None;
+            for hop in header_text("x-forwarded-for")?.split(',').rev() {
+                let ip = hop.trim().parse::<IpAddr>().ok()?;
+                client = Some(ip);
+                if !is_trusted(&ip) {
+                    break;
+                }
+            }
+            client
+        };
+
+        let peer_ip = peer.map(|addr| addr.ip());
+        let ip = peer_ip
+            .filter(|ip| is_trusted(ip))
+            .and_then(|_| forwarded())
+            .or(peer_ip);
+
+        Self {
+            ip: ip.map(|ip| ip.to_string()),
+            user_agent: header_text(header::USER_AGENT.as_str()).map(str::to_string),
+        }
+    }
+}
+
+/// 列出 session 時的項目
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    /// session 的識別碼（cookie 值的雜湊，不能拿來登入）
+    pub id: String,
+    /// 是否為發出這個請求的 session
+    pub current: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// 已驗證的 session
+#[derive(Debug, Clone)]
+pub struct ActiveSession {
+    pub id: String,
+    pub session: Session,
+}
+
+/// cookie 裡放的是隨機 token，Valkey 只存它的雜湊，列出 session 時也只顯示雜湊
+fn session_id(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn key(id: &str) -> String {
+    format!("session:{}", id)
+}
+
+fn user_index_key(user_id: Uuid) -> String {
+    format!("user_sessions:{}", user_id)
+}
+
+/// session 在 Valkey 的剩餘存活秒數：閒置逾時與絕對到期取較短者
+fn ttl_secs(config: &AuthConfig, session: &Session, now: DateTime<Utc>) -> u64 {
+    let remaining = (session.expires_at - now).num_seconds().max(1) as u64;
+    config.session_idle_timeout.as_secs().min(remaining)
+}
+
+async fn save(
+    redis: &ConnectionManager,
+    config: &AuthConfig,
+    id: &str,
+    session: &Session,
+) -> Result<(), AppError> {
+    let payload = serde_json::to_string(session)
+        .map_err(|e| AppError::internal_error(format!("序列化 session 失敗: {}", e)))?;
+    let mut conn = redis.clone();
+    conn.set_ex::<_, _, ()>(key(id), payload, ttl_secs(config, session, Utc::now()))
+        .await?;
+    Ok(())
+}
+
+async fn load(redis: &ConnectionManager, id: &str) -> Result<Option<Session>, AppError> {
+    let mut conn = redis.clone();
+    let payload: Option<String> = conn.get(key(id)).await?;
+    Ok(payload.and_then(|payload| serde_json::from_str(&payload).ok()))
+}
+
+async fn remove(redis: &ConnectionManager, user_id: Uuid, ids: &[String]) -> Result<(), AppError> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let keys: Vec<String> = ids.iter().map(|id| key(id)).collect();
+    let mut conn = redis.clone();
+    redis::pipe()
+        .del(keys)
+        .ignore()
+        .srem(user_index_key(user_id), ids)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// 建立 session，回傳寫入 cookie 的 token
+pub async fn create(
+    redis: &ConnectionManager,
+    config: &AuthConfig,
+    user_id: Uuid,
+    client: ClientInfo,
+) -> Result<String, AppError> {
+    let token = random_token(32);
+    let id = session_id(&token);
+    let now = Utc::now();
+    let session = Session {
+        user_id,
+        created_at: now,
+        last_seen_at: now,
+        expires_at: now + Duration::seconds(config.session_max_age.as_secs() as i64),
+        ip: client.ip,
+        user_agent: client.user_agent,
+    };
+    save(redis, config, &id, &session).await?;
+
+    // 使用者的 session 索引，存活時間至少涵蓋最新一個 session 的絕對到期時間
+    let index = user_index_key(user_id);
+    let mut conn = redis.clone();
+    redis::pipe()
+        .sadd(&index, &id)
+        .ignore()
+        .expire(&index, config.session_max_age.as_secs() as i64)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await?;
+
+    Ok(token)
+}
+
+/// 以 cookie 的 token 取得 session，並延長閒置期限（sliding expiry）
+///
+/// 不存在、閒置逾時或超過絕對到期時間時回傳 `None`。
+pub async fn authenticate(
+    redis: &ConnectionManager,
+    config: &AuthConfig,
+    token: &str,
+) -> Result<Option<ActiveSession>, AppError> {
+    let id = session_id(token);
+    let Some(mut session) = load(redis, &id).await? else {
+        return Ok(None);
+    };
+
+    let now = Utc::now();
+    if now >= session.expires_at {
+        remove(redis, session.user_id, std::slice::from_ref(&id)).await?;
+        return Ok(None);
+    }
+    if (now - session.last_seen_at).num_seconds() >= TOUCH_INTERVAL_SECS {
+        session.last_seen_at = now;
+        save(redis, config, &id, &session).await?;
+    }
+
+    Ok(Some(ActiveSession { id, session }))
+}
+
+/// 列出使用者所有有效的 session，最近使用的排在前面
+pub async fn list(
+    redis: &ConnectionManager,
+    user_id: Uuid,
+    current_id: Option<&str>,
+) -> Result<Vec<SessionSummary>, AppError> {
+    let mut conn = redis.clone();
+    let ids: Vec<String> = conn.smembers(user_index_key(user_id)).await?;
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let keys: Vec<String> = ids.iter().map(|id| key(id)).collect();
+    let payloads: Vec<Option<String>> =
+        redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+    let now = Utc::now();
+    let mut sessions = Vec::new();
+    let mut stale = Vec::new();
+    for (id, payload) in ids.into_iter().zip(payloads) {
+        let session = payload
+            .and_then(|payload| serde_json::from_str::<Session>(&payload).ok())
+            .filter(|session| session.user_id == user_id && session.expires_at > now);
+        match session {
+            Some(session) => sessions.push(SessionSummary {
+                current: current_id == Some(id.as_str()),
+                id,
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+                ip: session.ip,
+                user_agent: session.user_agent,
+            }),
+            None => stale.push(id),
+        }
+    }
+    // 已過期的 session 順便從索引移除
+    remove(redis, user_id, &stale).await?;
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+    Ok(sessions)
+}
+
+/// 撤銷使用者的某個 session，找不到（或不屬於該使用者）時回傳 false
+pub async fn revoke(redis: &ConnectionManager, user_id: Uuid, id: &str) -> Result<bool, AppError> {
+    match load(redis, id).await? {
+        Some(session) if session.user_id == user_id => {
+            remove(redis, user_id, &[id.to_string()]).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// 撤銷使用者的所有 session，可保留指定的一個（通常是目前的 session），回傳撤銷數量
+pub async fn revoke_all(
+    redis: &ConnectionManager,
+    user_id: Uuid,
+    keep: Option<&str>,
+) -> Result<usize, AppError> {
+    let mut conn = redis.clone();
+    let ids: Vec<String> = conn.smembers(user_index_key(user_id)).await?;
+    let ids: Vec<String> = ids
+        .into_iter()
+        .filter(|id| Some(id.as_str()) != keep)
+        .collect();
+    remove(redis, user_id, &ids).await?;
+    Ok(ids.len())
+}
+
+/// 以 cookie 的 token 刪除 session（登出）
+pub async fn destroy(redis: &ConnectionManager, token: &str) -> Result<(), AppError> {
+    let id = session_id(token);
+    if let Some(session) = load(redis, &id).await? {
+        remove(redis, session.user_id, &[id]).await?;
+    }
+    Ok(())
+}
+
+/// 從 Cookie 標頭取出 session token
+pub fn token_from_headers(headers: &HeaderMap, config: &AuthConfig) -> Option<String> {
+    cookie_from_headers(headers, &config.session_cookie)
+}
+
+/// 從 Cookie 標頭取出指定名稱的 cookie，空值視為沒有
+pub fn cookie_from_headers(headers: &HeaderMap, cookie_name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// 設定 session cookie 的 `Set-Cookie` 值，cookie 的期限與 session 的絕對到期時間相同
+pub fn cookie(config: &AuthConfig, token: &str) -> String {
+    build_cookie(
+        config,
+        &config.session_cookie,
+        "/",
+        token,
+        config.session_max_age.as_secs(),
+    )
+}
+
+/// 清除 session cookie 的 `Set-Cookie` 值
+pub fn clear_cookie(config: &AuthConfig) -> String {
+    build_cookie(config, &config.session_cookie, "/", "", 0)
+}
+
+/// 組出 HttpOnly、SameSite=Lax 的 `Set-Cookie` 值，`Secure` 依 `COOKIE_SECURE` 設定
+pub fn build_cookie(
+    config: &AuthConfig,
+    name: &str,
+    path: &str,
+    value: &str,
+    max_age: u64,
+) -> String {
+    let secure = if config.cookie_secure { "; Secure" } else { "" };
+    format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        name, value, path, max_age, secure
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(forwarded_for: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        headers
+    }
+
+    fn ip(headers: &HeaderMap, peer: &str, trusted: &[&str]) -> Option<String> {
+        let trusted: Vec<IpNet> = trusted.iter().map(|net| net.parse().unwrap()).collect();
+        ClientInfo::from_request(headers, Some(peer.parse().unwrap()), &trusted).ip
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peer() {
+        let headers = headers("203.0.113.7");
+        assert_eq!(
+            ip(&headers, "198.51.100.1:5000", &[]),
+            Some("198.51.100.1".into())
+        );
+        assert_eq!(
+            ip(&headers, "198.51.100.1:5000", &["10.0.0.0/8"]),
+            Some("198.51.100.1".into())
+        );
+    }
+
+    #[test]
+    fn takes_rightmost_untrusted_hop_behind_trusted_proxy() {
+        // 用戶端自己塞了 1.1.1.1，代理再附加真正的來源位址
+        let headers = headers("1.1.1.1, 203.0.113.7, 10.0.0.2");
+        assert_eq!(
+            ip(&headers, "10.0.0.1:5000", &["10.0.0.0/8"]),
+            Some("203.0.113.7".into())
+        );
+    }
+
+    #[test]
+    fn falls_back_to_peer_when_forwarded_for_is_missing_or_invalid() {
+        let trusted = &["10.0.0.1/32"];
+        assert_eq!(
+            ip(&HeaderMap::new(), "10.0.0.1:5000", trusted),
+            Some("10.0.0.1".into())
+        );
+        assert_eq!(
+            ip(&headers("not-an-ip"), "10.0.0.1:5000", trusted),
+            Some("10.0.0.1".into())
+        );
+    }
+}
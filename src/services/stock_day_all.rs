@@ -0,0 +1,892 @@
+// src/services/stock_day_all.rs
+
+use std::collections::{HashMap, HashSet};
+
+use axum::http::StatusCode;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::{
+    config::DataQualityConfig,
+    error::AppError,
+    services::{
+        cache,
+        data_quality::{self, QualityIssue},
+        market_summary,
+        payload_archive::{self, ArchivedPayload, RawPayload},
+        price_limit::{self, PriceLimitFlags},
+    },
+    state::AppState,
+};
+
+/// TWSE 上市個股日成交資訊
+const STOCK_DAY_ALL_URL: &str = "https://www.twse.com.tw/exchangeReport/STOCK_DAY_ALL";
+/// 原始回應封存時使用的來源名稱
+const ARCHIVE_SOURCE: &str = "stock_day_all";
+/// COPY 寫入的欄位（暫存表與 stock_day_all 共用）
+const COPY_COLUMNS: &str = "trade_date, stock_code, stock_name, trade_volume, trade_amount, \
+     open_price, high_price, low_price, close_price, price_change, transaction_count, \
+     limit_up, limit_down, touched_limit_up, touched_limit_down, halted";
+/// COPY 每次送出的資料大小
+const COPY_CHUNK_BYTES: usize = 1 << 20;
+/// 重新處理封存資料時，累積到這個筆數就寫入一次
+const REPROCESS_BATCH_ROWS: usize = 50_000;
+/// 重新處理時每次從封存讀取（並解壓）的日期範圍，避免多年回補一次載入所有原始回應
+const REPROCESS_WINDOW_DAYS: i64 = 31;
+
+#[derive(Deserialize, Debug)]
+pub struct TwseApiResponse {
+    #[serde(default)]
+    pub stat: String,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub data: Vec<Vec<String>>,
+}
+
+/// 整理後的一筆日成交資料
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StockDayRow {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub trade_volume: Option<i64>,
+    pub trade_amount: Option<i64>,
+    pub open_price: Option<f64>,
+    pub high_price: Option<f64>,
+    pub low_price: Option<f64>,
+    pub close_price: Option<f64>,
+    pub price_change: Option<f64>,
+    pub transaction_count: Option<i32>,
+}
+
+/// 匯入結果摘要
+#[derive(Debug, Serialize)]
+pub struct IngestSummary {
+    pub trade_date: NaiveDate,
+    /// 從 TWSE 回應中解析出的筆數
+    pub parsed_rows: usize,
+    /// 實際寫入的筆數（已存在且未要求取代的資料不會計入）
+    pub inserted_rows: u64,
+    /// 取代模式下先刪除的舊資料筆數
+    pub deleted_rows: u64,
+    /// 資料品質檢查發現的問題數
+    pub quality_issues: usize,
+    /// 因 error 等級問題而未寫入的筆數
+    pub rejected_rows: usize,
+}
+
+/// 重新處理封存資料的結果
+#[derive(Debug, Serialize)]
+pub struct ReprocessResult {
+    pub payload_id: i64,
+    pub trade_date: NaiveDate,
+    /// 成功時的匯入摘要
+    pub summary: Option<IngestSummary>,
+    /// 失敗原因
+    pub error: Option<String>,
+}
+
+/// 向 TWSE 取得日成交資訊原始回應，`date` 為 `None` 時取最新一個交易日
+pub async fn fetch(state: &AppState, date: Option<NaiveDate>) -> Result<RawPayload, AppError> {
+    let mut request = state.http_client.get(STOCK_DAY_ALL_URL);
+    if let Some(date) = date {
+        request = request.query(&[
+            ("response", "json".to_string()),
+            ("date", date.format("%Y%m%d").to_string()),
+        ]);
+    }
+
+    let resp = request.send().await?;
+    let url = resp.url().to_string();
+    let status_code = resp.status().as_u16();
+    // 非 2xx 的回應也照樣回傳並封存，交給解析階段判斷
+    let body = resp.bytes().await?.to_vec();
+
+    Ok(RawPayload {
+        url,
+        status_code,
+        body,
+    })
+}
+
+/// 將原始回應解析為 TWSE 回應結構
+pub fn decode(body: &[u8]) -> Result<TwseApiResponse, AppError> {
+    serde_json::from_slice(body)
+        .map_err(|e| AppError::with_source(StatusCode::BAD_GATEWAY, "TWSE 回應格式錯誤", e))
+}
+
+/// 解析 TWSE 回應，回傳交易日與資料列
+pub fn parse(resp: &TwseApiResponse) -> Result<(NaiveDate, Vec<StockDayRow>), AppError> {
+    let date = resp.date.as_deref().ok_or_else(|| {
+        AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("TWSE 回應沒有日期: {}", resp.stat),
+        )
+    })?;
+    let trade_date = NaiveDate::parse_from_str(date, "%Y%m%d")?;
+
+    let parse_i64 = |s: &str| s.replace(",", "").parse::<i64>().ok();
+    let parse_f64 = |s: &str| s.replace(",", "").parse::<f64>().ok();
+
+    let mut rows = Vec::with_capacity(resp.data.len());
+
+    for row in &resp.data {
+        if row.len() < 10 {
+            continue;
+        }
+
+        if row[0].trim().is_empty() {
+            continue;
+        }
+
+        // 暫停交易或全日無成交時價格欄位為 "--"，保留該列並以 NULL 寫入價格
+        rows.push(StockDayRow {
+            stock_code: row[0].clone(),
+            stock_name: row[1].clone(),
+            trade_volume: parse_i64(&row[2]),
+            trade_amount: parse_i64(&row[3]),
+            open_price: parse_f64(&row[4]),
+            high_price: parse_f64(&row[5]),
+            low_price: parse_f64(&row[6]),
+            close_price: parse_f64(&row[7]),
+            price_change: parse_f64(&row[8]),
+            transaction_count: Some(parse_i64(&row[9]).unwrap_or(0) as i32),
+        });
+    }
+
+    Ok((trade_date, rows))
+}
+
+/// 一個交易日檢查完、等待寫入的資料
+struct PreparedDay {
+    trade_date: NaiveDate,
+    parsed_rows: usize,
+    rows: Vec<StockDayRow>,
+    flags: Vec<PriceLimitFlags>,
+    issues: Vec<QualityIssue>,
+    rejected_codes: HashSet<String>,
+}
+
+impl PreparedDay {
+    /// 資料品質檢查、排除有 error 的資料列，並計算漲跌停標記
+    fn new(
+        trade_date: NaiveDate,
+        mut rows: Vec<StockDayRow>,
+        previous_closes: &HashMap<String, f64>,
+        config: &DataQualityConfig,
+    ) -> Self {
+        let parsed_rows = rows.len();
+        let issues = data_quality::validate(&rows, previous_closes, config);
+        let rejected_codes = data_quality::rejected_codes(&issues, config);
+        rows.retain(|row| !rejected_codes.contains(&row.stock_code));
+        let flags = price_limit::classify_all(&rows, previous_closes, config.price_limit_pct);
+
+        Self {
+            trade_date,
+            parsed_rows,
+            rows,
+            flags,
+            issues,
+            rejected_codes,
+        }
+    }
+}
+
+/// 將資料列與漲跌停標記寫入 stock_day_all，已存在的 (trade_date, stock_code) 會略過
+///
+/// 筆數超過 `bulk_threshold` 時改用 COPY 匯入暫存表再合併，否則用 UNNEST 一次寫入。
+/// 回傳每個交易日實際寫入的筆數。
+async fn insert_rows(
+    conn: &mut PgConnection,
+    days: &[PreparedDay],
+    bulk_threshold: usize,
+) -> Result<HashMap<NaiveDate, u64>, AppError> {
+    let total: usize = days.iter().map(|d| d.rows.len()).sum();
+    let counts = if total > bulk_threshold {
+        copy_rows(conn, days).await?
+    } else {
+        unnest_rows(conn, days, total).await?
+    };
+
+    Ok(counts
+        .into_iter()
+        .map(|(date, count)| (date, count as u64))
+        .collect())
+}
+
+async fn unnest_rows(
+    conn: &mut PgConnection,
+    days: &[PreparedDay],
+    total: usize,
+) -> Result<Vec<(NaiveDate, i64)>, AppError> {
+    // 收集欄位資料（每欄一個 Vec）
+    let mut trade_dates = Vec::with_capacity(total);
+    let mut stock_codes = Vec::with_capacity(total);
+    let mut stock_names = Vec::with_capacity(total);
+    let mut trade_volumes = Vec::with_capacity(total);
+    let mut trade_amounts = Vec::with_capacity(total);
+    let mut open_prices = Vec::with_capacity(total);
+    let mut high_prices = Vec::with_capacity(total);
+    let mut low_prices = Vec::with_capacity(total);
+    let mut close_prices = Vec::with_capacity(total);
+    let mut price_changes = Vec::with_capacity(total);
+    let mut transaction_counts = Vec::with_capacity(total);
+    let mut limit_ups = Vec::with_capacity(total);
+    let mut limit_downs = Vec::with_capacity(total);
+    let mut touched_limit_ups = Vec::with_capacity(total);
+    let mut touched_limit_downs = Vec::with_capacity(total);
+    let mut halteds = Vec::with_capacity(total);
+
+    for day in days {
+        for (row, flag) in day.rows.iter().zip(&day.flags) {
+            trade_dates.push(day.trade_date);
+            stock_codes.push(row.stock_code.as_str());
+            stock_names.push(row.stock_name.as_str());
+            trade_volumes.push(row.trade_volume);
+            trade_amounts.push(row.trade_amount);
+            open_prices.push(row.open_price);
+            high_prices.push(row.high_price);
+            low_prices.push(row.low_price);
+            close_prices.push(row.close_price);
+            price_changes.push(row.price_change);
+            transaction_counts.push(row.transaction_count);
+            limit_ups.push(flag.limit_up);
+            limit_downs.push(flag.limit_down);
+            touched_limit_ups.push(flag.touched_limit_up);
+            touched_limit_downs.push(flag.touched_limit_down);
+            halteds.push(flag.halted);
+        }
+    }
+
+    let query = r#"
+        WITH inserted AS (
+            INSERT INTO stock_day_all (
+                trade_date, stock_code, stock_name,
+                trade_volume, trade_amount, open_price,
+                high_price, low_price, close_price,
+                price_change, transaction_count,
+                limit_up, limit_down, touched_limit_up, touched_limit_down, halted
+            )
+            SELECT * FROM UNNEST(
+                $1::date[], $2::text[], $3::text[],
+                $4::bigint[], $5::bigint[], $6::double precision[],
+                $7::double precision[], $8::double precision[], $9::double precision[],
+                $10::double precision[], $11::int[],
+                $12::bool[], $13::bool[], $14::bool[], $15::bool[], $16::bool[]
+            )
+            ON CONFLICT (trade_date, stock_code) DO NOTHING
+            RETURNING trade_date
+        )
+        SELECT trade_date, COUNT(*) FROM inserted GROUP BY trade_date
+    "#;
+
+    let counts = sqlx::query_as::<_, (NaiveDate, i64)>(query)
+        .bind(&trade_dates)
+        .bind(&stock_codes)
+        .bind(&stock_names)
+        .bind(&trade_volumes)
+        .bind(&trade_amounts)
+        .bind(&open_prices)
+        .bind(&high_prices)
+        .bind(&low_prices)
+        .bind(&close_prices)
+        .bind(&price_changes)
+        .bind(&transaction_counts)
+        .bind(&limit_ups)
+        .bind(&limit_downs)
+        .bind(&touched_limit_ups)
+        .bind(&touched_limit_downs)
+        .bind(&halteds)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(counts)
+}
+
+/// 以 `COPY ... FROM STDIN` 串流寫入暫存表，再合併進 stock_day_all
+///
+/// 資料分段編碼成 CSV 送出，不會一次在記憶體中組出整批內容。暫存表在交易結束時自動刪除。
+async fn copy_rows(
+    conn: &mut PgConnection,
+    days: &[PreparedDay],
+) -> Result<Vec<(NaiveDate, i64)>, AppError> {
+    sqlx::query(
+        r#"
+        CREATE TEMP TABLE IF NOT EXISTS stock_day_all_staging (
+            LIKE stock_day_all INCLUDING DEFAULTS EXCLUDING CONSTRAINTS
+        ) ON COMMIT DROP
+        "#,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let mut copy = conn
+        .copy_in_raw(&format!(
+            "COPY stock_day_all_staging ({}) FROM STDIN WITH (FORMAT csv)",
+            COPY_COLUMNS
+        ))
+        .await?;
+
+    let mut buffer = String::with_capacity(COPY_CHUNK_BYTES + 1024);
+    for day in days {
+        for (row, flag) in day.rows.iter().zip(&day.flags) {
+            write_csv_row(&mut buffer, day.trade_date, row, flag);
+            if buffer.len() >= COPY_CHUNK_BYTES {
+                copy.send(buffer.as_bytes()).await?;
+                buffer.clear();
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        copy.send(buffer.as_bytes()).await?;
+    }
+    copy.finish().await?;
+
+    let counts = sqlx::query_as::<_, (NaiveDate, i64)>(&format!(
+        r#"
+        WITH inserted AS (
+            INSERT INTO stock_day_all ({columns})
+            SELECT {columns} FROM stock_day_all_staging
+            ON CONFLICT (trade_date, stock_code) DO NOTHING
+            RETURNING trade_date
+        )
+        SELECT trade_date, COUNT(*) FROM inserted GROUP BY trade_date
+        "#,
+        columns = COPY_COLUMNS
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // 同一個交易中可能再合併下一批，先清空暫存表
+    sqlx::query("TRUNCATE stock_day_all_staging")
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(counts)
+}
+
+/// 將一筆資料編碼為 CSV（NULL 為沒有引號的空欄位）
+fn write_csv_row(
+    buffer: &mut String,
+    trade_date: NaiveDate,
+    row: &StockDayRow,
+    flag: &PriceLimitFlags,
+) {
+    use std::fmt::Write;
+
+    fn opt<T: std::fmt::Display>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let flag_str = |b: bool| if b { "t" } else { "f" };
+
+    let _ = writeln!(
+        buffer,
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        trade_date,
+        quote(&row.stock_code),
+        quote(&row.stock_name),
+        opt(row.trade_volume),
+        opt(row.trade_amount),
+        opt(row.open_price),
+        opt(row.high_price),
+        opt(row.low_price),
+        opt(row.close_price),
+        opt(row.price_change),
+        opt(row.transaction_count),
+        flag_str(flag.limit_up),
+        flag_str(flag.limit_down),
+        flag_str(flag.touched_limit_up),
+        flag_str(flag.touched_limit_down),
+        flag_str(flag.halted),
+    );
+}
+
+/// 抓取並匯入日成交資訊
+///
+/// * `date`: 指定交易日；`None` 表示取 TWSE 最新一個交易日。指定日期時會檢查回應的日期是否一致，
+///   避免 TWSE 忽略參數時把別天的資料寫進來。
+/// * `replace`: 是否在同一個交易中先刪除該日既有資料再寫入
+///
+/// 原始回應會先封存到 upstream_payloads，之後解析邏輯調整時可以用 [`reprocess`] 重跑。
+pub async fn ingest(
+    state: &AppState,
+    date: Option<NaiveDate>,
+    replace: bool,
+) -> Result<IngestSummary, AppError> {
+    let payload = fetch(state, date).await?;
+
+    let decoded = decode(&payload.body);
+    let response_date = decoded
+        .as_ref()
+        .ok()
+        .and_then(|resp| resp.date.as_deref())
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok());
+    payload_archive::archive(&state.db, ARCHIVE_SOURCE, &payload, date, response_date).await?;
+
+    let (trade_date, rows) = parse(&decoded?)?;
+
+    if let Some(requested) = date
+        && requested != trade_date
+    {
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!(
+                "TWSE 回傳的日期 {} 與要求的日期 {} 不符",
+                trade_date, requested
+            ),
+        ));
+    }
+
+    let summary = store(state, trade_date, rows, replace).await?;
+    market_summary::refresh_after_write(&state.db).await;
+    Ok(summary)
+}
+
+/// 用目前的解析邏輯重新處理區間內已封存的原始回應，並取代既有資料
+///
+/// 每個交易日取最後抓到的一份，解析出相同交易日的其他封存會略過；單日解析或寫入失敗不影響其他日期。封存依日期範圍分段讀取，
+/// 檢查用的前一日收盤價在記憶體中逐日傳遞，累積到一定筆數才寫入一次，多年回補時會走 COPY 的
+/// 大量寫入路徑；整批寫入失敗時改為逐日寫入，找出失敗的那一天。
+pub async fn reprocess(
+    state: &AppState,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<ReprocessResult>, AppError> {
+    let mut previous_closes = {
+        let mut conn = state.db.acquire().await?;
+        data_quality::load_previous_closes(&mut conn, from).await?
+    };
+
+    let mut results = Vec::new();
+    let mut batch: Vec<PreparedDay> = Vec::new();
+    // batch 中每一天對應的 (封存 id, results 中的位置)
+    let mut batch_slots: Vec<(i64, usize)> = Vec::new();
+    // 已處理的交易日對應的封存 id，避免同一天寫入兩次
+    let mut processed: HashMap<NaiveDate, i64> = HashMap::new();
+
+    let mut window_start = from;
+    while window_start <= to {
+        let window_end = (window_start + Duration::days(REPROCESS_WINDOW_DAYS - 1)).min(to);
+        let payloads = payload_archive::load_latest_by_date(
+            &state.db,
+            ARCHIVE_SOURCE,
+            window_start,
+            window_end,
+        )
+        .await?;
+        window_start = window_end + Duration::days(1);
+
+        for payload in payloads {
+            results.push(ReprocessResult {
+                payload_id: payload.id,
+                trade_date: payload.trade_date,
+                summary: None,
+                error: None,
+            });
+
+            let parsed = parse_archived(&payload).and_then(|(trade_date, rows)| {
+                match processed.get(&trade_date) {
+                    Some(kept) => Err(AppError::new(
+                        StatusCode::CONFLICT,
+                        format!("交易日 {} 已由封存資料 {} 處理，略過", trade_date, kept),
+                    )),
+                    None => Ok((trade_date, rows)),
+                }
+            });
+            match parsed {
+                Ok((trade_date, rows)) => {
+                    processed.insert(trade_date, payload.id);
+                    let day = PreparedDay::new(
+                        trade_date,
+                        rows,
+                        &previous_closes,
+                        &state.config.data_quality,
+                    );
+                    for row in &day.rows {
+                        if let Some(close) = row.close_price {
+                            previous_closes.insert(row.stock_code.clone(), close);
+                        }
+                    }
+                    batch.push(day);
+                    batch_slots.push((payload.id, results.len() - 1));
+                }
+                Err(e) => {
+                    tracing::warn!("重新處理封存資料 {} 失敗: {}", payload.id, e);
+                    if let Some(result) = results.last_mut() {
+                        result.error = Some(e.to_string());
+                    }
+                }
+            }
+
+            let batch_rows: usize = batch.iter().map(|d| d.rows.len()).sum();
+            if batch_rows >= REPROCESS_BATCH_ROWS {
+                flush_reprocess_batch(state, &mut batch, &mut batch_slots, &mut results).await;
+            }
+        }
+    }
+    flush_reprocess_batch(state, &mut batch, &mut batch_slots, &mut results).await;
+
+    // 全部處理完再重新整理一次統計
+    if results.iter().any(|r| r.summary.is_some()) {
+        market_summary::refresh_after_write(&state.db).await;
+    }
+
+    Ok(results)
+}
+
+/// 解析封存的原始回應，解析出的交易日必須與封存時記錄的日期相同
+///
+/// 日期不符通常是抓取時 TWSE 忽略了日期參數（例如休市日回傳前一個交易日），寫入會蓋掉別天的資料。
+fn parse_archived(payload: &ArchivedPayload) -> Result<(NaiveDate, Vec<StockDayRow>), AppError> {
+    let (trade_date, rows) = parse(&decode(&payload.body)?)?;
+    if trade_date != payload.trade_date {
+        return Err(AppError::new(
+            StatusCode::BAD_GATEWAY,
+            format!(
+                "封存資料的日期 {} 與解析出的交易日 {} 不符，略過",
+                payload.trade_date, trade_date
+            ),
+        ));
+    }
+    Ok((trade_date, rows))
+}
+
+/// 寫入累積的交易日，並把結果填回對應的 [`ReprocessResult`]
+async fn flush_reprocess_batch(
+    state: &AppState,
+    batch: &mut Vec<PreparedDay>,
+    slots: &mut Vec<(i64, usize)>,
+    results: &mut [ReprocessResult],
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    match write_days(state, batch, true).await {
+        Ok(summaries) => {
+            for ((_, index), summary) in slots.iter().zip(summaries) {
+                results[*index].summary = Some(summary);
+            }
+        }
+        Err(e) => {
+            // 整批在同一個交易中，任何一天失敗都會全部回滾，改為逐日寫入
+            tracing::warn!("重新處理批次寫入失敗，改為逐日寫入: {}", e);
+            for (day, (payload_id, index)) in batch.iter().zip(slots.iter()) {
+                match write_days(state, std::slice::from_ref(day), true).await {
+                    Ok(mut summaries) => results[*index].summary = Some(summaries.remove(0)),
+                    Err(e) => {
+                        tracing::warn!("重新處理封存資料 {} 失敗: {}", payload_id, e);
+                        results[*index].error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+    }
+    batch.clear();
+    slots.clear();
+}
+
+/// 檢查資料品質後寫入某個交易日的資料
+async fn store(
+    state: &AppState,
+    trade_date: NaiveDate,
+    rows: Vec<StockDayRow>,
+    replace: bool,
+) -> Result<IngestSummary, AppError> {
+    let previous_closes = {
+        let mut conn = state.db.acquire().await?;
+        data_quality::load_previous_closes(&mut conn, trade_date).await?
+    };
+    let day = PreparedDay::new(
+        trade_date,
+        rows,
+        &previous_closes,
+        &state.config.data_quality,
+    );
+
+    let mut summaries = write_days(state, &[day], replace).await?;
+    Ok(summaries.remove(0))
+}
+
+/// 在同一個交易中寫入多個交易日：取代模式先刪除舊資料，再以本次的品質問題取代舊的結果並寫入資料列
+async fn write_days(
+    state: &AppState,
+    days: &[PreparedDay],
+    replace: bool,
+) -> Result<Vec<IngestSummary>, AppError> {
+    let mut tx = state.db.begin().await?;
+
+    let mut deleted: HashMap<NaiveDate, u64> = HashMap::new();
+    for day in days {
+        if replace {
+            let rows = sqlx::query("DELETE FROM stock_day_all WHERE trade_date = $1")
+                .bind(day.trade_date)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            deleted.insert(day.trade_date, rows);
+        }
+        // 資料品質問題寫入 stock_quality_issues；不論是否取代資料，舊的檢查結果都不再適用
+        data_quality::replace_issues(&mut tx, day.trade_date, &day.issues, &day.rejected_codes)
+            .await?;
+    }
+
+    let inserted = insert_rows(&mut tx, days, state.config.bulk_insert_threshold).await?;
+
+    tx.commit().await?;
+    cache::bump_version(&state.redis, "stock_day_all").await;
+
+    let summaries = days
+        .iter()
+        .map(|day| {
+            let summary = IngestSummary {
+                trade_date: day.trade_date,
+                parsed_rows: day.parsed_rows,
+                inserted_rows: inserted.get(&day.trade_date).copied().unwrap_or(0),
+                deleted_rows: deleted.get(&day.trade_date).copied().unwrap_or(0),
+                quality_issues: day.issues.len(),
+                rejected_rows: day.parsed_rows - day.rows.len(),
+            };
+            tracing::info!(
+                "📈 stock_day_all {}: 解析 {} 筆, 寫入 {} 筆, 刪除 {} 筆, 品質問題 {} 筆, 排除 {} 筆",
+                summary.trade_date,
+                summary.parsed_rows,
+                summary.inserted_rows,
+                summary.deleted_rows,
+                summary.quality_issues,
+                summary.rejected_rows
+            );
+            summary
+        })
+        .collect();
+
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(stock_code: &str, stock_name: &str, close_price: Option<f64>) -> StockDayRow {
+        StockDayRow {
+            stock_code: stock_code.to_string(),
+            stock_name: stock_name.to_string(),
+            trade_volume: close_price.map(|_| 1_234_567),
+            trade_amount: None,
+            open_price: close_price,
+            high_price: close_price,
+            low_price: close_price,
+            close_price,
+            price_change: close_price.map(|_| -0.5),
+            transaction_count: Some(0),
+        }
+    }
+
+    fn csv(row: &StockDayRow, flag: PriceLimitFlags) -> String {
+        let mut buffer = String::new();
+        write_csv_row(&mut buffer, date("2024-01-02"), row, &flag);
+        buffer
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn csv_quotes_text_and_leaves_null_unquoted() {
+        assert_eq!(
+            csv(
+                &row("2330", "台積電", Some(580.5)),
+                PriceLimitFlags::default()
+            ),
+            "2024-01-02,\"2330\",\"台積電\",1234567,,580.5,580.5,580.5,580.5,-0.5,0,f,f,f,f,f\n"
+        );
+
+        // 引號重複一次、逗號包在引號內；空字串是 "" 而不是 NULL
+        let flag = PriceLimitFlags {
+            halted: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            csv(&row("9999", "A \"B\", C", None), flag),
+            "2024-01-02,\"9999\",\"A \"\"B\"\", C\",,,,,,,,0,f,f,f,f,t\n"
+        );
+        assert!(csv(&row("9998", "", None), flag).starts_with("2024-01-02,\"9998\",\"\",,"));
+    }
+
+    #[test]
+    fn archived_payload_must_parse_to_its_own_date() {
+        const BODY: &str = r#"{"stat":"OK","date":"20240102","data":[
+            ["2330","台積電","1,000","580,000","580.00","581.00","579.00","580.00","+1.00","10"]
+        ]}"#;
+        let archived = |trade_date: &str| ArchivedPayload {
+            id: 1,
+            trade_date: date(trade_date),
+            body: BODY.as_bytes().to_vec(),
+        };
+
+        let (trade_date, rows) = parse_archived(&archived("2024-01-02")).unwrap();
+        assert_eq!(trade_date, date("2024-01-02"));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].trade_amount, Some(580_000));
+
+        // 以 1/1（休市）要求卻拿到 1/2 的資料
+        let err = parse_archived(&archived("2024-01-01")).unwrap_err();
+        assert!(err.message.contains("不符"));
+    }
+
+    fn prepared(trade_date: NaiveDate) -> PreparedDay {
+        let rows = vec![
+            row("2330", "台積電", Some(580.5)),
+            row("9999", "A \"B\", C", None),
+            row("9998", "", None),
+        ];
+        let flags = vec![
+            PriceLimitFlags {
+                limit_up: true,
+                ..Default::default()
+            },
+            PriceLimitFlags {
+                halted: true,
+                ..Default::default()
+            },
+            PriceLimitFlags::default(),
+        ];
+        PreparedDay {
+            trade_date,
+            parsed_rows: rows.len(),
+            rows,
+            flags,
+            issues: Vec::new(),
+            rejected_codes: HashSet::new(),
+        }
+    }
+
+    type StoredRow = (
+        String,
+        String,
+        Option<i64>,
+        Option<f64>,
+        Option<f64>,
+        bool,
+        bool,
+    );
+
+    async fn stored(conn: &mut PgConnection, trade_date: NaiveDate) -> Vec<StoredRow> {
+        sqlx::query_as::<_, StoredRow>(
+            r#"
+            SELECT stock_code, stock_name, trade_volume,
+                   close_price::float8, price_change::float8, limit_up, halted
+            FROM stock_day_all
+            WHERE trade_date = $1
+            ORDER BY stock_code
+            "#,
+        )
+        .bind(trade_date)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres（TEST_DATABASE_URL）"]
+    async fn copy_path_stores_same_rows_as_unnest() {
+        let url = std::env::var("TEST_DATABASE_URL").expect("Not Found TEST_DATABASE_URL");
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        // 交易不提交，測試資料在結束時回滾
+        let mut tx = db.begin().await.unwrap();
+        let (copied, unnested) = (date("1900-01-02"), date("1900-01-03"));
+
+        let counts = insert_rows(&mut tx, &[prepared(copied)], 0).await.unwrap();
+        assert_eq!(counts.get(&copied), Some(&3));
+        let counts = insert_rows(&mut tx, &[prepared(unnested)], usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(counts.get(&unnested), Some(&3));
+
+        let rows = stored(&mut tx, copied).await;
+        assert_eq!(rows, stored(&mut tx, unnested).await);
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "2330".to_string(),
+                    "台積電".to_string(),
+                    Some(1_234_567),
+                    Some(580.5),
+                    Some(-0.5),
+                    true,
+                    false
+                ),
+                (
+                    "9998".to_string(),
+                    String::new(),
+                    None,
+                    None,
+                    None,
+                    false,
+                    false
+                ),
+                (
+                    "9999".to_string(),
+                    "A \"B\", C".to_string(),
+                    None,
+                    None,
+                    None,
+                    false,
+                    true
+                ),
+            ]
+        );
+
+        // 已存在的資料列略過；同一個交易中可以再走一次 COPY
+        let counts = insert_rows(&mut tx, &[prepared(copied)], 0).await.unwrap();
+        assert_eq!(counts.get(&copied), None);
+    }
+
+    async fn stored_rules(conn: &mut PgConnection, trade_date: NaiveDate) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT rule FROM stock_quality_issues WHERE trade_date = $1 ORDER BY rule",
+        )
+        .bind(trade_date)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres（TEST_DATABASE_URL）"]
+    async fn revalidation_replaces_previous_issues() {
+        let url = std::env::var("TEST_DATABASE_URL").expect("Not Found TEST_DATABASE_URL");
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let mut tx = db.begin().await.unwrap();
+        let trade_date = date("1900-01-04");
+        let config = DataQualityConfig::default();
+        let previous_closes = HashMap::from([("2330".to_string(), 500.0)]);
+
+        // 第一次匯入：收盤價遠高於前日，漲跌價差也對不上
+        let day = PreparedDay::new(
+            trade_date,
+            vec![row("2330", "台積電", Some(580.5))],
+            &previous_closes,
+            &config,
+        );
+        data_quality::replace_issues(&mut tx, trade_date, &day.issues, &day.rejected_codes)
+            .await
+            .unwrap();
+        assert_eq!(
+            stored_rules(&mut tx, trade_date).await,
+            vec!["price_change", "price_limit"]
+        );
+
+        // 修正後以非取代模式重新匯入：上次的問題都不該留下
+        let mut fixed = row("2330", "台積電", Some(540.0));
+        fixed.price_change = Some(40.0);
+        let day = PreparedDay::new(trade_date, vec![fixed], &previous_closes, &config);
+        data_quality::replace_issues(&mut tx, trade_date, &day.issues, &day.rejected_codes)
+            .await
+            .unwrap();
+        assert!(stored_rules(&mut tx, trade_date).await.is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+// src/services/token_crypto.rs
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, KeyInit, Payload},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rand::RngCore;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{config::TokenEncryptionConfig, error::AppError};
+
+/// AES-GCM 的 nonce 長度
+const NONCE_LEN: usize = 12;
+/// 重新加密時每批處理的列數
+const REENCRYPT_BATCH_ROWS: i64 = 500;
+
+/// 綁定在密文上的附加資料，讓密文無法搬到其他列或其他欄位使用
+fn aad(provider: &str, provider_user_id: &str, column: &str) -> String {
+    format!("{}:{}:{}", provider, provider_user_id, column)
+}
+
+fn cipher(config: &TokenEncryptionConfig, kid: &str) -> Result<Aes256Gcm, AppError> {
+    let key = config
+        .key(kid)
+        .ok_or_else(|| AppError::internal_error(format!("找不到 token 加密金鑰 {}", kid)))?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+}
+
+/// 一組 token 欄位的密文與使用的金鑰
+#[derive(Debug)]
+pub struct SealedTokens {
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    /// 寫入 user_identities.token_key_id；沒有設定金鑰時為 `None`，內容為明文
+    pub key_id: Option<String>,
+}
+
+/// 加密 user_identities 某一列的 token 欄位
+pub fn seal(
+    config: &TokenEncryptionConfig,
+    provider: &str,
+    provider_user_id: &str,
+    access_token: Option<&str>,
+    refresh_token: Option<&str>,
+) -> Result<SealedTokens, AppError> {
+    let Some(kid) = config.active_kid.as_deref() else {
+        return Ok(SealedTokens {
+            access_token: access_token.map(str::to_string),
+            refresh_token: refresh_token.map(str::to_string),
+            key_id: None,
+        });
+    };
+    let cipher = cipher(config, kid)?;
+
+    let encrypt = |value: &str, column: &str| -> Result<String, AppError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let aad = aad(provider, provider_user_id, column);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| AppError::internal_error("token 加密失敗"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(sealed))
+    };
+
+    Ok(SealedTokens {
+        access_token: access_token
+            .map(|value| encrypt(value, "access_token"))
+            .transpose()?,
+        refresh_token: refresh_token
+            .map(|value| encrypt(value, "refresh_token"))
+            .transpose()?,
+        key_id: Some(kid.to_string()),
+    })
+}
+
+/// 解密單一 token 欄位；`key_id` 為 `None` 表示是加密功能上線前的明文
+pub fn open(
+    config: &TokenEncryptionConfig,
+    key_id: Option<&str>,
+    provider: &str,
+    provider_user_id: &str,
+    column: &str,
+    value: &str,
+) -> Result<String, AppError> {
+    let Some(kid) = key_id else {
+        return Ok(value.to_string());
+    };
+    let cipher = cipher(config, kid)?;
+
+    let sealed = STANDARD
+        .decode(value)
+        .map_err(|_| AppError::internal_error("token 密文格式錯誤"))?;
+    if sealed.len() <= NONCE_LEN {
+        return Err(AppError::internal_error("token 密文格式錯誤"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let aad = aad(provider, provider_user_id, column);
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| AppError::internal_error("token 解密失敗（金鑰錯誤或資料遭竄改）"))?;
+
+    String::from_utf8(plaintext).map_err(|_| AppError::internal_error("token 解密結果不是 UTF-8"))
+}
+
+#[derive(sqlx::FromRow)]
+struct IdentityTokens {
+    id: Uuid,
+    provider: String,
+    provider_user_id: String,
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    token_key_id: Option<String>,
+}
+
+/// 重新加密的結果
+#[derive(Debug, Serialize)]
+pub struct ReencryptSummary {
+    pub key_id: String,
+    pub updated_rows: u64,
+}
+
+/// 把所有不是以目前金鑰加密的 token（包含舊的明文）改用目前金鑰重新加密
+///
+/// 舊金鑰必須仍在設定中才能解密；完成後即可從 `TOKEN_ENCRYPTION_KEYS` 移除。
+pub async fn reencrypt_all(
+    db: &PgPool,
+    config: &TokenEncryptionConfig,
+) -> Result<ReencryptSummary, AppError> {
+    let Some(active_kid) = config.active_kid.clone() else {
+        return Err(AppError::bad_request(
+            "未設定 TOKEN_ENCRYPTION_KEYS，無法重新加密",
+        ));
+    };
+
+    let mut updated_rows = 0;
+    loop {
+        let mut tx = db.begin().await?;
+        let rows = sqlx::query_as::<_, IdentityTokens>(
+            r#"
+            SELECT id, provider, provider_user_id, access_token, refresh_token, token_key_id
+            FROM user_identities
+            WHERE token_key_id IS DISTINCT FROM $1
+              AND (access_token IS NOT NULL OR refresh_token IS NOT NULL)
+            ORDER BY id
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+            "#,
+        )
+        .bind(&active_kid)
+        .bind(REENCRYPT_BATCH_ROWS)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            let key_id = row.token_key_id.as_deref();
+            let decrypt = |column: &str, value: &Option<String>| {
+                value
+                    .as_deref()
+                    .map(|value| {
+                        open(
+                            config,
+                            key_id,
+                            &row.provider,
+                            &row.provider_user_id,
+                            column,
+                            value,
+                        )
+                    })
+                    .transpose()
+            };
+            let access_token = decrypt("access_token", &row.access_token)?;
+            let refresh_token = decrypt("refresh_token", &row.refresh_token)?;
+            let sealed = seal(
+                config,
+                &row.provider,
+                &row.provider_user_id,
+                access_token.as_deref(),
+                refresh_token.as_deref(),
+            )?;
+
+            sqlx::query(
+                r#"
+                UPDATE user_identities
+                SET access_token = $2, refresh_token = $3, token_key_id = $4
+                WHERE id = $1
+                "#,
+            )
+            .bind(row.id)
+            .bind(&sealed.access_token)
+            .bind(&sealed.refresh_token)
+            .bind(&sealed.key_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        updated_rows += rows.len() as u64;
+    }
+
+    tracing::info!(
+        "🔐 token 重新加密完成: 金鑰 {}, 更新 {} 筆",
+        active_kid,
+        updated_rows
+    );
+    Ok(ReencryptSummary {
+        key_id: active_kid,
+        updated_rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(active: Option<&str>) -> TokenEncryptionConfig {
+        TokenEncryptionConfig {
+            keys: vec![("k1".into(), vec![1; 32]), ("k2".into(), vec![2; 32])],
+            active_kid: active.map(str::to_string),
+        }
+    }
+
+    fn sealed() -> SealedTokens {
+        seal(
+            &config(Some("k1")),
+            "google",
+            "user-1",
+            Some("access"),
+            Some("refresh"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip_with_active_key() {
+        let config = config(Some("k1"));
+        let tokens = sealed();
+        assert_eq!(tokens.key_id.as_deref(), Some("k1"));
+
+        let access = tokens.access_token.unwrap();
+        let refresh = tokens.refresh_token.unwrap();
+        assert_ne!(access, "access");
+        let open = |column, value| open(&config, Some("k1"), "google", "user-1", column, value);
+        assert_eq!(open("access_token", &access).unwrap(), "access");
+        assert_eq!(open("refresh_token", &refresh).unwrap(), "refresh");
+
+        // 每次加密使用新的 nonce
+        assert_ne!(sealed().access_token.unwrap(), access);
+    }
+
+    #[test]
+    fn old_key_still_opens_after_rotation() {
+        let access = sealed().access_token.unwrap();
+        let rotated = config(Some("k2"));
+        let value = open(
+            &rotated,
+            Some("k1"),
+            "google",
+            "user-1",
+            "access_token",
+            &access,
+        );
+        assert_eq!(value.unwrap(), "access");
+    }
+
+    #[test]
+    fn wrong_or_unknown_kid_is_rejected() {
+        let config = config(Some("k1"));
+        let access = sealed().access_token.unwrap();
+        let open = |kid| {
+            open(
+                &config,
+                Some(kid),
+                "google",
+                "user-1",
+                "access_token",
+                &access,
+            )
+        };
+        assert!(open("k2").is_err());
+        assert!(open("missing").is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let config = config(Some("k1"));
+        let mut bytes = STANDARD.decode(sealed().access_token.unwrap()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = STANDARD.encode(&bytes);
+        let truncated = STANDARD.encode(&bytes[..NONCE_LEN]);
+        let open = |value| {
+            open(
+                &config,
+                Some("k1"),
+                "google",
+                "user-1",
+                "access_token",
+                value,
+            )
+        };
+
+        assert!(open(&tampered).is_err());
+        assert!(open(&truncated).is_err());
+        assert!(open("not base64!").is_err());
+    }
+
+    #[test]
+    fn ciphertext_is_bound_to_column_and_row() {
+        let config = config(Some("k1"));
+        let access = sealed().access_token.unwrap();
+        let open =
+            |provider, user, column| open(&config, Some("k1"), provider, user, column, &access);
+
+        assert!(open("google", "user-1", "access_token").is_ok());
+        assert!(open("google", "user-1", "refresh_token").is_err());
+        assert!(open("google", "user-2", "access_token").is_err());
+        assert!(open("discord", "user-1", "access_token").is_err());
+    }
+
+    #[test]
+    fn without_keys_tokens_stay_plaintext() {
+        let config = TokenEncryptionConfig {
+            keys: Vec::new(),
+            active_kid: None,
+        };
+        let tokens = seal(&config, "google", "user-1", Some("access"), None).unwrap();
+        assert_eq!(tokens.key_id, None);
+        assert_eq!(tokens.access_token.as_deref(), Some("access"));
+        assert_eq!(tokens.refresh_token, None);
+
+        let value = open(&config, None, "google", "user-1", "access_token", "access");
+        assert_eq!(value.unwrap(), "access");
+    }
+}
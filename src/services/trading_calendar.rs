@@ -0,0 +1,347 @@
+// src/services/trading_calendar.rs
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+/// TWSE 休市日期表（`queryYear` 為民國年）
+const HOLIDAY_SCHEDULE_URL: &str = "https://www.twse.com.tw/holidaySchedule/holidaySchedule";
+
+/// 交易日曆中的一天
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TradingCalendarDay {
+    pub trade_date: NaiveDate,
+    pub is_trading_day: bool,
+    pub description: Option<String>,
+    pub source: String,
+}
+
+/// 同步結果摘要
+#[derive(Debug, Serialize)]
+pub struct SyncSummary {
+    pub year: i32,
+    pub trading_days: usize,
+    pub holidays: usize,
+    /// 實際寫入（新增或更新）的筆數，手動維護的日期不會被覆蓋
+    pub upserted: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct HolidayScheduleResponse {
+    #[serde(default)]
+    data: Vec<Vec<String>>,
+}
+
+/// 休市日期表中的一筆資料
+#[derive(Debug)]
+struct Holiday {
+    date: NaiveDate,
+    name: String,
+}
+
+impl Holiday {
+    /// 表中也會列出「開始交易日」「最後交易日」這類提示，這些日子其實有開市
+    fn is_trading_marker(&self) -> bool {
+        self.name.contains("開始交易") || self.name.contains("最後交易")
+    }
+
+    /// 週末補行交易的日子（名稱寫明「不交易」的補行上班日除外）
+    fn is_makeup_trading_day(&self) -> bool {
+        self.name.contains("補行交易") && !self.name.contains("不交易")
+    }
+
+    /// 這一天是否開市：平日除非列在表上，週末除非是補行交易日
+    fn is_trading_day(&self) -> bool {
+        if is_weekend(self.date) {
+            self.is_makeup_trading_day()
+        } else {
+            self.is_trading_marker() || self.is_makeup_trading_day()
+        }
+    }
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// 依休市日期表產生整年每一天的（日期, 是否開市, 說明）
+fn year_days(year: i32, holidays: &[Holiday]) -> Vec<(NaiveDate, bool, Option<String>)> {
+    let Some(first_day) = NaiveDate::from_ymd_opt(year, 1, 1) else {
+        return Vec::new();
+    };
+    first_day
+        .iter_days()
+        .take_while(|d| d.year() == year)
+        .map(|date| match holidays.iter().find(|h| h.date == date) {
+            Some(h) => (date, h.is_trading_day(), Some(h.name.clone())),
+            None => (date, !is_weekend(date), None),
+        })
+        .collect()
+}
+
+/// 取得指定西元年份的 TWSE 休市日期表
+async fn fetch_holidays(http_client: &Client, year: i32) -> Result<Vec<Holiday>, AppError> {
+    let resp: HolidayScheduleResponse = http_client
+        .get(HOLIDAY_SCHEDULE_URL)
+        .query(&[
+            ("response", "json".to_string()),
+            ("queryYear", (year - 1911).to_string()),
+        ])
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    // 欄位順序在不同年份的報表中不一致，逐格判斷哪一格是日期
+    let holidays = resp
+        .data
+        .iter()
+        .filter_map(|row| {
+            let date = row.iter().find_map(|cell| parse_twse_date(cell))?;
+            let name = row
+                .iter()
+                .find(|cell| parse_twse_date(cell).is_none() && !cell.trim().is_empty())
+                .cloned()
+                .unwrap_or_default();
+            Some(Holiday { date, name })
+        })
+        .filter(|holiday| holiday.date.year() == year)
+        .collect();
+
+    Ok(holidays)
+}
+
+/// 解析 TWSE 常見的日期寫法：`2025-01-01`、`20250101`、民國 `114/01/01`、`1140101`
+pub fn parse_twse_date(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
+    let separators = s.chars().filter(|c| !c.is_ascii_digit()).count();
+    let is_plain = separators == 0;
+    let is_slashed = s.split('/').count() == 3 && separators == 2;
+    if !(is_plain || is_slashed) {
+        return None;
+    }
+
+    match digits.len() {
+        8 => NaiveDate::parse_from_str(&digits, "%Y%m%d").ok(),
+        7 => {
+            let roc_year: i32 = digits[..3].parse().ok()?;
+            let month: u32 = digits[3..5].parse().ok()?;
+            let day: u32 = digits[5..].parse().ok()?;
+            NaiveDate::from_ymd_opt(roc_year + 1911, month, day)
+        }
+        _ => None,
+    }
+}
+
+/// 依 TWSE 休市日期表產生整年的交易日曆並寫入資料庫
+///
+/// 週末視為休市，除非表上列為補行交易日；手動維護（`source = 'manual'`）的日期不會被覆蓋，
+/// 表上沒有的補班交易日可以用 `upsert_manual` 設定。
+pub async fn sync_year(
+    db: &PgPool,
+    http_client: &Client,
+    year: i32,
+) -> Result<SyncSummary, AppError> {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| AppError::bad_request(format!("無效的年份: {}", year)))?;
+
+    let holidays = fetch_holidays(http_client, year).await?;
+    if holidays.is_empty() {
+        return Err(AppError::not_found(format!(
+            "TWSE 尚未公布 {} 年的休市日期表",
+            year
+        )));
+    }
+
+    let mut dates = Vec::new();
+    let mut is_trading_days = Vec::new();
+    let mut descriptions = Vec::new();
+    for (date, is_trading_day, description) in year_days(year, &holidays) {
+        dates.push(date);
+        is_trading_days.push(is_trading_day);
+        descriptions.push(description);
+    }
+
+    let query = r#"
+        INSERT INTO trading_calendar (trade_date, is_trading_day, description, source)
+        SELECT d, t, desc_text, 'twse'
+        FROM UNNEST($1::date[], $2::boolean[], $3::text[]) AS u(d, t, desc_text)
+        ON CONFLICT (trade_date) DO UPDATE
+        SET is_trading_day = EXCLUDED.is_trading_day,
+            description = EXCLUDED.description,
+            updated_at = NOW()
+        WHERE trading_calendar.source <> 'manual';
+    "#;
+
+    let result = sqlx::query(query)
+        .bind(&dates)
+        .bind(&is_trading_days)
+        .bind(&descriptions)
+        .execute(db)
+        .await?;
+
+    let trading_days = is_trading_days.iter().filter(|t| **t).count();
+
+    Ok(SyncSummary {
+        year,
+        trading_days,
+        holidays: dates.len() - trading_days,
+        upserted: result.rows_affected(),
+    })
+}
+
+/// 查詢區間內的交易日曆
+pub async fn list(
+    db: &PgPool,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<TradingCalendarDay>, AppError> {
+    let days = sqlx::query_as::<_, TradingCalendarDay>(
+        r#"
+        SELECT trade_date, is_trading_day, description, source
+        FROM trading_calendar
+        WHERE trade_date BETWEEN $1 AND $2
+        ORDER BY trade_date
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    Ok(days)
+}
+
+/// 手動新增或修改某一天（例如颱風停市、週六補行交易），週末也可以設為開市
+pub async fn upsert_manual(
+    db: &PgPool,
+    date: NaiveDate,
+    is_trading_day: bool,
+    description: Option<String>,
+) -> Result<TradingCalendarDay, AppError> {
+    let day = sqlx::query_as::<_, TradingCalendarDay>(
+        r#"
+        INSERT INTO trading_calendar (trade_date, is_trading_day, description, source)
+        VALUES ($1, $2, $3, 'manual')
+        ON CONFLICT (trade_date) DO UPDATE
+        SET is_trading_day = EXCLUDED.is_trading_day,
+            description = EXCLUDED.description,
+            source = 'manual',
+            updated_at = NOW()
+        RETURNING trade_date, is_trading_day, description, source
+        "#,
+    )
+    .bind(date)
+    .bind(is_trading_day)
+    .bind(description)
+    .fetch_one(db)
+    .await?;
+
+    Ok(day)
+}
+
+/// 刪除某一天的設定
+pub async fn delete(db: &PgPool, date: NaiveDate) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM trading_calendar WHERE trade_date = $1")
+        .bind(date)
+        .execute(db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found(format!("交易日曆中沒有 {}", date)));
+    }
+
+    Ok(())
+}
+
+/// 找出區間內「是交易日但 stock_day_all 沒有任何資料」的日期，用於補抓
+pub async fn find_gaps(
+    db: &PgPool,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<NaiveDate>, AppError> {
+    let gaps = sqlx::query_scalar::<_, NaiveDate>(
+        r#"
+        SELECT c.trade_date
+        FROM trading_calendar c
+        WHERE c.is_trading_day
+          AND c.trade_date BETWEEN $1 AND $2
+          AND c.trade_date <= CURRENT_DATE
+          AND NOT EXISTS (
+              SELECT 1 FROM stock_day_all s WHERE s.trade_date = c.trade_date
+          )
+        ORDER BY c.trade_date
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    Ok(gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn holiday(date_str: &str, name: &str) -> Holiday {
+        Holiday {
+            date: date(date_str),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn weekends_open_only_for_makeup_trading() {
+        let holidays = [
+            holiday("2024-01-01", "中華民國開國紀念日"),
+            holiday("2024-01-02", "國曆新年開始交易日"),
+            holiday("2024-01-06", "補行交易日"),
+            holiday("2024-01-13", "補行上班日（不交易）"),
+        ];
+        let days = year_days(2024, &holidays);
+        assert_eq!(days.len(), 366);
+
+        let is_open = |d: &str| days.iter().find(|(day, ..)| *day == date(d)).unwrap().1;
+        assert!(!is_open("2024-01-01"));
+        assert!(is_open("2024-01-02"));
+        assert!(is_open("2024-01-03"));
+        // 週六補行交易
+        assert!(is_open("2024-01-06"));
+        assert!(!is_open("2024-01-07"));
+        assert!(!is_open("2024-01-13"));
+        assert!(!is_open("2024-01-20"));
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Postgres（TEST_DATABASE_URL）"]
+    async fn manual_upsert_opens_a_saturday() {
+        let url = std::env::var("TEST_DATABASE_URL").expect("Not Found TEST_DATABASE_URL");
+        let db = PgPool::connect(&url).await.unwrap();
+        let saturday = date("1900-01-06");
+
+        let day = upsert_manual(&db, saturday, true, Some("補行交易日".to_string()))
+            .await
+            .unwrap();
+        let gaps = find_gaps(&db, saturday, saturday).await.unwrap();
+        delete(&db, saturday).await.unwrap();
+
+        assert!(day.is_trading_day);
+        assert_eq!(day.source, "manual");
+        // 開市但沒有資料，列入缺漏報表
+        assert_eq!(gaps, vec![saturday]);
+    }
+}
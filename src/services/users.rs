@@ -0,0 +1,385 @@
+// src/services/users.rs
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    config::TokenEncryptionConfig,
+    error::AppError,
+    services::{
+        oauth::{ProviderProfile, ProviderTokens},
+        token_crypto,
+    },
+};
+
+/// 使用者
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct User {
+    pub id: Uuid,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_active: bool,
+    /// 可使用 `/admin` 底下的管理路由
+    pub is_admin: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 使用者綁定的登入方式（不含 token）
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Identity {
+    pub id: Uuid,
+    pub provider: String,
+    pub provider_email: Option<String>,
+    /// 提供者已撤銷授權，需以該提供者重新登入
+    pub needs_reconsent: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// 以第三方身分登入的結果
+pub enum SignIn {
+    /// 既有或新建立的使用者
+    User(User),
+    /// 第一次出現的身分，但驗證過的 email 屬於既有使用者，需要該使用者明確同意合併
+    EmailMatch(Uuid),
+}
+
+const USER_COLUMNS: &str = r#"
+    id, email, display_name, avatar_url,
+    COALESCE(is_active, TRUE) AS is_active,
+    is_admin,
+    COALESCE(created_at, NOW()) AS created_at
+"#;
+
+/// 依 id 取得使用者
+pub async fn find(db: &PgPool, id: Uuid) -> Result<Option<User>, AppError> {
+    let user =
+        sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+            .bind(id)
+            .fetch_optional(db)
+            .await?;
+
+    Ok(user)
+}
+
+async fn identity_owner(
+    conn: &mut PgConnection,
+    provider: &str,
+    subject: &str,
+) -> Result<Option<Uuid>, AppError> {
+    let owner = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT user_id FROM user_identities
+        WHERE provider = $1 AND provider_user_id = $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(provider)
+    .bind(subject)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(owner)
+}
+
+async fn update_identity(
+    conn: &mut PgConnection,
+    crypto: &TokenEncryptionConfig,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<(), AppError> {
+    // 提供者這次沒有給 refresh token 時沿用舊的，並以目前金鑰重新加密，讓整列只對應一個 kid
+    let refresh_token = match &tokens.refresh_token {
+        Some(token) => Some(token.clone()),
+        None => {
+            let (stored, key_id) = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+                r#"
+                SELECT refresh_token, token_key_id FROM user_identities
+                WHERE provider = $1 AND provider_user_id = $2
+                "#,
+            )
+            .bind(provider)
+            .bind(&profile.subject)
+            .fetch_one(&mut *conn)
+            .await?;
+            stored
+                .map(|value| {
+                    token_crypto::open(
+                        crypto,
+                        key_id.as_deref(),
+                        provider,
+                        &profile.subject,
+                        "refresh_token",
+                        &value,
+                    )
+                })
+                .transpose()?
+        }
+    };
+    let sealed = token_crypto::seal(
+        crypto,
+        provider,
+        &profile.subject,
+        Some(&tokens.access_token),
+        refresh_token.as_deref(),
+    )?;
+
+    sqlx::query(
+        r#"
+        UPDATE user_identities
+        SET provider_email = $3,
+            access_token = $4,
+            refresh_token = $5,
+            token_key_id = $6,
+            expires_at = $7,
+            refresh_failures = 0,
+            last_refresh_error = NULL,
+            needs_reconsent = FALSE,
+            updated_at = NOW()
+        WHERE provider = $1 AND provider_user_id = $2
+        "#,
+    )
+    .bind(provider)
+    .bind(&profile.subject)
+    .bind(&profile.email)
+    .bind(&sealed.access_token)
+    .bind(&sealed.refresh_token)
+    .bind(&sealed.key_id)
+    .bind(tokens.expires_at)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_identity(
+    conn: &mut PgConnection,
+    crypto: &TokenEncryptionConfig,
+    user_id: Uuid,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<(), AppError> {
+    let sealed = token_crypto::seal(
+        crypto,
+        provider,
+        &profile.subject,
+        Some(&tokens.access_token),
+        tokens.refresh_token.as_deref(),
+    )?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_identities
+            (user_id, provider, provider_user_id, provider_email,
+             access_token, refresh_token, token_key_id, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(user_id)
+    .bind(provider)
+    .bind(&profile.subject)
+    .bind(&profile.email)
+    .bind(&sealed.access_token)
+    .bind(&sealed.refresh_token)
+    .bind(&sealed.key_id)
+    .bind(tokens.expires_at)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn user_with_email(conn: &mut PgConnection, email: &str) -> Result<Option<Uuid>, AppError> {
+    let user_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE lower(email) = lower($1)")
+            .bind(email)
+            .fetch_optional(conn)
+            .await?;
+
+    Ok(user_id)
+}
+
+/// 以第三方登入的結果登入或建立使用者
+///
+/// 已綁定過的身分只更新 token 與個人資料；第一次出現的身分若驗證過的 email 屬於既有使用者，
+/// 不做任何寫入並回傳 `SignIn::EmailMatch`，由使用者決定要合併還是另外建立帳號。
+pub async fn sign_in(
+    db: &PgPool,
+    crypto: &TokenEncryptionConfig,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<SignIn, AppError> {
+    let mut tx = db.begin().await?;
+
+    let user = match identity_owner(&mut tx, provider, &profile.subject).await? {
+        Some(user_id) => {
+            update_identity(&mut tx, crypto, provider, profile, tokens).await?;
+            sqlx::query_as::<_, User>(&format!(
+                r#"
+                UPDATE users
+                SET display_name = COALESCE($2, display_name),
+                    avatar_url = COALESCE($3, avatar_url),
+                    updated_at = NOW()
+                WHERE id = $1
+                RETURNING {}
+                "#,
+                USER_COLUMNS
+            ))
+            .bind(user_id)
+            .bind(&profile.display_name)
+            .bind(&profile.avatar_url)
+            .fetch_one(&mut *tx)
+            .await?
+        }
+        None => {
+            if let Some(email) = profile.verified_email()
+                && let Some(user_id) = user_with_email(&mut tx, email).await?
+            {
+                return Ok(SignIn::EmailMatch(user_id));
+            }
+            create_in(&mut tx, crypto, provider, profile, tokens).await?
+        }
+    };
+
+    tx.commit().await?;
+    Ok(SignIn::User(user))
+}
+
+/// 以第三方身分建立新使用者（使用者選擇不合併時也走這裡）
+///
+/// 只有提供者驗證過的 email 才會寫入 users.email，且已被其他使用者使用時留空。
+pub async fn create_with_identity(
+    db: &PgPool,
+    crypto: &TokenEncryptionConfig,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<User, AppError> {
+    let mut tx = db.begin().await?;
+    if identity_owner(&mut tx, provider, &profile.subject)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            "此登入方式已綁定其他帳號",
+        ));
+    }
+    let user = create_in(&mut tx, crypto, provider, profile, tokens).await?;
+    tx.commit().await?;
+    Ok(user)
+}
+
+async fn create_in(
+    conn: &mut PgConnection,
+    crypto: &TokenEncryptionConfig,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<User, AppError> {
+    let email = match profile.verified_email() {
+        Some(email) if user_with_email(conn, email).await?.is_none() => Some(email),
+        _ => None,
+    };
+
+    let user = sqlx::query_as::<_, User>(&format!(
+        r#"
+        INSERT INTO users (email, display_name, avatar_url)
+        VALUES ($1, $2, $3)
+        RETURNING {}
+        "#,
+        USER_COLUMNS
+    ))
+    .bind(email)
+    .bind(&profile.display_name)
+    .bind(&profile.avatar_url)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    insert_identity(conn, crypto, user.id, provider, profile, tokens).await?;
+    Ok(user)
+}
+
+/// 把第三方身分綁定到指定使用者；已綁定到其他使用者時回傳 409
+pub async fn link_identity(
+    db: &PgPool,
+    crypto: &TokenEncryptionConfig,
+    user_id: Uuid,
+    provider: &str,
+    profile: &ProviderProfile,
+    tokens: &ProviderTokens,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+    match identity_owner(&mut tx, provider, &profile.subject).await? {
+        Some(owner) if owner == user_id => {
+            update_identity(&mut tx, crypto, provider, profile, tokens).await?;
+        }
+        Some(_) => {
+            return Err(AppError::new(
+                StatusCode::CONFLICT,
+                "此登入方式已綁定其他帳號",
+            ));
+        }
+        None => insert_identity(&mut tx, crypto, user_id, provider, profile, tokens).await?,
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 使用者綁定的所有登入方式
+pub async fn list_identities(db: &PgPool, user_id: Uuid) -> Result<Vec<Identity>, AppError> {
+    let identities = sqlx::query_as::<_, Identity>(
+        r#"
+        SELECT id, provider, provider_email, needs_reconsent, created_at, updated_at
+        FROM user_identities
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(identities)
+}
+
+/// 解除綁定；不允許解除最後一個登入方式，避免帳號再也無法登入
+pub async fn unlink_identity(
+    db: &PgPool,
+    user_id: Uuid,
+    identity_id: Uuid,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    // 鎖住使用者，避免同時解除兩個身分而全部解除
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let identity_ids =
+        sqlx::query_scalar::<_, Uuid>("SELECT id FROM user_identities WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+    if !identity_ids.contains(&identity_id) {
+        return Err(AppError::not_found("找不到此登入方式"));
+    }
+    if identity_ids.len() <= 1 {
+        return Err(AppError::bad_request("無法解除最後一個登入方式"));
+    }
+
+    sqlx::query("DELETE FROM user_identities WHERE id = $1")
+        .bind(identity_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
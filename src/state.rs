@@ -1,5 +1,6 @@
 // src/state.rs
 
+use crate::config::AppConfig;
 use redis::aio::ConnectionManager;
 use reqwest::Client;
 use sqlx::PgPool;
@@ -9,4 +10,5 @@ pub struct AppState {
     pub db: PgPool,
     pub http_client: Client,
     pub redis: ConnectionManager,
+    pub config: AppConfig,
 }